@@ -0,0 +1,175 @@
+//! Benchmarks for [`multiexp`] and the entry points built on it.
+//!
+//! These are the reference numbers for tuning the multiexp window size, and
+//! the number of exponents below which [`multiexp`] skips the bucket method.
+
+use bellman::{
+    commit::CommitKey,
+    kw15,
+    multicore::Worker,
+    multiexp::{multiexp, multiexp_with_method, DensityTracker, FullDensity},
+};
+use bls12_381::{Bls12, Scalar};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ff::Field;
+use group::{Curve, Group};
+use pairing::Engine;
+use rand_core::{RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use std::sync::Arc;
+
+type G1 = <Bls12 as Engine>::G1;
+type G1Affine = <Bls12 as Engine>::G1Affine;
+
+/// `n` distinct bases, without paying for `n` scalar multiplications.
+fn bases(rng: &mut XorShiftRng, n: usize) -> Vec<G1Affine> {
+    let step = G1::random(&mut *rng);
+    let mut acc = G1::random(&mut *rng);
+    let projective: Vec<G1> = (0..n)
+        .map(|_| {
+            acc += step;
+            acc
+        })
+        .collect();
+    let mut affine = vec![G1Affine::default(); n];
+    G1::batch_normalize(&projective, &mut affine);
+    affine
+}
+
+fn scalars(rng: &mut XorShiftRng, n: usize) -> Vec<Scalar> {
+    (0..n).map(|_| Scalar::random(&mut *rng)).collect()
+}
+
+fn bench_multiexp(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    let pool = Worker::new();
+
+    let mut group = c.benchmark_group("multiexp");
+    group.sample_size(10);
+    for log_n in 12..=20 {
+        let n = 1 << log_n;
+        group.throughput(Throughput::Elements(n as u64));
+
+        let g = Arc::new(bases(&mut rng, n));
+        let v = Arc::new(
+            scalars(&mut rng, n)
+                .iter()
+                .map(|e| e.into())
+                .collect::<Vec<_>>(),
+        );
+
+        group.bench_with_input(BenchmarkId::new("full", n), &n, |b, _| {
+            b.iter(|| {
+                let _: G1 = multiexp(&pool, (g.clone(), 0), FullDensity, v.clone())
+                    .wait()
+                    .unwrap();
+            })
+        });
+
+        // Roughly half of the variables are used, as for the A and B queries of
+        // a typical circuit. Only the used variables have bases, but every
+        // variable has an exponent.
+        let mut density = DensityTracker::new();
+        for i in 0..n {
+            density.add_element();
+            if rng.next_u32() % 2 == 0 {
+                density.inc(i);
+            }
+        }
+        let density = Arc::new(density);
+
+        group.bench_with_input(BenchmarkId::new("sparse", n), &n, |b, _| {
+            b.iter(|| {
+                let _: G1 = multiexp(&pool, (g.clone(), 0), density.clone(), v.clone())
+                    .wait()
+                    .unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_multiexp_small(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+    let pool = Worker::new();
+
+    let mut group = c.benchmark_group("multiexp::small");
+    for &n in [1usize, 2, 4, 8, 16, 24, 32, 36, 40, 44, 48, 56, 64].iter() {
+        let g = Arc::new(bases(&mut rng, n));
+        let v = Arc::new(
+            scalars(&mut rng, n)
+                .iter()
+                .map(|e| e.into())
+                .collect::<Vec<_>>(),
+        );
+
+        for &(name, small) in [("small", true), ("buckets", false)].iter() {
+            group.bench_with_input(BenchmarkId::new(name, n), &n, |b, _| {
+                b.iter(|| {
+                    let _: G1 =
+                        multiexp_with_method(&pool, (g.clone(), 0), FullDensity, v.clone(), small)
+                            .wait()
+                            .unwrap();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_commit(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let mut group = c.benchmark_group("commit");
+    group.sample_size(10);
+    for log_n in (12..=20).step_by(2) {
+        let n = 1 << log_n;
+        group.throughput(Throughput::Elements(n as u64));
+
+        let key = CommitKey::<Bls12>::new(
+            Arc::new(bases(&mut rng, n)),
+            G1::random(&mut rng).to_affine(),
+        );
+        let values = scalars(&mut rng, n);
+        let blind = Scalar::random(&mut rng);
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| key.commit(&values, blind).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_kw15_prove(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let mut group = c.benchmark_group("kw15::prove");
+    group.sample_size(10);
+    // Stops short of 2^20: `key_gen` does a scalar multiplication per witness.
+    for log_n in (12..=18).step_by(2) {
+        let n = 1 << log_n;
+        group.throughput(Throughput::Elements(n as u64));
+
+        // Two commitments, each to half of the witnesses.
+        let mut m = kw15::Matrix::<Bls12>::new(2, n);
+        for (i, base) in bases(&mut rng, n).into_iter().enumerate() {
+            m.add_entry(i * 2 / n, i, base);
+        }
+        let (pk, _) = kw15::key_gen(&m, &mut rng);
+        let wits = scalars(&mut rng, n);
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| kw15::prove(&pk, &wits).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_multiexp,
+    bench_multiexp_small,
+    bench_commit,
+    bench_kw15_prove
+);
+criterion_main!(benches);
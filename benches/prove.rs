@@ -0,0 +1,86 @@
+//! Benchmarks for [`mirage::create_proof`], on a chain of squarings, and for
+//! its FFT stage, [`mirage::compute_h_coeffs`].
+//!
+//! These are the reference numbers for scheduling the prover's multiexps and
+//! FFTs on the worker.
+
+use bellman::{
+    cc::{CcCircuit, CcConstraintSystem},
+    mirage,
+    multicore::Worker,
+    SynthesisError,
+};
+use bls12_381::{Bls12, Scalar};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ff::Field;
+use rand_core::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+/// Proves knowledge of `x` with `x^(2^n)` public, in `n` constraints.
+struct Squarings {
+    x: Option<Scalar>,
+    n: usize,
+}
+
+impl CcCircuit<Scalar> for Squarings {
+    fn synthesize<CS: CcConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut value = self.x;
+        let mut x = cs.alloc(|| "x", || value.ok_or(SynthesisError::AssignmentMissing))?;
+        for i in 0..self.n {
+            value = value.map(|v| v.square());
+            let get = || value.ok_or(SynthesisError::AssignmentMissing);
+            let y = if i + 1 == self.n {
+                cs.alloc_input(|| "out", get)?
+            } else {
+                cs.alloc(|| "y", get)?
+            };
+            cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+            x = y;
+        }
+        Ok(())
+    }
+
+    fn num_aux_blocks(&self) -> usize {
+        0
+    }
+}
+
+fn bench_create_proof(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let mut group = c.benchmark_group("mirage::create_proof");
+    group.sample_size(10);
+    for &n in [1usize << 10, 1 << 12, 1 << 14].iter() {
+        let params =
+            mirage::generate_random_parameters::<Bls12, _, _>(Squarings { x: None, n }, &mut rng)
+                .unwrap();
+        let x = Scalar::random(&mut rng);
+        let (r, s) = (Scalar::random(&mut rng), Scalar::random(&mut rng));
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter(|| mirage::create_proof(Squarings { x: Some(x), n }, &params, r, s, vec![]))
+        });
+    }
+    group.finish();
+}
+
+fn bench_compute_h_coeffs(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let mut group = c.benchmark_group("mirage::compute_h_coeffs");
+    group.sample_size(10);
+    for &n in [1usize << 12, 1 << 14, 1 << 16].iter() {
+        let mut evals = || (0..n).map(|_| Scalar::random(&mut rng)).collect::<Vec<_>>();
+        let (a, b, c) = (evals(), evals(), evals());
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |bencher, _| {
+            bencher.iter(|| {
+                mirage::compute_h_coeffs(a.clone(), b.clone(), c.clone(), Worker::global())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_create_proof, bench_compute_h_coeffs);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//! Benchmarks for [`kw15::verify`], with the verifying key prepared on every
+//! call versus once for all calls.
+
+use bellman::kw15;
+use bls12_381::{Bls12, Scalar};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ff::Field;
+use group::{Curve, Group};
+use pairing::Engine;
+use rand_core::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+type G1 = <Bls12 as Engine>::G1;
+
+fn bench_kw15_verify(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let mut group = c.benchmark_group("kw15::verify");
+    group.sample_size(10);
+    for &num_cmts in [2usize, 8, 32].iter() {
+        // One witness per commitment.
+        let bases: Vec<_> = (0..num_cmts)
+            .map(|_| G1::random(&mut rng).to_affine())
+            .collect();
+        let mut m = kw15::Matrix::<Bls12>::new(num_cmts, num_cmts);
+        for (i, base) in bases.iter().enumerate() {
+            m.add_entry(i, i, *base);
+        }
+        let (pk, vk) = kw15::key_gen(&m, &mut rng);
+        let wits: Vec<Scalar> = (0..num_cmts).map(|_| Scalar::random(&mut rng)).collect();
+        let cmts: Vec<_> = bases
+            .iter()
+            .zip(&wits)
+            .map(|(base, w)| (*base * w).to_affine())
+            .collect();
+        let pf = kw15::prove(&pk, &wits).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("prepare per call", num_cmts),
+            &num_cmts,
+            |b, _| b.iter(|| assert!(kw15::verify(&(&vk).into(), &cmts, &pf))),
+        );
+
+        let pvk = kw15::PreparedVerifyingKey::from(&vk);
+        group.bench_with_input(
+            BenchmarkId::new("prepared once", num_cmts),
+            &num_cmts,
+            |b, _| b.iter(|| assert!(kw15::verify(&pvk, &cmts, &pf))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_kw15_verify);
+criterion_main!(benches);
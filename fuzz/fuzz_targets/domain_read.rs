@@ -0,0 +1,10 @@
+//! `EvaluationDomain::read` on arbitrary bytes.
+
+#![no_main]
+
+use bellman::domain::{EvaluationDomain, Scalar};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = EvaluationDomain::<bls12_381::Scalar, Scalar<bls12_381::Scalar>>::read(data);
+});
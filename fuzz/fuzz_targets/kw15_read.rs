@@ -0,0 +1,20 @@
+//! The `kw15` key and proof readers on arbitrary bytes, with both point
+//! encodings. The `cp_link` types are the same types.
+
+#![no_main]
+
+use bellman::curve_io::Compression;
+use bellman::kw15::{Proof, ProvingKey, VerifyingKey};
+use bls12_381::Bls12;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for compression in [Compression::Compressed, Compression::Uncompressed] {
+        let _ = ProvingKey::<Bls12>::read_with(data, compression);
+        let _ = VerifyingKey::<Bls12>::read_with(data, compression);
+        let _ = Proof::<Bls12>::read_with(data, compression);
+    }
+    let _ = ProvingKey::<Bls12>::read_checked(data);
+    let _ = VerifyingKey::<Bls12>::read_checked(data);
+    let _ = Proof::<Bls12>::read_framed(data);
+});
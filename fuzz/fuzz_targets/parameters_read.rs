@@ -0,0 +1,13 @@
+//! `mirage::Parameters::read` on arbitrary bytes, with and without the
+//! subgroup checks.
+
+#![no_main]
+
+use bellman::mirage::Parameters;
+use bls12_381::Bls12;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Parameters::<Bls12>::read(data, false);
+    let _ = Parameters::<Bls12>::read(data, true);
+});
@@ -0,0 +1,16 @@
+//! `mirage::Proof::read` on arbitrary bytes: it may fail, but must not panic,
+//! and a proof it accepts must round-trip.
+
+#![no_main]
+
+use bellman::mirage::Proof;
+use bls12_381::Bls12;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proof) = Proof::<Bls12>::read(data) {
+        let mut bytes = vec![];
+        proof.write(&mut bytes).unwrap();
+        assert!(Proof::<Bls12>::read_framed(&bytes[..]).unwrap() == proof);
+    }
+});
@@ -0,0 +1,16 @@
+//! `mirage::TranscriptLog::read` on arbitrary bytes, and replaying what it
+//! accepts.
+
+#![no_main]
+
+use bellman::mirage::{ProofContext, TranscriptLog};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(log) = TranscriptLog::read(data) {
+        let mut bytes = vec![];
+        log.write(&mut bytes).unwrap();
+        assert_eq!(TranscriptLog::read(&bytes[..]).unwrap(), log);
+        let _ = log.replay(&ProofContext::default());
+    }
+});
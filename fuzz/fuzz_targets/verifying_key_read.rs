@@ -0,0 +1,19 @@
+//! `mirage::VerifyingKey::read` and `PreparedVerifyingKey::from_compact_bytes`
+//! on arbitrary bytes.
+
+#![no_main]
+
+use bellman::mirage::{PreparedVerifyingKey, VerifyingKey};
+use bls12_381::Bls12;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(vk) = VerifyingKey::<Bls12>::read(data) {
+        let mut bytes = vec![];
+        vk.write(&mut bytes).unwrap();
+        assert!(VerifyingKey::<Bls12>::read(&bytes[..]).unwrap() == vk);
+    }
+    if let Ok(pvk) = PreparedVerifyingKey::<Bls12>::from_compact_bytes(data) {
+        assert_eq!(pvk.to_compact_bytes(), data);
+    }
+});
@@ -1,7 +1,17 @@
 //! Interface to a commitment-carrying zkSNARK (CC-zkSNARK).
 
-use crate::{ConstraintSystem, SynthesisError, Variable};
+#[cfg(all(feature = "pairing", feature = "prover"))]
+use crate::domain::required_domain_exp;
+#[cfg(all(feature = "pairing", feature = "prover"))]
+use crate::multiexp::Exponent;
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use ff::PrimeField;
+#[cfg(all(feature = "pairing", feature = "prover"))]
+use ff::PrimeFieldBits;
+#[cfg(all(feature = "pairing", feature = "prover"))]
+use pairing::Engine;
+#[cfg(all(feature = "pairing", feature = "prover"))]
+use std::mem;
 
 /// A constraint system builder for a CC-zkSNARK.
 pub trait CcConstraintSystem<Scalar: PrimeField>: ConstraintSystem<Scalar> {
@@ -15,6 +25,9 @@ pub trait CcConstraintSystem<Scalar: PrimeField>: ConstraintSystem<Scalar> {
         A: FnOnce() -> AR,
         AR: Into<String>;
 
+    /// How many aux blocks have been ended so far?
+    fn current_aux_block(&self) -> usize;
+
     /// Allocate a random variable.
     ///
     /// Returns the variable *and* its scalar value (which is filled only in proving mode).
@@ -25,6 +38,20 @@ pub trait CcConstraintSystem<Scalar: PrimeField>: ConstraintSystem<Scalar> {
     where
         A: FnOnce() -> AR,
         AR: Into<String>;
+
+    /// Allocate an auxiliary variable for each of `values`, annotated
+    /// `"{prefix} {i}"`.
+    ///
+    /// Stops at the first error the allocation returns.
+    fn alloc_vec<I>(&mut self, prefix: &str, values: I) -> Result<Vec<Variable>, SynthesisError>
+    where
+        I: Iterator<Item = Result<Scalar, SynthesisError>>,
+    {
+        values
+            .enumerate()
+            .map(|(i, value)| self.alloc(|| format!("{} {}", prefix, i), || value))
+            .collect()
+    }
 }
 
 /// For synthesizing a constraint system for a CC-zkSNARK.
@@ -34,3 +61,407 @@ pub trait CcCircuit<Scalar: PrimeField> {
     /// How many auxilary blocks will this circuit produce?
     fn num_aux_blocks(&self) -> usize;
 }
+
+/// Two circuits synthesized one after the other into the same constraint
+/// system, concatenating their variables and aux blocks.
+///
+/// `a`'s public inputs, random coins and blocks come before `b`'s. Any
+/// auxiliary variables `a` allocates after its last block are not committed
+/// separately: they become part of `b`'s first block.
+pub struct ComposedCircuit<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+impl<A, B> ComposedCircuit<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        ComposedCircuit { a, b }
+    }
+}
+
+impl<Scalar, A, B> CcCircuit<Scalar> for ComposedCircuit<A, B>
+where
+    Scalar: PrimeField,
+    A: CcCircuit<Scalar>,
+    B: CcCircuit<Scalar>,
+{
+    fn synthesize<CS: CcConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.a.synthesize(cs)?;
+        self.b.synthesize(cs)
+    }
+
+    fn num_aux_blocks(&self) -> usize {
+        self.a.num_aux_blocks() + self.b.num_aux_blocks()
+    }
+}
+
+/// The size of a synthesized circuit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// Public inputs, including the constant one and random coins.
+    pub num_inputs: usize,
+    /// Random coins.
+    pub num_coins: usize,
+    /// Auxiliary variables.
+    pub num_aux: usize,
+    /// Constraints, excluding the input constraints that the provers add.
+    pub num_constraints: usize,
+    /// Ended aux blocks.
+    pub num_aux_blocks: usize,
+}
+
+/// A constraint system that only counts what a circuit allocates, without
+/// computing any assignments or linear combinations.
+pub struct CountingConstraintSystem {
+    stats: CircuitStats,
+}
+
+impl Default for CountingConstraintSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CountingConstraintSystem {
+    /// Create an empty constraint system, with the "one" input allocated.
+    pub fn new() -> Self {
+        CountingConstraintSystem {
+            stats: CircuitStats {
+                num_inputs: 1,
+                ..CircuitStats::default()
+            },
+        }
+    }
+
+    pub fn stats(&self) -> CircuitStats {
+        self.stats
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for CountingConstraintSystem {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.stats.num_aux += 1;
+
+        Ok(Variable(Index::Aux(self.stats.num_aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.stats.num_inputs += 1;
+
+        Ok(Variable(Index::Input(self.stats.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, _: LA, _: LB, _: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.stats.num_constraints += 1;
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+impl<Scalar: PrimeField> CcConstraintSystem<Scalar> for CountingConstraintSystem {
+    fn end_aux_block<A, AR>(&mut self, _: A) -> Result<(), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.stats.num_aux_blocks += 1;
+        Ok(())
+    }
+
+    fn current_aux_block(&self) -> usize {
+        self.stats.num_aux_blocks
+    }
+
+    fn alloc_random<A, AR>(
+        &mut self,
+        annotation: A,
+    ) -> Result<(Variable, Option<Scalar>), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let var = ConstraintSystem::<Scalar>::alloc_input(self, annotation, || {
+            Err(SynthesisError::AssignmentMissing)
+        })?;
+        self.stats.num_coins += 1;
+        Ok((var, None))
+    }
+}
+
+/// Count the variables, constraints and aux blocks of `circuit`, for example
+/// to size the parameters before an expensive setup.
+///
+/// The circuit is synthesized without assignments, as in parameter
+/// generation.
+pub fn synthesize_count<Scalar, C>(circuit: C) -> Result<CircuitStats, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: CcCircuit<Scalar>,
+{
+    let mut cs = CountingConstraintSystem::new();
+    circuit.synthesize(&mut cs)?;
+    Ok(cs.stats())
+}
+
+/// A rough estimate, in bytes, of the peak memory of a mirage proof of a
+/// circuit of size `stats` over `E`, with the parameters held in memory, for
+/// deciding whether a proof fits in the available RAM.
+///
+/// This counts the dominant terms: the multiexp bases of the parameters, the
+/// A, B and C evaluations padded to a radix-2 evaluation domain, and the
+/// assignment and its multiexp exponents. It leaves out the multiexp
+/// buckets, the constraints themselves and constant overheads.
+#[cfg(all(feature = "pairing", feature = "prover"))]
+pub fn estimate_proving_memory<E>(stats: &CircuitStats) -> usize
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+{
+    let fr = mem::size_of::<E::Fr>();
+    let exponent = mem::size_of::<Exponent<E::Fr>>();
+    let g1 = mem::size_of::<E::G1Affine>();
+    let g2 = mem::size_of::<E::G2Affine>();
+
+    let num_vars = stats.num_inputs + stats.num_aux;
+    // The provers add an input constraint per input.
+    let m = 1 << required_domain_exp(stats.num_constraints + stats.num_inputs);
+
+    // The A and B queries (B in both groups) cover every variable, L the aux
+    // variables, and H the domain.
+    let bases = num_vars * (2 * g1 + g2) + stats.num_aux * g1 + (m - 1) * g1;
+    let domains = 3 * m * fr;
+    // The assignment and its exponents, and the exponents of H.
+    let assignment = num_vars * (fr + exponent) + m * exponent;
+    bases + domains + assignment
+}
+
+/// Values for the variables of a circuit, keyed by variable, for synthesizing
+/// a circuit's structure separately from its witness (see [`WitnessLookup`]).
+#[derive(Clone, Debug)]
+pub struct WitnessMap<Scalar: PrimeField> {
+    inputs: Vec<Option<Scalar>>,
+    aux: Vec<Option<Scalar>>,
+}
+
+impl<Scalar: PrimeField> Default for WitnessMap<Scalar> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Scalar: PrimeField> WitnessMap<Scalar> {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        WitnessMap {
+            inputs: vec![],
+            aux: vec![],
+        }
+    }
+
+    /// Set the value of `var`, replacing any previous value.
+    pub fn insert(&mut self, var: Variable, value: Scalar) {
+        let (values, i) = match var.get_unchecked() {
+            Index::Input(i) => (&mut self.inputs, i),
+            Index::Aux(i) => (&mut self.aux, i),
+        };
+        if values.len() <= i {
+            values.resize(i + 1, None);
+        }
+        values[i] = Some(value);
+    }
+
+    /// The value of `var`, if it has one.
+    pub fn get(&self, var: Variable) -> Option<Scalar> {
+        let (values, i) = match var.get_unchecked() {
+            Index::Input(i) => (&self.inputs, i),
+            Index::Aux(i) => (&self.aux, i),
+        };
+        values.get(i).cloned().flatten()
+    }
+}
+
+/// A constraint system that assigns variables from a [`WitnessMap`] rather than
+/// with the closures passed to `alloc` and `alloc_input`, which are never
+/// called.
+///
+/// Everything else is forwarded to the wrapped constraint system, which must
+/// be fresh (with only the "one" input allocated), so that the variables it
+/// allocates are the ones the map is keyed by. Random coins come from the
+/// wrapped constraint system, so the witness can't depend on them.
+pub struct WitnessLookup<'a, Scalar: PrimeField, CS> {
+    cs: &'a mut CS,
+    witness: &'a WitnessMap<Scalar>,
+    num_inputs: usize,
+    num_aux: usize,
+}
+
+impl<'a, Scalar: PrimeField, CS: CcConstraintSystem<Scalar>> WitnessLookup<'a, Scalar, CS> {
+    pub fn new(cs: &'a mut CS, witness: &'a WitnessMap<Scalar>) -> Self {
+        WitnessLookup {
+            cs,
+            witness,
+            num_inputs: 1,
+            num_aux: 0,
+        }
+    }
+
+    fn lookup(&self, var: Variable) -> Result<Scalar, SynthesisError> {
+        self.witness
+            .get(var)
+            .ok_or(SynthesisError::AssignmentMissing)
+    }
+}
+
+impl<'a, Scalar, CS> ConstraintSystem<Scalar> for WitnessLookup<'a, Scalar, CS>
+where
+    Scalar: PrimeField,
+    CS: CcConstraintSystem<Scalar>,
+{
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let value = self.lookup(Variable(Index::Aux(self.num_aux)));
+        let var = self.cs.alloc(annotation, || value)?;
+        self.num_aux += 1;
+        Ok(var)
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, _: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let value = self.lookup(Variable(Index::Input(self.num_inputs)));
+        let var = self.cs.alloc_input(annotation, || value)?;
+        self.num_inputs += 1;
+        Ok(var)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.cs.enforce(annotation, a, b, c);
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.cs.get_root().push_namespace(name_fn);
+    }
+
+    fn pop_namespace(&mut self) {
+        self.cs.get_root().pop_namespace();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+impl<'a, Scalar, CS> CcConstraintSystem<Scalar> for WitnessLookup<'a, Scalar, CS>
+where
+    Scalar: PrimeField,
+    CS: CcConstraintSystem<Scalar>,
+{
+    fn end_aux_block<A, AR>(&mut self, annotation: A) -> Result<(), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.cs.end_aux_block(annotation)
+    }
+
+    fn current_aux_block(&self) -> usize {
+        self.cs.current_aux_block()
+    }
+
+    fn alloc_random<A, AR>(
+        &mut self,
+        annotation: A,
+    ) -> Result<(Variable, Option<Scalar>), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let coin = self.cs.alloc_random(annotation)?;
+        self.num_inputs += 1;
+        Ok(coin)
+    }
+}
+
+/// A circuit's structure together with a witness for it, synthesized through
+/// a [`WitnessLookup`].
+///
+/// Like [`WitnessLookup`], this must be synthesized into a fresh constraint
+/// system, so it can't be the second half of a [`ComposedCircuit`].
+pub struct WitnessedCircuit<'a, Scalar: PrimeField, C> {
+    pub circuit: C,
+    pub witness: &'a WitnessMap<Scalar>,
+}
+
+impl<'a, Scalar: PrimeField, C> WitnessedCircuit<'a, Scalar, C> {
+    pub fn new(circuit: C, witness: &'a WitnessMap<Scalar>) -> Self {
+        WitnessedCircuit { circuit, witness }
+    }
+}
+
+impl<'a, Scalar, C> CcCircuit<Scalar> for WitnessedCircuit<'a, Scalar, C>
+where
+    Scalar: PrimeField,
+    C: CcCircuit<Scalar>,
+{
+    fn synthesize<CS: CcConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.circuit
+            .synthesize(&mut WitnessLookup::new(cs, self.witness))
+    }
+
+    fn num_aux_blocks(&self) -> usize {
+        self.circuit.num_aux_blocks()
+    }
+}
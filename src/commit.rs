@@ -1,20 +1,50 @@
 //! Pedersen commitments
 
-use ff::PrimeFieldBits;
+#[cfg(feature = "prover")]
+use ff::{Field, PrimeFieldBits};
+#[cfg(feature = "prover")]
+use group::{Curve, Group};
+#[cfg(feature = "prover")]
 use pairing::Engine;
+#[cfg(feature = "prover")]
+use std::fmt;
+#[cfg(feature = "prover")]
 use std::sync::Arc;
 
+#[cfg(feature = "prover")]
 use crate::multicore::Worker;
+#[cfg(feature = "prover")]
 use crate::multiexp::{multiexp, FullDensity};
+#[cfg(feature = "prover")]
+use crate::SynthesisError;
 
 pub mod cp_link;
 
 /// A commitment key
+///
+/// A key commits to vectors of up to [`CommitKey::max_len`] values. Shorter
+/// vectors use only the first generators, which is the same as padding them
+/// with zeros.
+#[cfg(feature = "prover")]
 pub struct CommitKey<E: Engine> {
     generators: Arc<Vec<E::G1Affine>>,
     blind_generator: E::G1Affine,
 }
 
+/// Shows the number of generators, not the generators themselves.
+#[cfg(feature = "prover")]
+impl<E: Engine> fmt::Debug for CommitKey<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommitKey")
+            .field(
+                "generators",
+                &format_args!("{} points", self.generators.len()),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "prover")]
 impl<E> CommitKey<E>
 where
     E: Engine,
@@ -26,31 +56,407 @@ where
             blind_generator,
         }
     }
+
+    /// The length of the longest vector this key can commit to.
+    pub fn max_len(&self) -> usize {
+        self.generators.len()
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), SynthesisError> {
+        if len > self.max_len() {
+            return Err(SynthesisError::LengthMismatch);
+        }
+        Ok(())
+    }
 }
 
+#[cfg(feature = "prover")]
 impl<E> CommitKey<E>
 where
     E: Engine,
     E::Fr: PrimeFieldBits,
 {
     /// Commit to a list of values, with some blind.
-    pub fn commit(&self, values: &[E::Fr], blind: E::Fr) -> E::G1 {
-        let worker = Worker::new();
+    ///
+    /// Fails with [`SynthesisError::LengthMismatch`] if there are more values
+    /// than [`Self::max_len`], and with [`SynthesisError::UnexpectedIdentity`]
+    /// if the key has an identity generator.
+    pub fn commit(&self, values: &[E::Fr], blind: E::Fr) -> Result<E::G1, SynthesisError> {
+        self.commit_with(Worker::global(), values, blind)
+    }
+
+    /// Like [`Self::commit`], but running the multiexp on `worker`.
+    pub fn commit_with(
+        &self,
+        worker: &Worker,
+        values: &[E::Fr],
+        blind: E::Fr,
+    ) -> Result<E::G1, SynthesisError> {
+        self.check_len(values.len())?;
         let exponents = Arc::new(
             values
                 .into_iter()
                 .map(|s| s.clone().into())
                 .collect::<Vec<_>>(),
         );
-        let mut commitment: E::G1 = multiexp(
-            &worker,
-            (self.generators.clone(), 0),
+        let mut commitment: E::G1 =
+            multiexp(worker, (self.generators.clone(), 0), FullDensity, exponents).wait()?;
+        commitment += &(self.blind_generator * blind);
+        Ok(commitment)
+    }
+
+    /// Commit to a list of signed values, with some blind.
+    ///
+    /// Each value is `(negate, value)`: values with `negate` set are subtracted rather than
+    /// added, which is the same as committing to their negations. Fails as
+    /// [`Self::commit`] does.
+    pub fn commit_signed(
+        &self,
+        values: &[(bool, E::Fr)],
+        blind: E::Fr,
+    ) -> Result<E::G1, SynthesisError> {
+        self.commit_signed_with(Worker::global(), values, blind)
+    }
+
+    /// Like [`Self::commit_signed`], but running the multiexp on `worker`.
+    pub fn commit_signed_with(
+        &self,
+        worker: &Worker,
+        values: &[(bool, E::Fr)],
+        blind: E::Fr,
+    ) -> Result<E::G1, SynthesisError> {
+        self.check_len(values.len())?;
+        let exponents = Arc::new(
+            values
+                .iter()
+                .map(|(negate, s)| if *negate { (-*s).into() } else { s.into() })
+                .collect::<Vec<_>>(),
+        );
+        let mut commitment: E::G1 =
+            multiexp(worker, (self.generators.clone(), 0), FullDensity, exponents).wait()?;
+        commitment += &(self.blind_generator * blind);
+        Ok(commitment)
+    }
+
+    /// Commit to each of `values`, with the matching blind from `blinds`.
+    ///
+    /// The multiexps share the key's generators and run concurrently. Fails as
+    /// [`Self::commit`] does, if it would for any of the vectors, and with
+    /// [`SynthesisError::LengthMismatch`] if there is not one blind per vector.
+    pub fn commit_batch(
+        &self,
+        values: &[Vec<E::Fr>],
+        blinds: &[E::Fr],
+    ) -> Result<Vec<E::G1>, SynthesisError> {
+        self.commit_batch_with(Worker::global(), values, blinds)
+    }
+
+    /// Like [`Self::commit_batch`], but running the multiexps on `worker`.
+    pub fn commit_batch_with(
+        &self,
+        worker: &Worker,
+        values: &[Vec<E::Fr>],
+        blinds: &[E::Fr],
+    ) -> Result<Vec<E::G1>, SynthesisError> {
+        if values.len() != blinds.len() {
+            return Err(SynthesisError::LengthMismatch);
+        }
+        for values in values {
+            self.check_len(values.len())?;
+        }
+        // Start every multiexp before waiting on any of them.
+        let pending: Vec<_> = values
+            .iter()
+            .map(|values| {
+                let exponents = Arc::new(values.iter().map(|s| s.into()).collect::<Vec<_>>());
+                multiexp(worker, (self.generators.clone(), 0), FullDensity, exponents)
+            })
+            .collect();
+        pending
+            .into_iter()
+            .zip(blinds)
+            .map(|(pending, blind)| {
+                let mut commitment: E::G1 = pending.wait()?;
+                commitment += &(self.blind_generator * blind);
+                Ok(commitment)
+            })
+            .collect()
+    }
+
+    /// Combine commitments under the same key into `sum_i coeffs[i] *
+    /// commitments[i]`, which is a commitment to the combination of their
+    /// openings computed by [`Self::combine_openings`].
+    ///
+    /// Fails with [`SynthesisError::LengthMismatch`] if there is not one
+    /// coefficient per commitment.
+    pub fn combine(commitments: &[E::G1], coeffs: &[E::Fr]) -> Result<E::G1, SynthesisError> {
+        if commitments.len() != coeffs.len() {
+            return Err(SynthesisError::LengthMismatch);
+        }
+        // The multiexp rejects identity bases, which add nothing anyway.
+        let (bases, exponents): (Vec<_>, Vec<_>) = commitments
+            .iter()
+            .zip(coeffs)
+            .filter(|(c, _)| !bool::from(c.is_identity()))
+            .map(|(c, coeff)| (*c, coeff.into()))
+            .unzip();
+        let mut affine = vec![E::G1::identity().to_affine(); bases.len()];
+        E::G1::batch_normalize(&bases, &mut affine);
+        multiexp(
+            Worker::global(),
+            (Arc::new(affine), 0),
             FullDensity,
-            exponents,
+            Arc::new(exponents),
         )
         .wait()
-        .unwrap();
-        commitment += &(self.blind_generator * blind);
-        commitment
+    }
+
+    /// Combine openings `(values, blind)` into `sum_i coeffs[i] *
+    /// openings[i]`, which opens the commitment computed by [`Self::combine`].
+    /// Shorter vectors are padded with zeros.
+    ///
+    /// Fails with [`SynthesisError::LengthMismatch`] if there is not one
+    /// coefficient per opening.
+    pub fn combine_openings(
+        openings: &[(Vec<E::Fr>, E::Fr)],
+        coeffs: &[E::Fr],
+    ) -> Result<(Vec<E::Fr>, E::Fr), SynthesisError> {
+        if openings.len() != coeffs.len() {
+            return Err(SynthesisError::LengthMismatch);
+        }
+        let len = openings.iter().map(|(values, _)| values.len()).max();
+        let mut combined = vec![E::Fr::zero(); len.unwrap_or(0)];
+        let mut blind = E::Fr::zero();
+        for ((values, b), coeff) in openings.iter().zip(coeffs) {
+            for (c, v) in combined.iter_mut().zip(values) {
+                *c += *v * coeff;
+            }
+            blind += *b * coeff;
+        }
+        Ok((combined, blind))
+    }
+
+    /// Check that `(values, blind)` is an opening of `commitment`.
+    ///
+    /// The commitment is recomputed and compared by checking that the difference is the
+    /// identity, which is constant-time in the group implementation. There is no opening with
+    /// more values than [`Self::max_len`].
+    pub fn verify_open(&self, commitment: &E::G1, values: &[E::Fr], blind: E::Fr) -> bool {
+        match self.commit(values, blind) {
+            Ok(recomputed) => bool::from((recomputed - commitment).is_identity()),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod test {
+    use super::*;
+    use bls12_381::{Bls12, Scalar};
+    use ff::Field;
+    use rand_core::RngCore;
+
+    fn rand_ck<E: Engine, R: RngCore>(len: usize, rng: &mut R) -> CommitKey<E> {
+        CommitKey::new(
+            Arc::new(
+                std::iter::repeat_with(|| E::G1::random(&mut *rng).into())
+                    .take(len)
+                    .collect(),
+            ),
+            E::G1::random(&mut *rng).into(),
+        )
+    }
+
+    #[test]
+    fn commit_batch() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        // Vectors may be shorter than the key.
+        let values: Vec<Vec<Scalar>> = [10, 3, 0, 10]
+            .iter()
+            .map(|len| (0..*len).map(|_| Scalar::random(&mut *rng)).collect())
+            .collect();
+        let blinds: Vec<Scalar> = (0..values.len())
+            .map(|_| Scalar::random(&mut *rng))
+            .collect();
+        let expected: Vec<_> = values
+            .iter()
+            .zip(&blinds)
+            .map(|(values, blind)| ck.commit(values, *blind).unwrap())
+            .collect();
+        assert_eq!(ck.commit_batch(&values, &blinds).unwrap(), expected);
+        assert!(ck.commit_batch(&[], &[]).unwrap().is_empty());
+        assert!(matches!(
+            ck.commit_batch(&values, &blinds[1..]),
+            Err(SynthesisError::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn shorter_than_key() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        assert_eq!(ck.max_len(), 10);
+        let values: Vec<_> = (0..5).map(|_| Scalar::random(&mut *rng)).collect();
+        let blind = Scalar::random(&mut *rng);
+        let commitment = ck.commit(&values, blind).unwrap();
+
+        // The same as zero-padding the vector to the length of the key...
+        let mut padded = values.clone();
+        padded.resize(10, Scalar::zero());
+        assert_eq!(ck.commit(&padded, blind).unwrap(), commitment);
+        assert!(ck.verify_open(&commitment, &padded, blind));
+
+        // ... or as truncating the key to the length of the vector.
+        let short_ck =
+            CommitKey::<Bls12>::new(Arc::new(ck.generators[..5].to_vec()), ck.blind_generator);
+        assert_eq!(short_ck.max_len(), 5);
+        assert_eq!(short_ck.commit(&values, blind).unwrap(), commitment);
+
+        // Vectors longer than the key are rejected.
+        assert!(matches!(
+            short_ck.commit(&padded, blind),
+            Err(SynthesisError::LengthMismatch)
+        ));
+        assert!(matches!(
+            short_ck.commit_batch(&[values, padded.clone()], &[blind, blind]),
+            Err(SynthesisError::LengthMismatch)
+        ));
+        assert!(!short_ck.verify_open(&commitment, &padded, blind));
+    }
+
+    #[test]
+    fn identity_generator() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(3, rng);
+        let mut generators = ck.generators.to_vec();
+        generators[1] = <Bls12 as Engine>::G1Affine::identity();
+        let ck = CommitKey::<Bls12>::new(Arc::new(generators), ck.blind_generator);
+        assert!(matches!(
+            ck.commit(&[Scalar::one(); 3], Scalar::one()),
+            Err(SynthesisError::UnexpectedIdentity)
+        ));
+    }
+
+    #[test]
+    fn verify_open() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        let mut values: Vec<_> = (0..10).map(|_| Scalar::random(&mut *rng)).collect();
+        let blind = Scalar::random(&mut *rng);
+        let commitment = ck.commit(&values, blind).unwrap();
+        assert!(ck.verify_open(&commitment, &values, blind));
+
+        values[3] += Scalar::one();
+        assert!(!ck.verify_open(&commitment, &values, blind));
+        values[3] -= Scalar::one();
+        assert!(!ck.verify_open(&commitment, &values, blind + Scalar::one()));
+    }
+
+    #[test]
+    fn combine() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        let openings: Vec<(Vec<Scalar>, Scalar)> = [10, 4, 0]
+            .iter()
+            .map(|len| {
+                let values = (0..*len).map(|_| Scalar::random(&mut *rng)).collect();
+                (values, Scalar::random(&mut *rng))
+            })
+            .collect();
+        let commitments: Vec<_> = openings
+            .iter()
+            .map(|(values, blind)| ck.commit(values, *blind).unwrap())
+            .collect();
+        let coeffs: Vec<_> = (0..3).map(|_| Scalar::random(&mut *rng)).collect();
+
+        let combined = CommitKey::<Bls12>::combine(&commitments, &coeffs).unwrap();
+        let (values, blind) = CommitKey::<Bls12>::combine_openings(&openings, &coeffs).unwrap();
+        assert_eq!(values.len(), 10);
+        assert!(ck.verify_open(&combined, &values, blind));
+        assert!(!ck.verify_open(&combined, &openings[0].0, blind));
+
+        // Combining one commitment with coefficient one leaves it unchanged.
+        assert_eq!(
+            CommitKey::<Bls12>::combine(&commitments[..1], &[Scalar::one()]).unwrap(),
+            commitments[0]
+        );
+        // The identity, a commitment to nothing with a zero blind, adds nothing.
+        let with_identity = [commitments[0], <Bls12 as Engine>::G1::identity()];
+        assert_eq!(
+            CommitKey::<Bls12>::combine(&with_identity, &[Scalar::one(); 2]).unwrap(),
+            commitments[0]
+        );
+        assert!(bool::from(
+            CommitKey::<Bls12>::combine(&[], &[]).unwrap().is_identity()
+        ));
+        assert!(matches!(
+            CommitKey::<Bls12>::combine(&commitments, &coeffs[..2]),
+            Err(SynthesisError::LengthMismatch)
+        ));
+        assert!(matches!(
+            CommitKey::<Bls12>::combine_openings(&openings[..2], &coeffs),
+            Err(SynthesisError::LengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn commit_signed() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        let signed: Vec<_> = (0..10)
+            .map(|i| (i % 2 == 1, Scalar::random(&mut *rng)))
+            .collect();
+        let values: Vec<_> = signed
+            .iter()
+            .map(|(negate, s)| if *negate { -*s } else { *s })
+            .collect();
+        let blind = Scalar::random(&mut *rng);
+        assert_eq!(
+            ck.commit_signed(&signed, blind).unwrap(),
+            ck.commit(&values, blind).unwrap()
+        );
+    }
+
+    #[test]
+    fn commit_with_worker() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = rand_ck::<Bls12, _>(10, rng);
+        let values: Vec<_> = (0..10).map(|_| Scalar::random(&mut *rng)).collect();
+        let blind = Scalar::random(&mut *rng);
+        let commitment = ck.commit(&values, blind).unwrap();
+
+        // One worker can be shared across many commitments.
+        let worker = Worker::with_log_threads(2);
+        for _ in 0..3 {
+            assert_eq!(ck.commit_with(&worker, &values, blind).unwrap(), commitment);
+        }
+        let signed: Vec<_> = values.iter().map(|s| (false, *s)).collect();
+        assert_eq!(
+            ck.commit_signed_with(&worker, &signed, blind).unwrap(),
+            commitment
+        );
+    }
+
+    #[test]
+    fn concurrent_global_commits() {
+        let rng = &mut crate::testing::test_rng();
+        let ck = Arc::new(rand_ck::<Bls12, _>(64, rng));
+        let values: Arc<Vec<_>> = Arc::new((0..64).map(|_| Scalar::random(&mut *rng)).collect());
+        let blind = Scalar::random(&mut *rng);
+        let expected = ck.commit(&values, blind).unwrap();
+
+        // Both threads run their multiexps on the global worker at once.
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let (ck, values) = (ck.clone(), values.clone());
+                std::thread::spawn(move || {
+                    ck.commit_with(Worker::global(), &values, blind).unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
     }
 }
@@ -1,19 +1,37 @@
 /// Relation:
 /// * Index
-///   * K be a commitment key to a length-n vector
-///   * Ji be commitment keys for i in 0..k
+///   * K be a commitment key to a vector of length at least max(ni)
+///   * Ji be commitment keys to length-ni vectors for i in 0..k
 /// * Instance: (Ci, Di) for i in 0..k
-/// * Witness: Xi (vector of length-n) for i in 0..k, ri for i in 0..k
-/// * Relation: Ci = Commit(K, Xi, ri) and Di = Commit(Ji, Xi, ri)
+/// * Witness: Xi (vector of length-ni) for i in 0..k, ri and si for i in 0..k
+///
+/// Ci commits to Xi using the first ni generators of K.
+/// * Relation: Ci = Commit(K, Xi, ri) and Di = Commit(Ji, Xi, si)
+///
+/// The blinds are independent: ri is the `rands_1` argument of `prove` and si
+/// the `rands_2` argument.
+#[cfg(feature = "prover")]
 use super::*;
 use crate::kw15;
 use pairing::MultiMillerLoop;
+#[cfg(feature = "prover")]
 use rand_core::RngCore;
 
-type ProvingKey<E> = kw15::ProvingKey<E>;
-type VerifyingKey<E> = kw15::VerifyingKey<E>;
-type Proof<E> = kw15::Proof<E>;
+/// A [`kw15`] proving key. Read untrusted keys with
+/// [`kw15::ProvingKey::read_checked`].
+#[cfg(feature = "prover")]
+pub type ProvingKey<E> = kw15::ProvingKey<E>;
+/// A [`kw15`] verifying key. Read untrusted keys with
+/// [`kw15::VerifyingKey::read_checked`].
+pub type VerifyingKey<E> = kw15::VerifyingKey<E>;
+/// A [`kw15`] proof. Read untrusted proofs with [`kw15::Proof::read_checked`].
+pub type Proof<E> = kw15::Proof<E>;
+/// A [`kw15`] prepared verifying key, built from a [`VerifyingKey`] with
+/// `PreparedVerifyingKey::from(&vk)`. Build it once to verify many proofs with
+/// [`verify_prepared`] and [`verify_single_prepared`].
+pub type PreparedVerifyingKey<E> = kw15::PreparedVerifyingKey<E>;
 
+#[cfg(feature = "prover")]
 pub fn key_gen<E, R>(
     k: &CommitKey<E>,
     js: &[CommitKey<E>],
@@ -24,34 +42,41 @@ where
     R: RngCore,
 {
     let n_commits = 2 * js.len();
-    let len = k.generators.len();
-    let n_wits = (len + 2) * js.len();
+    let n_wits = js.iter().map(|j| j.generators.len() + 2).sum();
     let mut matrix = kw15::Matrix::new(n_commits, n_wits);
-    // witness layout: repeat(vec || rand_1 || rand_2)
+    // witness layout: repeat(vec_i || rand_1 || rand_2), where vec_i has length len_i
     // commitments layout: repeat(com_1 || com_2)
-    for vec_i in 0..js.len() {
+    let mut wit_i_start = 0;
+    for (vec_i, j_key) in js.iter().enumerate() {
+        let len = j_key.generators.len();
+        assert!(
+            len <= k.generators.len(),
+            "commitment key K is shorter than J_{}",
+            vec_i
+        );
         let cmt_i_1 = 2 * vec_i;
         let cmt_i_2 = 2 * vec_i + 1;
-        let wit_i_start = (len + 2) * vec_i;
-        let rand_i_1 = (len + 2) * vec_i + len;
-        let rand_i_2 = (len + 2) * vec_i + len + 1;
+        let rand_i_1 = wit_i_start + len;
+        let rand_i_2 = wit_i_start + len + 1;
         for j in 0..len {
             let wit_i = wit_i_start + j;
             matrix.add_entry(cmt_i_1, wit_i, k.generators[j]);
-            matrix.add_entry(cmt_i_1, wit_i, js[vec_i].generators[j]);
+            matrix.add_entry(cmt_i_2, wit_i, j_key.generators[j]);
         }
         matrix.add_entry(cmt_i_1, rand_i_1, k.blind_generator);
-        matrix.add_entry(cmt_i_2, rand_i_2, js[vec_i].blind_generator);
+        matrix.add_entry(cmt_i_2, rand_i_2, j_key.blind_generator);
+        wit_i_start += len + 2;
     }
     kw15::key_gen(&matrix, rng)
 }
 
+#[cfg(feature = "prover")]
 pub fn prove<E>(
     pk: &ProvingKey<E>,
     vectors: Vec<Vec<E::Fr>>,
     rands_1: Vec<E::Fr>,
     rands_2: Vec<E::Fr>,
-) -> Proof<E>
+) -> Result<Proof<E>, SynthesisError>
 where
     E: Engine,
     E::Fr: PrimeFieldBits,
@@ -65,6 +90,8 @@ where
     kw15::prove(pk, &wit)
 }
 
+/// Prepares `vk` on every call; use [`verify_prepared`] to verify many proofs
+/// against one key.
 pub fn verify<E>(
     vk: &VerifyingKey<E>,
     cmts_1: &[E::G1Affine],
@@ -74,14 +101,93 @@ pub fn verify<E>(
 where
     E: MultiMillerLoop,
 {
+    verify_prepared(&PreparedVerifyingKey::from(vk), cmts_1, cmts_2, pf)
+}
+
+/// [`verify`] with a key prepared once.
+pub fn verify_prepared<E>(
+    pvk: &PreparedVerifyingKey<E>,
+    cmts_1: &[E::G1Affine],
+    cmts_2: &[E::G1Affine],
+    pf: &Proof<E>,
+) -> bool
+where
+    E: MultiMillerLoop,
+{
+    kw15::verify(pvk, &kw15_cmts::<E>(cmts_1, cmts_2), pf)
+}
+
+/// The [`kw15`] commitments of the statement: `cmts_1[i]` and `cmts_2[i]`,
+/// interleaved.
+pub(crate) fn kw15_cmts<E: MultiMillerLoop>(
+    cmts_1: &[E::G1Affine],
+    cmts_2: &[E::G1Affine],
+) -> Vec<E::G1Affine> {
     let mut cmts: Vec<E::G1Affine> = Vec::new();
-    for (c1, c2) in cmts_1.into_iter().zip(cmts_2) {
+    for (c1, c2) in cmts_1.iter().zip(cmts_2) {
         cmts.push(*c1);
         cmts.push(*c2);
     }
-    let pvk = kw15::PreparedVerifyingKey::from(vk);
-    kw15::verify(&pvk, &cmts, pf)
+    cmts
+}
+
+/// [`key_gen`] for a single commitment.
+#[cfg(feature = "prover")]
+pub fn key_gen_single<E, R>(
+    k: &CommitKey<E>,
+    j: &CommitKey<E>,
+    rng: &mut R,
+) -> (ProvingKey<E>, VerifyingKey<E>)
+where
+    E: Engine,
+    R: RngCore,
+{
+    key_gen(k, std::slice::from_ref(j), rng)
+}
+
+/// [`prove`] for a single commitment.
+#[cfg(feature = "prover")]
+pub fn prove_single<E>(
+    pk: &ProvingKey<E>,
+    vector: Vec<E::Fr>,
+    rand_1: E::Fr,
+    rand_2: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+{
+    let mut wit = vector;
+    wit.push(rand_1);
+    wit.push(rand_2);
+    kw15::prove(pk, &wit)
+}
+
+/// [`verify`] for a single commitment.
+pub fn verify_single<E>(
+    vk: &VerifyingKey<E>,
+    cmt_1: &E::G1Affine,
+    cmt_2: &E::G1Affine,
+    pf: &Proof<E>,
+) -> bool
+where
+    E: MultiMillerLoop,
+{
+    verify_single_prepared(&PreparedVerifyingKey::from(vk), cmt_1, cmt_2, pf)
+}
+
+/// [`verify_prepared`] for a single commitment.
+pub fn verify_single_prepared<E>(
+    pvk: &PreparedVerifyingKey<E>,
+    cmt_1: &E::G1Affine,
+    cmt_2: &E::G1Affine,
+    pf: &Proof<E>,
+) -> bool
+where
+    E: MultiMillerLoop,
+{
+    kw15::verify(pvk, &[*cmt_1, *cmt_2], pf)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod test;
@@ -15,52 +15,61 @@ fn rand_ck<E: Engine, R: RngCore>(len: usize, rng: &mut R) -> CommitKey<E> {
     )
 }
 
-/// Returns (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2)
-fn random_statement<E, R>(
-    num_cmts: usize,
-    len: usize,
-    rng: &mut R,
-) -> (
-    Vec<Vec<E::Fr>>,
-    Vec<E::Fr>,
-    Vec<E::Fr>,
+/// (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2)
+type Statement<E> = (
+    Vec<Vec<<E as Engine>::Fr>>,
+    Vec<<E as Engine>::Fr>,
+    Vec<<E as Engine>::Fr>,
     CommitKey<E>,
     Vec<CommitKey<E>>,
-    Vec<E::G1Affine>,
-    Vec<E::G1Affine>,
-)
+    Vec<<E as Engine>::G1Affine>,
+    Vec<<E as Engine>::G1Affine>,
+);
+
+fn random_statement<E, R>(num_cmts: usize, len: usize, rng: &mut R) -> Statement<E>
 where
     E: Engine,
     R: RngCore,
     E::Fr: PrimeFieldBits,
 {
-    let ck1 = rand_ck(len, rng);
-    let cks2: Vec<_> = std::iter::repeat_with(|| rand_ck(len, rng))
-        .take(num_cmts)
+    random_statement_with_lens(&vec![len; num_cmts], rng)
+}
+
+/// Like [`random_statement`], but the i-th committed vector has length `lens[i]`.
+fn random_statement_with_lens<E, R>(lens: &[usize], rng: &mut R) -> Statement<E>
+where
+    E: Engine,
+    R: RngCore,
+    E::Fr: PrimeFieldBits,
+{
+    let num_cmts = lens.len();
+    let ck1 = rand_ck(lens.iter().copied().max().unwrap_or(0), rng);
+    let cks2: Vec<_> = lens.iter().map(|len| rand_ck(*len, rng)).collect();
+    let vecs: Vec<Vec<E::Fr>> = lens
+        .iter()
+        .map(|len| {
+            std::iter::repeat_with(|| E::Fr::random(&mut *rng))
+                .take(*len)
+                .collect()
+        })
         .collect();
-    let vecs: Vec<Vec<E::Fr>> = std::iter::repeat_with(|| {
-        std::iter::repeat_with(|| E::Fr::random(&mut *rng))
-            .take(len)
-            .collect()
-    })
-    .take(num_cmts)
-    .collect();
     let rands1: Vec<E::Fr> = std::iter::repeat_with(|| E::Fr::random(&mut *rng))
         .take(num_cmts)
         .collect();
     let rands2: Vec<E::Fr> = std::iter::repeat_with(|| E::Fr::random(&mut *rng))
         .take(num_cmts)
         .collect();
-    let cmts1 = vecs
-        .iter()
-        .zip(&rands1)
-        .map(|(vec, rand)| E::G1Affine::from(ck1.commit(&vec, *rand)))
+    let cmts1 = ck1
+        .commit_batch(&vecs, &rands1)
+        .unwrap()
+        .into_iter()
+        .map(E::G1Affine::from)
         .collect();
     let cmts2 = vecs
         .iter()
-        .zip(&rands1)
+        .zip(&rands2)
         .zip(&cks2)
-        .map(|((vec, rand), ck)| E::G1Affine::from(ck.commit(&vec, *rand)))
+        .map(|((vec, rand), ck)| E::G1Affine::from(ck.commit(vec, *rand).unwrap()))
         .collect();
     (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2)
 }
@@ -70,11 +79,11 @@ where
     E: MultiMillerLoop,
     E::Fr: PrimeFieldBits,
 {
-    let rng = &mut crate::kw15::test::test_rng();
+    let rng = &mut crate::testing::test_rng();
     let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) =
         random_statement::<E, _>(num_cmts, len, rng);
     let (pk, vk) = key_gen(&ck1, &cks2, rng);
-    let pf = prove(&pk, vecs, rands1, rands2);
+    let pf = prove(&pk, vecs, rands1, rands2).unwrap();
     assert!(verify(&vk, &cmts1, &cmts2, &pf));
 }
 
@@ -93,16 +102,188 @@ fn bls12_381_two_by_ten() {
     random_test::<Bls12>(2, 10);
 }
 
+#[test]
+fn bls12_381_different_lengths() {
+    let rng = &mut crate::testing::test_rng();
+    let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) =
+        random_statement_with_lens::<Bls12, _>(&[3, 7], rng);
+    let (pk, vk) = key_gen(&ck1, &cks2, rng);
+    let pf = prove(&pk, vecs, rands1, rands2).unwrap();
+    assert!(verify(&vk, &cmts1, &cmts2, &pf));
+}
+
+fn random_test_single<E>(len: usize)
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) =
+        random_statement::<E, _>(1, len, &mut crate::testing::test_rng());
+
+    let (pk, vk) = key_gen(&ck1, &cks2, &mut crate::testing::test_rng());
+    let (pk_s, vk_s) = key_gen_single(&ck1, &cks2[0], &mut crate::testing::test_rng());
+    assert!(pk == pk_s);
+    assert!(vk == vk_s);
+
+    let pf = prove(&pk, vecs.clone(), rands1.clone(), rands2.clone()).unwrap();
+    let pf_s = prove_single(&pk_s, vecs[0].clone(), rands1[0], rands2[0]).unwrap();
+    assert!(pf == pf_s);
+
+    assert!(verify(&vk, &cmts1, &cmts2, &pf_s));
+    assert!(verify_single(&vk_s, &cmts1[0], &cmts2[0], &pf));
+    assert!(verify_single_prepared(
+        &PreparedVerifyingKey::from(&vk_s),
+        &cmts1[0],
+        &cmts2[0],
+        &pf
+    ));
+    assert_eq!(
+        verify(&vk, &cmts2, &cmts1, &pf),
+        verify_single(&vk_s, &cmts2[0], &cmts1[0], &pf)
+    );
+}
+
+/// A key prepared once verifies several statements as [`verify`] does.
+#[test]
+fn bls12_381_prepared_reuse() {
+    let rng = &mut crate::testing::test_rng();
+    let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) = random_statement::<Bls12, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&ck1, &cks2, rng);
+    let pvk = PreparedVerifyingKey::from(&vk);
+    let pf = prove(&pk, vecs.clone(), rands1, rands2).unwrap();
+
+    // Other vectors, committed under the same keys.
+    let other_vecs: Vec<Vec<_>> = vecs
+        .iter()
+        .map(|v| v.iter().map(|x| x.double()).collect())
+        .collect();
+    let other_rands1 = vec![Field::random(&mut *rng), Field::random(&mut *rng)];
+    let other_rands2 = vec![Field::random(&mut *rng), Field::random(&mut *rng)];
+    let other_cmts1: Vec<_> = ck1
+        .commit_batch(&other_vecs, &other_rands1)
+        .unwrap()
+        .into_iter()
+        .map(<Bls12 as Engine>::G1Affine::from)
+        .collect();
+    let other_cmts2: Vec<_> = other_vecs
+        .iter()
+        .zip(&other_rands2)
+        .zip(&cks2)
+        .map(|((v, r), ck)| <Bls12 as Engine>::G1Affine::from(ck.commit(v, *r).unwrap()))
+        .collect();
+    let other_pf = prove(&pk, other_vecs, other_rands1, other_rands2).unwrap();
+
+    for (c1, c2, pf) in [
+        (&cmts1, &cmts2, &pf),
+        (&other_cmts1, &other_cmts2, &other_pf),
+        (&cmts1, &cmts2, &other_pf),
+        (&cmts1, &other_cmts2, &pf),
+    ] {
+        assert_eq!(verify_prepared(&pvk, c1, c2, pf), verify(&vk, c1, c2, pf));
+    }
+    assert!(verify_prepared(&pvk, &other_cmts1, &other_cmts2, &other_pf));
+    assert!(!verify_prepared(&pvk, &cmts1, &cmts2, &other_pf));
+}
+
+#[test]
+fn dummy_single() {
+    random_test_single::<DummyEngine>(10);
+}
+
+#[test]
+fn bls12_381_single() {
+    random_test_single::<Bls12>(10);
+}
+
+/// The second commitments must be to the same vectors as the first, each
+/// with its own blind from `rands2`.
+fn mismatched_test<E>()
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut crate::testing::test_rng();
+    let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) = random_statement::<E, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&ck1, &cks2, rng);
+    let pf = prove(&pk, vecs.clone(), rands1.clone(), rands2.clone()).unwrap();
+    assert!(verify(&vk, &cmts1, &cmts2, &pf));
+
+    // D_1 commits to a different vector than C_1.
+    let mut other = vecs[1].clone();
+    other[0] += E::Fr::one();
+    let mut bad_cmts2 = cmts2.clone();
+    bad_cmts2[1] = cks2[1].commit(&other, rands2[1]).unwrap().into();
+    assert!(!verify(&vk, &cmts1, &bad_cmts2, &pf));
+    // Nor can the prover link them, with either vector.
+    let mut other_vecs = vecs.clone();
+    other_vecs[1] = other;
+    let other_pf = prove(&pk, other_vecs, rands1.clone(), rands2.clone()).unwrap();
+    assert!(!verify(&vk, &cmts1, &bad_cmts2, &other_pf));
+
+    // D_0 reusing C_0's blind, rather than its own, doesn't match the proof.
+    let mut reused_blind = cmts2;
+    reused_blind[0] = cks2[0].commit(&vecs[0], rands1[0]).unwrap().into();
+    assert!(!verify(&vk, &cmts1, &reused_blind, &pf));
+}
+
+#[test]
+fn dummy_mismatched() {
+    mismatched_test::<DummyEngine>();
+}
+
+#[test]
+fn bls12_381_mismatched() {
+    mismatched_test::<Bls12>();
+}
+
+/// J_i's generators belong to D_i, not C_i: the key accepts C_i = Commit(K, X_i, r_i)
+/// and D_i = Commit(J_i, X_i, s_i), and not C_i + Commit(J_i, X_i, 0) with only the blind
+/// in D_i.
+fn j_in_second_commitment_test<E>()
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut crate::testing::test_rng();
+    let (vecs, rands1, rands2, ck1, cks2, cmts1, cmts2) = random_statement::<E, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&ck1, &cks2, rng);
+    let pf = prove(&pk, vecs.clone(), rands1, rands2.clone()).unwrap();
+    assert!(verify(&vk, &cmts1, &cmts2, &pf));
+
+    let merged_cmts1: Vec<E::G1Affine> = cmts1
+        .iter()
+        .zip(&vecs)
+        .zip(&cks2)
+        .map(|((c, vec), ck)| (ck.commit(vec, E::Fr::zero()).unwrap() + c).into())
+        .collect();
+    let blind_cmts2: Vec<E::G1Affine> = rands2
+        .iter()
+        .zip(&cks2)
+        .map(|(r, ck)| ck.commit(&[], *r).unwrap().into())
+        .collect();
+    assert!(!verify(&vk, &merged_cmts1, &blind_cmts2, &pf));
+}
+
+#[test]
+fn dummy_j_in_second_commitment() {
+    j_in_second_commitment_test::<DummyEngine>();
+}
+
+#[test]
+fn bls12_381_j_in_second_commitment() {
+    j_in_second_commitment_test::<Bls12>();
+}
+
 fn random_test_serde<E>(num_cmts: usize, len: usize)
 where
     E: MultiMillerLoop,
     E::Fr: PrimeFieldBits,
 {
-    let rng = &mut crate::kw15::test::test_rng();
+    let rng = &mut crate::testing::test_rng();
     let (vecs, rands1, rands2, ck1, cks2, _cmts1, _cmts2) =
         random_statement::<E, _>(num_cmts, len, rng);
     let (pk, vk) = key_gen(&ck1, &cks2, rng);
-    let pf = prove(&pk, vecs, rands1, rands2);
+    let pf = prove(&pk, vecs, rands1, rands2).unwrap();
     let mut ser_pk: Vec<u8> = Vec::new();
     let mut ser_vk: Vec<u8> = Vec::new();
     let mut ser_pf: Vec<u8> = Vec::new();
@@ -1,10 +1,46 @@
 //! IO utilities
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::PrimeField;
 use group::{prime::PrimeCurveAffine, GroupEncoding, UncompressedEncoding};
 use std::io::{self, Read, Write};
+use subtle::CtOption;
+
+#[cfg(feature = "async-io")]
+pub mod async_io;
+
+/// Magic bytes at the start of every serialized object.
+pub const MAGIC: [u8; 4] = *b"BLMN";
+/// The current serialization format version.
+pub const VERSION: u8 = 1;
+
+/// A default bound on the length of group vectors read from untrusted input,
+/// used by [`GroupReader::read_groups`] and the other readers without a
+/// `max_len`.
+pub const DEFAULT_MAX_GROUPS: usize = 1 << 28;
+
+/// The most elements reserved up front for a vector read from untrusted
+/// input. Longer vectors grow as their elements are read, so that a length
+/// prefix without the data behind it can't trigger a huge allocation.
+const MAX_INITIAL_CAPACITY: usize = 1 << 12;
+
+/// Whether group elements are written compressed or uncompressed.
+///
+/// Compressed points are about half the size, but reading them requires a
+/// square root per point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Compressed,
+    Uncompressed,
+}
 
 pub trait GroupWriter: Write {
+    /// Write the magic bytes, the format version, and the `kind` of object that follows.
+    fn write_header(&mut self, kind: u8) -> io::Result<()> {
+        self.write_all(&MAGIC)?;
+        self.write_u8(VERSION)?;
+        self.write_u8(kind)
+    }
     fn write_group_uncompressed<Enc: UncompressedEncoding>(&mut self, e: &Enc) -> io::Result<()> {
         self.write_all(e.to_uncompressed().as_ref())
     }
@@ -28,9 +64,57 @@ pub trait GroupWriter: Write {
         }
         Ok(())
     }
+    /// [`Self::write_group`] or [`Self::write_group_uncompressed`], as chosen by `compression`.
+    fn write_group_with<Enc: GroupEncoding + UncompressedEncoding>(
+        &mut self,
+        e: &Enc,
+        compression: Compression,
+    ) -> io::Result<()> {
+        match compression {
+            Compression::Compressed => self.write_group(e),
+            Compression::Uncompressed => self.write_group_uncompressed(e),
+        }
+    }
+    /// [`Self::write_groups`] or [`Self::write_groups_uncompressed`], as chosen by
+    /// `compression`.
+    fn write_groups_with<Enc: GroupEncoding + UncompressedEncoding>(
+        &mut self,
+        es: &[Enc],
+        compression: Compression,
+    ) -> io::Result<()> {
+        match compression {
+            Compression::Compressed => self.write_groups(es),
+            Compression::Uncompressed => self.write_groups_uncompressed(es),
+        }
+    }
+    /// Write a scalar in its canonical [`PrimeField::Repr`] encoding.
+    fn write_scalar<F: PrimeField>(&mut self, s: &F) -> io::Result<()> {
+        self.write_all(s.to_repr().as_ref())
+    }
 }
 
 pub trait GroupReader: Read {
+    /// Read a header written by [`GroupWriter::write_header`], checking that it
+    /// has the right magic bytes and version and is for an object of `expected_kind`.
+    fn read_header(&mut self, expected_kind: u8) -> io::Result<()> {
+        let mut magic = [0u8; 4];
+        self.read_exact(&mut magic)?;
+        let version = self.read_u8()?;
+        let kind = self.read_u8()?;
+        check_header(magic, version, kind, expected_kind)
+    }
+    /// Check that the reader is exhausted, failing if any bytes remain.
+    fn read_end(&mut self) -> io::Result<()> {
+        let mut byte = [0u8; 1];
+        loop {
+            match self.read(&mut byte) {
+                Ok(0) => return Ok(()),
+                Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "trailing data")),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
     fn read_group_uncompressed<Enc: UncompressedEncoding + PrimeCurveAffine>(
         &mut self,
         checked: bool,
@@ -45,22 +129,7 @@ pub trait GroupReader: Read {
             Enc::from_uncompressed_unchecked(&repr)
         };
 
-        let affine = if affine.is_some().into() {
-            Ok(affine.unwrap())
-        } else {
-            Err(io::Error::new(io::ErrorKind::InvalidData, "invalid group"))
-        }?;
-
-        if allow_zero {
-            Ok(affine)
-        } else if affine.is_identity().into() {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "point at infinity",
-            ))
-        } else {
-            Ok(affine)
-        }
+        check_group(affine, allow_zero)
     }
     fn read_group<Enc: GroupEncoding + PrimeCurveAffine>(
         &mut self,
@@ -76,48 +145,309 @@ pub trait GroupReader: Read {
             Enc::from_bytes_unchecked(&repr)
         };
 
-        let affine = if affine.is_some().into() {
-            Ok(affine.unwrap())
-        } else {
-            Err(io::Error::new(io::ErrorKind::InvalidData, "invalid group"))
-        }?;
-
-        if allow_zero {
-            Ok(affine)
-        } else if affine.is_identity().into() {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "point at infinity",
-            ))
-        } else {
-            Ok(affine)
-        }
+        check_group(affine, allow_zero)
     }
+    /// Read a vector length, rejecting lengths above `max_len` so that a
+    /// malicious prefix can't trigger a huge allocation.
+    fn read_groups_len(&mut self, max_len: usize) -> io::Result<usize> {
+        let len = self.read_u64::<BigEndian>()?;
+        check_groups_len(len, max_len)
+    }
+    /// [`Self::read_groups_uncompressed_bounded`] with a bound of
+    /// [`DEFAULT_MAX_GROUPS`].
     fn read_groups_uncompressed<Enc: UncompressedEncoding + PrimeCurveAffine>(
         &mut self,
         checked: bool,
         allow_zero: bool,
     ) -> io::Result<Vec<Enc>> {
-        let len = self.read_u64::<BigEndian>()? as usize;
-        let mut groups = Vec::new();
+        self.read_groups_uncompressed_bounded(checked, allow_zero, DEFAULT_MAX_GROUPS)
+    }
+    /// Read a vector written by [`GroupWriter::write_groups_uncompressed`],
+    /// rejecting lengths above `max_len`.
+    fn read_groups_uncompressed_bounded<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+        max_len: usize,
+    ) -> io::Result<Vec<Enc>> {
+        let len = self.read_groups_len(max_len)?;
+        let mut groups = Vec::with_capacity(initial_capacity(len));
         for _ in 0..len {
             groups.push(self.read_group_uncompressed(checked, allow_zero)?);
         }
         Ok(groups)
     }
+    /// [`Self::read_groups_bounded`] with a bound of [`DEFAULT_MAX_GROUPS`].
     fn read_groups<Enc: PrimeCurveAffine>(
         &mut self,
         checked: bool,
         allow_zero: bool,
     ) -> io::Result<Vec<Enc>> {
-        let len = self.read_u64::<BigEndian>()? as usize;
-        let mut groups = Vec::new();
+        self.read_groups_bounded(checked, allow_zero, DEFAULT_MAX_GROUPS)
+    }
+    /// Read a vector written by [`GroupWriter::write_groups`], rejecting
+    /// lengths above `max_len`.
+    fn read_groups_bounded<Enc: PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+        max_len: usize,
+    ) -> io::Result<Vec<Enc>> {
+        let len = self.read_groups_len(max_len)?;
+        let mut groups = Vec::with_capacity(initial_capacity(len));
         for _ in 0..len {
             groups.push(self.read_group(checked, allow_zero)?);
         }
         Ok(groups)
     }
+    /// [`Self::read_group`] or [`Self::read_group_uncompressed`], as chosen by `compression`.
+    fn read_group_with<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        compression: Compression,
+        checked: bool,
+        allow_zero: bool,
+    ) -> io::Result<Enc> {
+        match compression {
+            Compression::Compressed => self.read_group(checked, allow_zero),
+            Compression::Uncompressed => self.read_group_uncompressed(checked, allow_zero),
+        }
+    }
+    /// [`Self::read_groups`] or [`Self::read_groups_uncompressed`], as chosen by
+    /// `compression`.
+    fn read_groups_with<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        compression: Compression,
+        checked: bool,
+        allow_zero: bool,
+    ) -> io::Result<Vec<Enc>> {
+        self.read_groups_with_bounded(compression, checked, allow_zero, DEFAULT_MAX_GROUPS)
+    }
+    /// [`Self::read_groups_bounded`] or
+    /// [`Self::read_groups_uncompressed_bounded`], as chosen by `compression`.
+    fn read_groups_with_bounded<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        compression: Compression,
+        checked: bool,
+        allow_zero: bool,
+        max_len: usize,
+    ) -> io::Result<Vec<Enc>> {
+        match compression {
+            Compression::Compressed => self.read_groups_bounded(checked, allow_zero, max_len),
+            Compression::Uncompressed => {
+                self.read_groups_uncompressed_bounded(checked, allow_zero, max_len)
+            }
+        }
+    }
+    /// Read a scalar written by [`GroupWriter::write_scalar`], rejecting
+    /// non-canonical encodings.
+    ///
+    /// The encoding is checked in constant time; only whether it was valid is
+    /// revealed.
+    fn read_scalar<F: PrimeField>(&mut self) -> io::Result<F> {
+        let mut repr = F::Repr::default();
+        self.read_exact(repr.as_mut())?;
+        check_scalar(F::from_repr(repr))
+    }
+}
+
+/// Read an object with `read` and then check that `reader` has no data left,
+/// for strict parsing of framed messages.
+pub fn read_framed<R, T, F>(mut reader: R, read: F) -> io::Result<T>
+where
+    R: Read,
+    F: FnOnce(&mut R) -> io::Result<T>,
+{
+    let t = read(&mut reader)?;
+    reader.read_end()?;
+    Ok(t)
+}
+
+fn check_header(magic: [u8; 4], version: u8, kind: u8, expected_kind: u8) -> io::Result<()> {
+    if magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "bad magic bytes",
+        ));
+    }
+    if version != VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported format version",
+        ));
+    }
+    if kind != expected_kind {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected object kind",
+        ));
+    }
+    Ok(())
+}
+
+fn check_group<Enc: PrimeCurveAffine>(affine: CtOption<Enc>, allow_zero: bool) -> io::Result<Enc> {
+    let affine = if affine.is_some().into() {
+        Ok(affine.unwrap())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "invalid group"))
+    }?;
+
+    if allow_zero {
+        Ok(affine)
+    } else if affine.is_identity().into() {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "point at infinity",
+        ))
+    } else {
+        Ok(affine)
+    }
+}
+
+fn check_scalar<F: PrimeField>(s: CtOption<F>) -> io::Result<F> {
+    if s.is_some().into() {
+        Ok(s.unwrap())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "invalid scalar"))
+    }
+}
+
+/// The capacity to reserve for a vector of `len` elements read from untrusted
+/// input: `len`, up to a small bound.
+pub(crate) fn initial_capacity(len: usize) -> usize {
+    len.min(MAX_INITIAL_CAPACITY)
+}
+
+fn check_groups_len(len: u64, max_len: usize) -> io::Result<usize> {
+    if len > max_len as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "group vector too long",
+        ));
+    }
+    Ok(len as usize)
 }
 
 impl<R: Read> GroupReader for R {}
 impl<W: Write> GroupWriter for W {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::test_rng;
+    use bls12_381::{G1Affine, G2Affine, Scalar};
+    use ff::Field;
+
+    #[test]
+    fn oversized_length() {
+        let mut bytes = vec![];
+        bytes.write_u64::<BigEndian>(u64::MAX).unwrap();
+
+        let err = (&bytes[..])
+            .read_groups_uncompressed_bounded::<G1Affine>(true, true, 1024)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = (&bytes[..])
+            .read_groups::<G1Affine>(true, true)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn length_without_data() {
+        let mut bytes = vec![];
+        bytes
+            .write_u64::<BigEndian>(DEFAULT_MAX_GROUPS as u64)
+            .unwrap();
+        bytes
+            .write_group_uncompressed(&G2Affine::generator())
+            .unwrap();
+
+        // Only the points that are there are allocated for.
+        let err = (&bytes[..])
+            .read_groups_uncompressed::<G2Affine>(true, true)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(initial_capacity(DEFAULT_MAX_GROUPS), MAX_INITIAL_CAPACITY);
+        assert_eq!(initial_capacity(3), 3);
+    }
+
+    #[test]
+    fn max_length() {
+        let groups = vec![G1Affine::generator(); 3];
+        let mut bytes = vec![];
+        bytes.write_groups_uncompressed(&groups).unwrap();
+
+        let read = (&bytes[..])
+            .read_groups_uncompressed_bounded::<G1Affine>(true, false, 3)
+            .unwrap();
+        assert_eq!(read, groups);
+        assert!((&bytes[..])
+            .read_groups_uncompressed_bounded::<G1Affine>(true, false, 2)
+            .is_err());
+    }
+
+    #[test]
+    fn with_compression() {
+        let g1s = vec![G1Affine::generator(), G1Affine::identity()];
+        let g2 = G2Affine::generator();
+
+        let mut sizes = vec![];
+        for compression in [Compression::Compressed, Compression::Uncompressed] {
+            let mut bytes = vec![];
+            bytes.write_groups_with(&g1s, compression).unwrap();
+            bytes.write_group_with(&g2, compression).unwrap();
+            sizes.push(bytes.len());
+
+            let mut reader = &bytes[..];
+            let de_g1s: Vec<G1Affine> = reader.read_groups_with(compression, true, true).unwrap();
+            let de_g2: G2Affine = reader.read_group_with(compression, true, false).unwrap();
+            reader.read_end().unwrap();
+            assert_eq!(de_g1s, g1s);
+            assert_eq!(de_g2, g2);
+        }
+        assert!(sizes[0] < sizes[1]);
+    }
+
+    #[test]
+    fn scalars() {
+        let mut rng = test_rng();
+        let scalars: Vec<Scalar> = (0..10).map(|_| Scalar::random(&mut rng)).collect();
+
+        let mut bytes = vec![];
+        for s in &scalars {
+            bytes.write_scalar(s).unwrap();
+        }
+        assert_eq!(bytes.len(), 32 * scalars.len());
+
+        let mut reader = &bytes[..];
+        for s in &scalars {
+            assert_eq!(reader.read_scalar::<Scalar>().unwrap(), *s);
+        }
+        reader.read_end().unwrap();
+
+        // Not reduced modulo r.
+        let err = (&[0xff; 32][..]).read_scalar::<Scalar>().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!((&bytes[..31]).read_scalar::<Scalar>().is_err());
+    }
+
+    #[test]
+    fn framed() {
+        let mut bytes = vec![];
+        bytes.write_group(&G1Affine::generator()).unwrap();
+
+        let read = |r: &mut &[u8]| r.read_group::<G1Affine>(true, false);
+        assert_eq!(
+            read_framed(&bytes[..], read).unwrap(),
+            G1Affine::generator()
+        );
+
+        bytes.push(0);
+        assert_eq!(read(&mut &bytes[..]).unwrap(), G1Affine::generator());
+        let err = read_framed(&bytes[..], read).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
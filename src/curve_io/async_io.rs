@@ -0,0 +1,236 @@
+//! Async counterparts of [`GroupReader`] and [`GroupWriter`], over tokio's IO traits.
+//!
+//! The encodings are identical to the blocking versions, so data written with one can be
+//! read with the other.
+//!
+//! [`GroupReader`]: super::GroupReader
+//! [`GroupWriter`]: super::GroupWriter
+
+use group::{prime::PrimeCurveAffine, GroupEncoding, UncompressedEncoding};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{
+    check_group, check_groups_len, check_header, initial_capacity, DEFAULT_MAX_GROUPS, MAGIC,
+    VERSION,
+};
+
+type IoFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+pub trait AsyncGroupWriter: AsyncWrite + Unpin + Send {
+    /// Write the magic bytes, the format version, and the `kind` of object that follows.
+    fn write_header(&mut self, kind: u8) -> IoFuture<'_, ()> {
+        Box::pin(async move {
+            self.write_all(&MAGIC).await?;
+            self.write_u8(VERSION).await?;
+            self.write_u8(kind).await
+        })
+    }
+    fn write_group_uncompressed<Enc: UncompressedEncoding>(&mut self, e: &Enc) -> IoFuture<'_, ()> {
+        let bytes = e.to_uncompressed().as_ref().to_vec();
+        Box::pin(async move { self.write_all(&bytes).await })
+    }
+    fn write_group<Enc: GroupEncoding>(&mut self, e: &Enc) -> IoFuture<'_, ()> {
+        let repr = e.to_bytes();
+        Box::pin(async move { self.write_all(repr.as_ref()).await })
+    }
+    fn write_groups<'a, Enc: GroupEncoding + Sync>(
+        &'a mut self,
+        es: &'a [Enc],
+    ) -> IoFuture<'a, ()> {
+        Box::pin(async move {
+            self.write_u64(es.len() as u64).await?;
+            for e in es {
+                self.write_group(e).await?;
+            }
+            Ok(())
+        })
+    }
+    fn write_groups_uncompressed<'a, Enc: UncompressedEncoding + Sync>(
+        &'a mut self,
+        es: &'a [Enc],
+    ) -> IoFuture<'a, ()> {
+        Box::pin(async move {
+            self.write_u64(es.len() as u64).await?;
+            for e in es {
+                self.write_group_uncompressed(e).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub trait AsyncGroupReader: AsyncRead + Unpin + Send {
+    /// Read a header written by [`AsyncGroupWriter::write_header`], checking that it
+    /// has the right magic bytes and version and is for an object of `expected_kind`.
+    fn read_header(&mut self, expected_kind: u8) -> IoFuture<'_, ()> {
+        Box::pin(async move {
+            let mut magic = [0u8; 4];
+            self.read_exact(&mut magic).await?;
+            let version = self.read_u8().await?;
+            let kind = self.read_u8().await?;
+            check_header(magic, version, kind, expected_kind)
+        })
+    }
+    fn read_group_uncompressed<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+    ) -> IoFuture<'_, Enc> {
+        Box::pin(async move {
+            let len = <Enc as UncompressedEncoding>::Uncompressed::default()
+                .as_ref()
+                .len();
+            let mut bytes = vec![0u8; len];
+            self.read_exact(&mut bytes).await?;
+
+            let mut repr = <Enc as UncompressedEncoding>::Uncompressed::default();
+            repr.as_mut().copy_from_slice(&bytes);
+            let affine = if checked {
+                Enc::from_uncompressed(&repr)
+            } else {
+                Enc::from_uncompressed_unchecked(&repr)
+            };
+
+            check_group(affine, allow_zero)
+        })
+    }
+    fn read_group<Enc: GroupEncoding + PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+    ) -> IoFuture<'_, Enc> {
+        Box::pin(async move {
+            let mut repr = <Enc as GroupEncoding>::Repr::default();
+            self.read_exact(repr.as_mut()).await?;
+
+            let affine = if checked {
+                Enc::from_bytes(&repr)
+            } else {
+                Enc::from_bytes_unchecked(&repr)
+            };
+
+            check_group(affine, allow_zero)
+        })
+    }
+    /// Read a vector length, rejecting lengths above `max_len` so that a
+    /// malicious prefix can't trigger a huge allocation.
+    fn read_groups_len(&mut self, max_len: usize) -> IoFuture<'_, usize> {
+        Box::pin(async move {
+            let len = self.read_u64().await?;
+            check_groups_len(len, max_len)
+        })
+    }
+    /// [`Self::read_groups_uncompressed_bounded`] with a bound of
+    /// [`DEFAULT_MAX_GROUPS`].
+    fn read_groups_uncompressed<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+    ) -> IoFuture<'_, Vec<Enc>> {
+        self.read_groups_uncompressed_bounded(checked, allow_zero, DEFAULT_MAX_GROUPS)
+    }
+    /// Read a vector written by
+    /// [`AsyncGroupWriter::write_groups_uncompressed`], rejecting lengths
+    /// above `max_len`.
+    fn read_groups_uncompressed_bounded<Enc: UncompressedEncoding + PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+        max_len: usize,
+    ) -> IoFuture<'_, Vec<Enc>> {
+        Box::pin(async move {
+            let len = self.read_groups_len(max_len).await?;
+            let mut groups = Vec::with_capacity(initial_capacity(len));
+            for _ in 0..len {
+                groups.push(self.read_group_uncompressed(checked, allow_zero).await?);
+            }
+            Ok(groups)
+        })
+    }
+    /// [`Self::read_groups_bounded`] with a bound of [`DEFAULT_MAX_GROUPS`].
+    fn read_groups<Enc: PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+    ) -> IoFuture<'_, Vec<Enc>> {
+        self.read_groups_bounded(checked, allow_zero, DEFAULT_MAX_GROUPS)
+    }
+    /// Read a vector written by [`AsyncGroupWriter::write_groups`], rejecting
+    /// lengths above `max_len`.
+    fn read_groups_bounded<Enc: PrimeCurveAffine>(
+        &mut self,
+        checked: bool,
+        allow_zero: bool,
+        max_len: usize,
+    ) -> IoFuture<'_, Vec<Enc>> {
+        Box::pin(async move {
+            let len = self.read_groups_len(max_len).await?;
+            let mut groups = Vec::with_capacity(initial_capacity(len));
+            for _ in 0..len {
+                groups.push(self.read_group(checked, allow_zero).await?);
+            }
+            Ok(groups)
+        })
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> AsyncGroupReader for R {}
+impl<W: AsyncWrite + Unpin + Send> AsyncGroupWriter for W {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::curve_io::GroupReader;
+    use bls12_381::{G1Affine, G2Affine};
+    use group::Curve;
+
+    #[test]
+    fn duplex_round_trip() {
+        let g1s: Vec<G1Affine> = (1..4u64)
+            .map(|i| (G1Affine::generator() * bls12_381::Scalar::from(i)).to_affine())
+            .collect();
+        let g2 = bls12_381::G2Projective::generator().to_affine();
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (mut client, mut server) = tokio::io::duplex(1 << 16);
+
+            client.write_header(7).await.unwrap();
+            client.write_groups(&g1s).await.unwrap();
+            client.write_groups_uncompressed(&g1s).await.unwrap();
+            client.write_group(&g2).await.unwrap();
+            client.write_group_uncompressed(&g2).await.unwrap();
+
+            server.read_header(7).await.unwrap();
+            let de_g1s: Vec<G1Affine> = server.read_groups(true, false).await.unwrap();
+            assert_eq!(de_g1s, g1s);
+            let de_g1s: Vec<G1Affine> = server.read_groups_uncompressed(true, false).await.unwrap();
+            assert_eq!(de_g1s, g1s);
+            let de_g2: G2Affine = server.read_group(true, false).await.unwrap();
+            assert_eq!(de_g2, g2);
+            let de_g2: G2Affine = server.read_group_uncompressed(true, false).await.unwrap();
+            assert_eq!(de_g2, g2);
+
+            client.write_header(7).await.unwrap();
+            let err = server.read_header(8).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        });
+
+        // The blocking readers can read what the async writers wrote.
+        let mut written: Vec<u8> = vec![];
+        rt.block_on(async {
+            written.write_header(7).await.unwrap();
+            written.write_groups_uncompressed(&g1s).await.unwrap();
+        });
+        let mut reader = &written[..];
+        GroupReader::read_header(&mut reader, 7).unwrap();
+        let de_g1s: Vec<G1Affine> =
+            GroupReader::read_groups_uncompressed(&mut reader, true, false).unwrap();
+        assert_eq!(de_g1s, g1s);
+    }
+}
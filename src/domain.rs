@@ -8,19 +8,40 @@
 //! field. This allows us to perform polynomial operations in O(n) by performing
 //! an O(n log n) FFT over such a domain.
 //!
+//! Some fields have too small a 2-adic subgroup for large circuits. With the
+//! `mixed-radix` feature, [`EvaluationDomain::from_coeffs`] falls back to a
+//! domain of size `q * 2^k` for a small odd `q` dividing `p - 1`, and performs
+//! its FFTs as `q` radix-2 FFTs followed by a size-`q` combination step.
+//!
+//! For domains too large to transform in memory,
+//! [`EvaluationDomain::fft_out_of_core`] stages the FFT through a scratch file.
+//!
+//! Domains of [`Scalar`]s and of [`Point`]s can be saved with
+//! [`EvaluationDomain::write`] and loaded with [`EvaluationDomain::read`].
+//!
 //! [`EvaluationDomain`]: crate::domain::EvaluationDomain
 //! [Groth16]: https://eprint.iacr.org/2016/260
 
-use ff::PrimeField;
+use ff::{Field, PrimeField};
 use group::cofactor::CofactorCurve;
 
 use super::SynthesisError;
 
 use super::multicore::Worker;
 
+pub use self::encoding::DomainElementIO;
+
+mod encoding;
+mod out_of_core;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
 pub struct EvaluationDomain<S: PrimeField, G: Group<S>> {
     coeffs: Vec<G>,
+    /// The domain has `radix * 2^exp` elements.
     exp: u32,
+    /// The odd factor of the domain size; 1 for radix-2 domains.
+    radix: usize,
     omega: S,
     omegainv: S,
     geninv: S,
@@ -39,25 +60,215 @@ impl<S: PrimeField, G: Group<S>> AsMut<[G]> for EvaluationDomain<S, G> {
     }
 }
 
+/// The `exp` of the smallest evaluation domain (of size `2^exp`) that fits
+/// `num_constraints` points, i.e. `ceil(log2(num_constraints))`.
+///
+/// For a circuit, `num_constraints` should count the constraints plus the
+/// input constraints that the provers add. The circuit fits in the field iff
+/// the result is at most [`EvaluationDomain::max_exp()`], or, with the
+/// `mixed-radix` feature, if [`EvaluationDomain::from_coeffs`] can find a
+/// mixed-radix domain for it.
+pub fn required_domain_exp(num_constraints: usize) -> u32 {
+    // Doubling a size up to `num_constraints` could overflow, so count the
+    // bits of `num_constraints - 1` instead.
+    match num_constraints {
+        0 | 1 => 0,
+        n => usize::BITS - (n - 1).leading_zeros(),
+    }
+}
+
+/// The size `radix * 2^exp` of a domain, or `None` if it doesn't fit in a
+/// `usize`.
+fn domain_size(radix: usize, exp: u32) -> Option<usize> {
+    1usize.checked_shl(exp).and_then(|m| m.checked_mul(radix))
+}
+
+/// Invert every element of `elems` in place, with Montgomery's trick: one
+/// field inversion plus `3(n - 1)` multiplications.
+///
+/// Panics if any element is zero; see [`try_batch_invert`].
+pub fn batch_invert<S: Field>(elems: &mut [S]) {
+    if let Err(i) = try_batch_invert(elems) {
+        panic!("element {} is zero", i);
+    }
+}
+
+/// Like [`batch_invert`], but returns the index of the first zero element
+/// instead of panicking. `elems` is left unchanged in that case.
+pub fn try_batch_invert<S: Field>(elems: &mut [S]) -> Result<(), usize> {
+    if let Some(i) = elems.iter().position(|e| bool::from(e.is_zero())) {
+        return Err(i);
+    }
+    if elems.is_empty() {
+        return Ok(());
+    }
+
+    // prefix[i] is the product of elems[..i].
+    let mut prefix = Vec::with_capacity(elems.len());
+    let mut acc = elems[0];
+    prefix.push(S::one());
+    for e in &elems[1..] {
+        prefix.push(acc);
+        acc *= e;
+    }
+
+    // The inverse of the product of elems[..=i], walking i down.
+    let mut inv = acc.invert().unwrap();
+    for (e, prefix) in elems.iter_mut().zip(prefix).skip(1).rev() {
+        let next = inv * *e;
+        *e = inv * prefix;
+        inv = next;
+    }
+    elems[0] = inv;
+    Ok(())
+}
+
 impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
+    /// The largest `exp` for which a domain of size `2^exp` can be constructed
+    /// over `S`.
+    pub fn max_exp() -> u32 {
+        S::S - 1
+    }
+
+    /// The generator of the domain: a primitive root of unity whose order is
+    /// the domain size.
+    ///
+    /// ```
+    /// use bellman::domain::{EvaluationDomain, Scalar};
+    /// use bls12_381::Scalar as Fr;
+    /// use ff::Field;
+    ///
+    /// let domain = EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_padded_to(vec![], 3).unwrap();
+    /// let m = domain.as_ref().len() as u64;
+    /// assert_eq!(m, 1u64 << domain.exp());
+    /// // Fr has an inherent `pow_vartime` taking 256-bit exponents.
+    /// assert_eq!(Field::pow_vartime(&domain.omega(), [m]), Fr::one());
+    /// assert_ne!(Field::pow_vartime(&domain.omega(), [m / 2]), Fr::one());
+    /// ```
+    pub fn omega(&self) -> S {
+        self.omega
+    }
+
+    /// The inverse of [`EvaluationDomain::omega`], used by
+    /// [`EvaluationDomain::ifft`].
+    ///
+    /// ```
+    /// use bellman::domain::{EvaluationDomain, Scalar};
+    /// use bls12_381::Scalar as Fr;
+    /// use ff::Field;
+    ///
+    /// let domain = EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_padded_to(vec![], 3).unwrap();
+    /// assert_eq!(domain.omega() * domain.omega_inv(), Fr::one());
+    /// ```
+    pub fn omega_inv(&self) -> S {
+        self.omegainv
+    }
+
+    /// The log2 of the domain size. With the `mixed-radix` feature, this is
+    /// only the 2-adic part of the size of a mixed-radix domain.
+    pub fn exp(&self) -> u32 {
+        self.exp
+    }
+
     pub fn into_coeffs(self) -> Vec<G> {
         self.coeffs
     }
 
-    pub fn from_coeffs(mut coeffs: Vec<G>) -> Result<EvaluationDomain<S, G>, SynthesisError> {
+    /// Zero-pad `coeffs` to the smallest domain that fits them.
+    ///
+    /// Fails with [`SynthesisError::EmptyDomain`] if `coeffs` is empty: the
+    /// prover always has at least one constraint per input, so no
+    /// coefficients means a caller bug rather than a size-1 domain. Use
+    /// [`EvaluationDomain::from_coeffs_padded_to`] for a domain of zeros.
+    ///
+    /// With the `mixed-radix` feature, coefficients that don't fit in a
+    /// radix-2 domain are padded to a mixed-radix one instead (see the
+    /// [module docs](self)).
+    pub fn from_coeffs(coeffs: Vec<G>) -> Result<EvaluationDomain<S, G>, SynthesisError> {
+        if coeffs.is_empty() {
+            return Err(SynthesisError::EmptyDomain);
+        }
         // Compute the size of our evaluation domain
-        let mut m = 1;
-        let mut exp = 0;
-        while m < coeffs.len() {
-            m *= 2;
-            exp += 1;
-
-            // The pairing-friendly curve may not be able to support
-            // large enough (radix2) evaluation domains.
-            if exp >= S::S {
-                return Err(SynthesisError::PolynomialDegreeTooLarge);
-            }
+        let exp = required_domain_exp(coeffs.len());
+        #[cfg(feature = "mixed-radix")]
+        if exp > Self::max_exp() {
+            return Self::from_coeffs_mixed_radix(coeffs);
+        }
+        Self::from_coeffs_padded_to(coeffs, exp)
+    }
+
+    /// Like [`EvaluationDomain::from_coeffs`], but zero-pads to the smallest
+    /// domain that fits at least `min_size` points, even if `coeffs` is
+    /// shorter. Domains built with the same `min_size` from no more than
+    /// `min_size` coefficients have the same size, so they can be combined
+    /// with e.g. [`EvaluationDomain::mul_assign`].
+    pub fn from_coeffs_sized(
+        mut coeffs: Vec<G>,
+        min_size: usize,
+    ) -> Result<EvaluationDomain<S, G>, SynthesisError> {
+        if coeffs.len() < min_size {
+            coeffs.resize(min_size, G::group_zero());
+        }
+        Self::from_coeffs(coeffs)
+    }
+
+    /// Zero-pad `coeffs` to the smallest domain of size `q * 2^exp` that fits
+    /// them, for an odd `q` of at most [`MAX_RADIX`] that divides `p - 1`.
+    #[cfg(feature = "mixed-radix")]
+    fn from_coeffs_mixed_radix(
+        mut coeffs: Vec<G>,
+    ) -> Result<EvaluationDomain<S, G>, SynthesisError> {
+        let p_minus_1 = to_limbs(-S::one());
+        let (exp, radix, m) = (3..=MAX_RADIX)
+            .step_by(2)
+            .filter(|&radix| div_rem(&p_minus_1, radix as u64).1 == 0)
+            .map(|radix| {
+                let exp = required_domain_exp((coeffs.len() - 1) / radix + 1);
+                (exp, radix)
+            })
+            .filter(|&(exp, _)| exp <= Self::max_exp())
+            .filter_map(|(exp, radix)| domain_size(radix, exp).map(|m| (exp, radix, m)))
+            .min_by_key(|&(_, _, m)| m)
+            .ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
+
+        // Compute omega, a primitive m-th root of unity. The multiplicative
+        // generator has order p - 1, so g^((p - 1) / m) has order m.
+        let (cofactor, rem) = div_rem(&p_minus_1, m as u64);
+        debug_assert_eq!(rem, 0);
+        let omega = S::multiplicative_generator().pow_vartime(&cofactor);
+
+        // Extend the coeffs vector with zeroes if necessary
+        coeffs.resize(m, G::group_zero());
+
+        Ok(EvaluationDomain {
+            coeffs,
+            exp,
+            radix,
+            omega,
+            omegainv: omega.invert().unwrap(),
+            geninv: S::multiplicative_generator().invert().unwrap(),
+            minv: S::from(m as u64).invert().unwrap(),
+        })
+    }
+
+    /// Like [`EvaluationDomain::from_coeffs`], but zero-pads to a domain of
+    /// size `2^target_exp` rather than the smallest one that fits.
+    ///
+    /// Fails if `coeffs` doesn't fit in that domain, or if `target_exp` exceeds
+    /// [`EvaluationDomain::max_exp()`].
+    pub fn from_coeffs_padded_to(
+        mut coeffs: Vec<G>,
+        target_exp: u32,
+    ) -> Result<EvaluationDomain<S, G>, SynthesisError> {
+        let exp = target_exp;
+        // The pairing-friendly curve may not be able to support
+        // large enough (radix2) evaluation domains.
+        if exp > Self::max_exp() || required_domain_exp(coeffs.len()) > exp {
+            return Err(SynthesisError::PolynomialDegreeTooLarge);
         }
+        // Fields with a large 2-adicity have domains too large to address on
+        // 32-bit targets.
+        let m = domain_size(1, exp).ok_or(SynthesisError::PolynomialDegreeTooLarge)?;
 
         // Compute omega, the 2^exp primitive root of unity
         let mut omega = S::root_of_unity();
@@ -71,6 +282,7 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
         Ok(EvaluationDomain {
             coeffs,
             exp,
+            radix: 1,
             omega,
             omegainv: omega.invert().unwrap(),
             geninv: S::multiplicative_generator().invert().unwrap(),
@@ -79,11 +291,17 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
     }
 
     pub fn fft(&mut self, worker: &Worker) {
-        best_fft(&mut self.coeffs, worker, &self.omega, self.exp);
+        mixed_radix_fft(&mut self.coeffs, worker, &self.omega, self.exp, self.radix);
     }
 
     pub fn ifft(&mut self, worker: &Worker) {
-        best_fft(&mut self.coeffs, worker, &self.omegainv, self.exp);
+        mixed_radix_fft(
+            &mut self.coeffs,
+            worker,
+            &self.omegainv,
+            self.exp,
+            self.radix,
+        );
 
         worker.scope(self.coeffs.len(), |scope, chunk| {
             let minv = self.minv;
@@ -125,7 +343,7 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
     }
 
     /// This evaluates t(tau) for this domain, which is
-    /// tau^m - 1 for these radix-2 domains.
+    /// tau^m - 1 for these multiplicative subgroups.
     pub fn z(&self, tau: &S) -> S {
         let mut tmp = tau.pow_vartime(&[self.coeffs.len() as u64]);
         tmp.sub_assign(&S::one());
@@ -133,6 +351,28 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
         tmp
     }
 
+    /// The evaluations of t(x) on the coset, in the order produced by
+    /// [`EvaluationDomain::coset_fft`], e.g. as a divisor for
+    /// [`EvaluationDomain::divide_by_on_coset`].
+    ///
+    /// Since omega^m = 1, t(g omega^i) = g^m - 1 for every i: the
+    /// evaluations are all equal to `self.z(&g)`.
+    pub fn z_on_coset_evals(&self, worker: &Worker) -> Vec<S> {
+        let z = self.z(&S::multiplicative_generator());
+        let mut evals = vec![S::zero(); self.coeffs.len()];
+
+        worker.scope(evals.len(), |scope, chunk| {
+            for v in evals.chunks_mut(chunk) {
+                scope.spawn(move |_scope| {
+                    for v in v {
+                        *v = z;
+                    }
+                });
+            }
+        });
+        evals
+    }
+
     /// The target polynomial is the zero polynomial in our
     /// evaluation domain, so we must perform division over
     /// a coset.
@@ -150,6 +390,34 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
         });
     }
 
+    /// Divide element-wise by `divisor`, where both hold evaluations on the
+    /// coset (e.g. after [`EvaluationDomain::coset_fft`]). This generalizes
+    /// [`EvaluationDomain::divide_by_z_on_coset`] to any target polynomial.
+    ///
+    /// Fails with [`SynthesisError::DivisionByZero`], leaving `self`
+    /// unchanged, if any evaluation of `divisor` is zero.
+    pub fn divide_by_on_coset(
+        &mut self,
+        worker: &Worker,
+        divisor: &EvaluationDomain<S, Scalar<S>>,
+    ) -> Result<(), SynthesisError> {
+        assert_eq!(self.coeffs.len(), divisor.coeffs.len());
+
+        let mut inverses: Vec<S> = divisor.coeffs.iter().map(|s| s.0).collect();
+        try_batch_invert(&mut inverses).map_err(|_| SynthesisError::DivisionByZero)?;
+
+        worker.scope(self.coeffs.len(), |scope, chunk| {
+            for (a, b) in self.coeffs.chunks_mut(chunk).zip(inverses.chunks(chunk)) {
+                scope.spawn(move |_scope| {
+                    for (a, b) in a.iter_mut().zip(b.iter()) {
+                        a.group_mul_assign(b);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
     /// Perform O(n) multiplication of two polynomials in the domain.
     pub fn mul_assign(&mut self, worker: &Worker, other: &EvaluationDomain<S, Scalar<S>>) {
         assert_eq!(self.coeffs.len(), other.coeffs.len());
@@ -188,8 +456,30 @@ impl<S: PrimeField, G: Group<S>> EvaluationDomain<S, G> {
         });
     }
 
+    /// The number of points in the domain, `radix * 2^exp`, which is also the
+    /// number of (zero-padded) coefficients: `self.as_ref().len()`.
     pub fn len(&self) -> usize {
-        2 << self.exp
+        self.coeffs.len()
+    }
+
+    /// The sum of the polynomial's evaluations over the domain, where the
+    /// domain holds the polynomial's coefficients.
+    ///
+    /// Every non-trivial power of omega sums to zero over the domain, so this
+    /// is just `m * coeffs[0]` and needs no FFT.
+    pub fn sum_over_domain(&self) -> G {
+        let mut sum = self.coeffs[0];
+        sum.group_mul_assign(&S::from(self.coeffs.len() as u64));
+        sum
+    }
+
+    /// The sum of the polynomial's evaluations over the domain, where the
+    /// domain holds those evaluations (e.g. after [`EvaluationDomain::fft`]).
+    pub fn sum_evaluations(&self) -> G {
+        self.coeffs.iter().fold(G::group_zero(), |mut sum, v| {
+            sum.group_add_assign(v);
+            sum
+        })
     }
 }
 
@@ -262,6 +552,90 @@ impl<S: PrimeField> Group<S> for Scalar<S> {
     }
 }
 
+/// The largest odd factor of a mixed-radix domain's size. The combination step
+/// costs `radix` group operations per element.
+#[cfg(feature = "mixed-radix")]
+pub const MAX_RADIX: usize = 15;
+
+/// The little-endian 64-bit limbs of the canonical representative of `x`,
+/// recovered bit by bit with field arithmetic.
+#[cfg(feature = "mixed-radix")]
+fn to_limbs<S: PrimeField>(mut x: S) -> Vec<u64> {
+    let two_inv = S::from(2).invert().unwrap();
+    let mut limbs = vec![0u64; (S::NUM_BITS as usize - 1) / 64 + 1];
+    for i in 0..S::NUM_BITS as usize {
+        if bool::from(x.is_odd()) {
+            limbs[i / 64] |= 1 << (i % 64);
+            x.sub_assign(&S::one());
+        }
+        x.mul_assign(&two_inv);
+    }
+    limbs
+}
+
+/// Divide the little-endian `limbs` by `d`, returning the quotient and the
+/// remainder.
+#[cfg(feature = "mixed-radix")]
+fn div_rem(limbs: &[u64], d: u64) -> (Vec<u64>, u64) {
+    let mut quotient = vec![0; limbs.len()];
+    let mut rem = 0u128;
+    for (q, limb) in quotient.iter_mut().zip(limbs).rev() {
+        let cur = (rem << 64) | u128::from(*limb);
+        *q = (cur / u128::from(d)) as u64;
+        rem = cur % u128::from(d);
+    }
+    (quotient, rem as u64)
+}
+
+/// An FFT over a domain of size `radix * 2^log_n`: a radix-2 FFT of each of the
+/// `radix` interleaved subsequences, combined with a naive size-`radix` DFT.
+fn mixed_radix_fft<S: PrimeField, T: Group<S>>(
+    a: &mut [T],
+    worker: &Worker,
+    omega: &S,
+    log_n: u32,
+    radix: usize,
+) {
+    if radix == 1 {
+        return best_fft(a, worker, omega, log_n);
+    }
+    assert_eq!(a.len(), radix << log_n);
+
+    // A[i] = sum_r omega^(i r) F_r[i mod 2^log_n], where F_r is the FFT of
+    // a[r], a[r + radix], ... over omega^radix, of order 2^log_n.
+    let sub_omega = omega.pow_vartime([radix as u64]);
+    let mut subs: Vec<Vec<T>> = (0..radix)
+        .map(|r| a.iter().skip(r).step_by(radix).cloned().collect())
+        .collect();
+    for sub in &mut subs {
+        best_fft(sub, worker, &sub_omega, log_n);
+    }
+
+    worker.scope(a.len(), |scope, chunk| {
+        let subs = &subs;
+        let mask = (1 << log_n) - 1;
+
+        for (idx, a) in a.chunks_mut(chunk).enumerate() {
+            scope.spawn(move |_scope| {
+                let start = idx * chunk;
+                let mut w = omega.pow_vartime([start as u64]);
+                for (idx, a) in (start..).zip(a) {
+                    let mut acc = T::group_zero();
+                    let mut wr = S::one();
+                    for sub in subs {
+                        let mut t = sub[idx & mask];
+                        t.group_mul_assign(&wr);
+                        acc.group_add_assign(&t);
+                        wr.mul_assign(&w);
+                    }
+                    *a = acc;
+                    w.mul_assign(omega);
+                }
+            });
+        }
+    });
+}
+
 fn best_fft<S: PrimeField, T: Group<S>>(a: &mut [T], worker: &Worker, omega: &S, log_n: u32) {
     let log_cpus = worker.log_num_threads();
 
@@ -408,6 +782,12 @@ fn polynomial_arith() {
                 a.resize(coeffs_a + coeffs_b, Scalar(S::zero()));
                 b.resize(coeffs_a + coeffs_b, Scalar(S::zero()));
 
+                if a.is_empty() {
+                    // There is no domain to multiply the empty polynomials in.
+                    assert!(EvaluationDomain::from_coeffs(a).is_err());
+                    continue;
+                }
+
                 let mut a = EvaluationDomain::from_coeffs(a).unwrap();
                 let mut b = EvaluationDomain::from_coeffs(b).unwrap();
 
@@ -500,3 +880,348 @@ fn parallel_fft_consistency() {
 
     test_consistency::<Fr, _>(rng);
 }
+
+#[cfg(feature = "pairing")]
+#[test]
+fn fft_thread_independence() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+
+    for log_d in 0..10 {
+        let coeffs = (0..(1 << log_d))
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+
+        let outputs: Vec<Vec<[u8; 32]>> = [0, 1, 2, 3]
+            .iter()
+            .map(|log_threads| {
+                let mut domain = EvaluationDomain::from_coeffs(coeffs.clone()).unwrap();
+                domain.fft(&Worker::with_log_threads(*log_threads));
+                domain.coeffs.iter().map(|c| c.0.to_repr()).collect()
+            })
+            .collect();
+
+        for output in &outputs[1..] {
+            assert_eq!(&outputs[0], output);
+        }
+
+        // A worker pinned to one thread does the FFT serially, with the same result.
+        let worker = Worker::with_num_threads(1);
+        let mut domain = EvaluationDomain::from_coeffs(coeffs.clone()).unwrap();
+        domain.fft(&worker);
+        let output: Vec<[u8; 32]> = domain.coeffs.iter().map(|c| c.0.to_repr()).collect();
+        assert_eq!(outputs[0], output);
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn fft_padded_domain() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+
+    // A degree-5 polynomial
+    let poly = (0..6)
+        .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+        .collect::<Vec<_>>();
+
+    assert!(EvaluationDomain::from_coeffs_padded_to(poly.clone(), 2).is_err());
+    assert!(matches!(
+        EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs(vec![]),
+        Err(SynthesisError::EmptyDomain)
+    ));
+    // An explicit size is still allowed, for the zero polynomial.
+    let zero = EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_padded_to(vec![], 0).unwrap();
+    assert_eq!(zero.coeffs.len(), 1);
+    assert_eq!(zero.coeffs[0].0, Fr::zero());
+    assert!(EvaluationDomain::from_coeffs_padded_to(poly.clone(), Fr::S).is_err());
+
+    let mut domain = EvaluationDomain::from_coeffs_padded_to(poly.clone(), 4).unwrap();
+    assert_eq!(domain.coeffs.len(), 16);
+    domain.fft(&worker);
+
+    // Compare against naive evaluation at the powers of omega.
+    let mut point = Fr::one();
+    for eval in &domain.coeffs {
+        let mut expected = Fr::zero();
+        for coeff in poly.iter().rev() {
+            expected = expected * point + coeff.0;
+        }
+        assert_eq!(eval.0, expected);
+        point *= domain.omega;
+    }
+    assert_eq!(point, Fr::one());
+}
+
+#[test]
+fn domain_sizes() {
+    assert_eq!(required_domain_exp(0), 0);
+    assert_eq!(required_domain_exp(1), 0);
+    assert_eq!(required_domain_exp(5), 3);
+    assert_eq!(required_domain_exp(8), 3);
+    assert_eq!(required_domain_exp(9), 4);
+    assert_eq!(required_domain_exp(usize::MAX), usize::BITS);
+
+    assert_eq!(domain_size(1, 3), Some(8));
+    assert_eq!(domain_size(3, 3), Some(24));
+    assert_eq!(
+        domain_size(1, usize::BITS - 1),
+        Some(1 << (usize::BITS - 1))
+    );
+    assert_eq!(domain_size(1, usize::BITS), None);
+    assert_eq!(domain_size(3, usize::BITS - 1), None);
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn from_coeffs_len() {
+    use bls12_381::Scalar as Fr;
+
+    // Five coefficients are padded to a domain of eight.
+    let domain = EvaluationDomain::from_coeffs(vec![Scalar(Fr::one()); 5]).unwrap();
+    assert_eq!(domain.len(), 8);
+    assert_eq!(domain.exp(), 3);
+}
+
+// `len` used to be `2 << exp`, twice the domain size.
+#[cfg(feature = "pairing")]
+#[test]
+fn len_matches_coeffs() {
+    use bls12_381::Scalar as Fr;
+
+    let worker = Worker::new();
+
+    for n in 1..=33 {
+        let mut domain = EvaluationDomain::from_coeffs(vec![Scalar(Fr::one()); n]).unwrap();
+        assert_eq!(domain.len(), domain.as_ref().len());
+        assert_eq!(domain.len(), 1 << domain.exp());
+        domain.fft(&worker);
+        assert_eq!(domain.len(), domain.as_ref().len());
+    }
+    for exp in 0..6 {
+        let domain =
+            EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_padded_to(vec![], exp).unwrap();
+        assert_eq!(domain.len(), domain.as_ref().len());
+        assert_eq!(domain.len(), 1 << exp);
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn from_coeffs_sized() {
+    use bls12_381::Scalar as Fr;
+
+    let worker = Worker::new();
+    let coeffs = |n: u64| (1..=n).map(|i| Scalar(Fr::from(i))).collect::<Vec<_>>();
+
+    let mut a = EvaluationDomain::from_coeffs_sized(coeffs(3), 10).unwrap();
+    let b = EvaluationDomain::from_coeffs_sized(coeffs(6), 10).unwrap();
+    assert_eq!(a.len(), b.len());
+    assert_eq!(a.coeffs.len(), 16);
+    assert_eq!(b.coeffs.len(), 16);
+    a.mul_assign(&worker, &b);
+
+    // A hint below the number of coefficients doesn't truncate them.
+    let c = EvaluationDomain::from_coeffs_sized(coeffs(20), 10).unwrap();
+    assert_eq!(c.coeffs.len(), 32);
+    assert!(c.coeffs[..20] == coeffs(20)[..]);
+
+    // The hint alone is enough for a domain of zeros.
+    let zero = EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_sized(vec![], 3).unwrap();
+    assert_eq!(zero.coeffs.len(), 4);
+    assert!(matches!(
+        EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_sized(vec![], 0),
+        Err(SynthesisError::EmptyDomain)
+    ));
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn divide_by_on_coset() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+
+    let p = (0..5)
+        .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+        .collect::<Vec<_>>();
+    let q = (0..4)
+        .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+        .collect::<Vec<_>>();
+    let mut pq = vec![Scalar(Fr::zero()); p.len() + q.len() - 1];
+    for (i, p) in p.iter().enumerate() {
+        for (j, q) in q.iter().enumerate() {
+            pq[i + j].0 += p.0 * q.0;
+        }
+    }
+
+    let mut pq = EvaluationDomain::from_coeffs(pq).unwrap();
+    let mut q = EvaluationDomain::from_coeffs_padded_to(q, pq.exp).unwrap();
+    pq.coset_fft(&worker);
+    q.coset_fft(&worker);
+    pq.divide_by_on_coset(&worker, &q).unwrap();
+    pq.icoset_fft(&worker);
+    assert!(pq.coeffs[..p.len()] == p[..]);
+    assert!(pq.coeffs[p.len()..].iter().all(|c| c.0.is_zero_vartime()));
+
+    // A zero evaluation is rejected, and leaves the dividend alone.
+    let before = pq.coeffs.clone();
+    q.coeffs[3] = Scalar(Fr::zero());
+    assert!(matches!(
+        pq.divide_by_on_coset(&worker, &q),
+        Err(SynthesisError::DivisionByZero)
+    ));
+    assert!(pq.coeffs == before);
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn z_on_coset_evals() {
+    use bls12_381::Scalar as Fr;
+
+    let worker = Worker::new();
+    let g = Fr::multiplicative_generator();
+
+    for log_d in 0..6 {
+        let domain =
+            EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs_padded_to(vec![], log_d).unwrap();
+        let evals: Vec<Fr> = domain.z_on_coset_evals(&worker);
+        assert_eq!(evals.len(), 1 << log_d);
+
+        // Evaluate t(x) = x^m - 1 directly at each coset point.
+        let mut point = g;
+        for eval in &evals {
+            assert_eq!(*eval, domain.z(&g));
+            assert_eq!(*eval, domain.z(&point));
+            point *= domain.omega;
+        }
+        assert_eq!(point, g);
+
+        // They divide exactly like divide_by_z_on_coset.
+        let coeffs = (0..(1 << log_d))
+            .map(|i| Scalar(Fr::from(i as u64 + 1)))
+            .collect::<Vec<_>>();
+        let mut by_z = EvaluationDomain::from_coeffs(coeffs.clone()).unwrap();
+        by_z.divide_by_z_on_coset(&worker);
+        let mut by_evals = EvaluationDomain::from_coeffs(coeffs).unwrap();
+        let z = EvaluationDomain::from_coeffs(evals.into_iter().map(Scalar).collect()).unwrap();
+        by_evals.divide_by_on_coset(&worker, &z).unwrap();
+        assert!(by_z.coeffs == by_evals.coeffs);
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn sum_over_domain() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+
+    for log_d in 0..6 {
+        let poly = (0..(1 << log_d))
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+        let mut domain = EvaluationDomain::from_coeffs(poly.clone()).unwrap();
+
+        // Naively evaluate at every power of omega.
+        let mut naive = Fr::zero();
+        let mut point = Fr::one();
+        for _ in 0..(1 << log_d) {
+            let mut eval = Fr::zero();
+            for coeff in poly.iter().rev() {
+                eval = eval * point + coeff.0;
+            }
+            naive += eval;
+            point *= domain.omega;
+        }
+
+        assert_eq!(domain.sum_over_domain().0, naive);
+        domain.fft(&worker);
+        assert_eq!(domain.sum_evaluations().0, naive);
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn batch_inversion() {
+    use bls12_381::Scalar as Fr;
+
+    let rng = &mut crate::testing::test_rng();
+
+    for n in [0, 1, 2, 3, 17] {
+        let elems = (0..n).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let mut inverted = elems.clone();
+        batch_invert(&mut inverted);
+        for (e, inv) in elems.iter().zip(&inverted) {
+            assert_eq!(*inv, e.invert().unwrap());
+        }
+    }
+
+    let mut elems = (0..5).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+    elems[3] = Fr::zero();
+    let original = elems.clone();
+    assert_eq!(try_batch_invert(&mut elems), Err(3));
+    assert_eq!(elems, original);
+}
+
+#[cfg(all(feature = "mixed-radix", feature = "mirage"))]
+#[test]
+fn mixed_radix_domain() {
+    // p - 1 = 2^10 * 3^2 * 7, with a 2-adic subgroup of at most 2^9 points.
+    use crate::mirage::tests::Fr;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+
+    for (n, radix, exp) in [(600, 3, 8), (1000, 9, 7)] {
+        let poly = (0..n)
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+        let mut domain = EvaluationDomain::from_coeffs(poly.clone()).unwrap();
+        assert_eq!((domain.radix, domain.exp), (radix, exp));
+        let m = radix << exp;
+        assert_eq!(domain.coeffs.len(), m);
+        assert_eq!(domain.len(), m);
+        assert_eq!(domain.omega.pow_vartime([m as u64]), Fr::one());
+        assert_ne!(domain.omega.pow_vartime([(m / 3) as u64]), Fr::one());
+        assert_ne!(domain.omega.pow_vartime([(m / 2) as u64]), Fr::one());
+
+        domain.fft(&worker);
+
+        // Compare against naive evaluation at the powers of omega.
+        let mut point = Fr::one();
+        for eval in &domain.coeffs {
+            let mut expected = Fr::zero();
+            for coeff in poly.iter().rev() {
+                expected = expected * point + coeff.0;
+            }
+            assert_eq!(eval.0, expected);
+            point *= domain.omega;
+        }
+
+        domain.ifft(&worker);
+        assert!(domain.coeffs[..n] == poly[..]);
+        domain.coset_fft(&worker);
+        domain.icoset_fft(&worker);
+        assert!(domain.coeffs[..n] == poly[..]);
+        assert_eq!(
+            domain.z(&Fr::from(5)),
+            Fr::from(5).pow_vartime([m as u64]) - Fr::one()
+        );
+    }
+
+    // No radix of at most MAX_RADIX fits 9 * 2^9 < 5000 points.
+    assert!(matches!(
+        EvaluationDomain::<Fr, Scalar<Fr>>::from_coeffs(vec![Scalar(Fr::zero()); 5000]),
+        Err(SynthesisError::PolynomialDegreeTooLarge)
+    ));
+}
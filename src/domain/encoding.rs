@@ -0,0 +1,139 @@
+//! Serialization of evaluation domains of [`Scalar`]s and [`Point`]s.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use ff::PrimeField;
+use group::cofactor::CofactorCurve;
+
+use super::{EvaluationDomain, Group, Point, Scalar};
+use crate::curve_io::{initial_capacity, GroupReader, GroupWriter, DEFAULT_MAX_GROUPS};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+impl<S: PrimeField> sealed::Sealed for Scalar<S> {}
+impl<G: CofactorCurve> sealed::Sealed for Point<G> {}
+
+/// The elements of domains that [`EvaluationDomain::write`] and
+/// [`EvaluationDomain::read`] can serialize.
+///
+/// This trait is sealed: it is implemented for [`Scalar`] and [`Point`] only,
+/// so that [`Group`] needs no serialization of its own.
+pub trait DomainElementIO: sealed::Sealed + Sized {
+    fn write_elem<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    /// Read an element written by [`DomainElementIO::write_elem`], rejecting
+    /// invalid encodings.
+    fn read_elem<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+impl<S: PrimeField> DomainElementIO for Scalar<S> {
+    fn write_elem<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_scalar(&self.0)
+    }
+
+    fn read_elem<R: Read>(reader: &mut R) -> io::Result<Self> {
+        reader.read_scalar().map(Scalar)
+    }
+}
+
+impl<G: CofactorCurve> DomainElementIO for Point<G> {
+    fn write_elem<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_group(&self.0)
+    }
+
+    /// Points are checked to be on the curve, but not to be in a subgroup.
+    /// The identity is allowed, since domains hold sums of points.
+    fn read_elem<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut repr = G::Repr::default();
+        reader.read_exact(repr.as_mut())?;
+        let point = G::from_bytes(&repr);
+        if bool::from(point.is_some()) {
+            Ok(Point(point.unwrap()))
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "invalid group"))
+        }
+    }
+}
+
+impl<S: PrimeField, G: Group<S> + DomainElementIO> EvaluationDomain<S, G> {
+    /// Write the domain as its number of elements, as a big-endian `u64`,
+    /// followed by the elements: [`Scalar`]s in their canonical encoding and
+    /// [`Point`]s in their compressed one. The roots of unity are not written;
+    /// [`EvaluationDomain::read`] recomputes them from the size.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<BigEndian>(self.coeffs.len() as u64)?;
+        for e in &self.coeffs {
+            e.write_elem(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Read a domain written by [`EvaluationDomain::write`]. Fails if the
+    /// number of elements is not the size of a domain over `S`, or is more
+    /// than [`DEFAULT_MAX_GROUPS`].
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let len = reader.read_groups_len(DEFAULT_MAX_GROUPS)?;
+        let mut coeffs = Vec::with_capacity(initial_capacity(len));
+        for _ in 0..len {
+            coeffs.push(G::read_elem(&mut reader)?);
+        }
+        let domain = EvaluationDomain::from_coeffs(coeffs)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if domain.coeffs.len() != len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not the size of a domain",
+            ));
+        }
+        Ok(domain)
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn round_trip() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+
+    let scalars = EvaluationDomain::from_coeffs(
+        (0..8)
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect(),
+    )
+    .unwrap();
+    let mut bytes = vec![];
+    scalars.write(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 8 + 8 * 32);
+    let read = EvaluationDomain::<Fr, Scalar<Fr>>::read(&bytes[..]).unwrap();
+    assert!(read.coeffs == scalars.coeffs);
+    assert_eq!(read.omega(), scalars.omega());
+
+    // BLS12-381's groups don't implement `CofactorCurve`, so use the Jubjub
+    // curve. Its scalar field has a 2-adicity of 1, so the only radix-2 domain
+    // has one element. The identity is a valid one.
+    for p in [
+        <jubjub::ExtendedPoint as group::Group>::random(&mut *rng),
+        <jubjub::ExtendedPoint as group::Group>::identity(),
+    ] {
+        let points = EvaluationDomain::from_coeffs(vec![Point(p)]).unwrap();
+        let mut bytes = vec![];
+        points.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 8 + 32);
+        let read =
+            EvaluationDomain::<jubjub::Fr, Point<jubjub::ExtendedPoint>>::read(&bytes[..]).unwrap();
+        assert!(read.coeffs == points.coeffs);
+    }
+
+    // A truncated domain, and one of 3 elements, are rejected.
+    assert!(EvaluationDomain::<Fr, Scalar<Fr>>::read(&bytes[..bytes.len() - 1]).is_err());
+    let mut three = vec![];
+    three.extend_from_slice(&3u64.to_be_bytes());
+    for _ in 0..3 {
+        Scalar(Fr::one()).write_elem(&mut three).unwrap();
+    }
+    assert!(EvaluationDomain::<Fr, Scalar<Fr>>::read(&three[..]).is_err());
+}
@@ -0,0 +1,225 @@
+//! An FFT that stages the domain through a scratch file, so that it never
+//! holds more than one copy of the coefficients in memory.
+//!
+//! This is the four-step FFT: a domain of size `n = n1 * n2` is viewed as an
+//! `n1 x n2` matrix with `a[j + n2 * i]` in row `i` and column `j`. Each column
+//! is transformed with a size-`n1` FFT, multiplied by twiddle factors and
+//! written to the scratch file. The rows of the transformed matrix are then
+//! read back a batch at a time and transformed with size-`n2` FFTs, straight
+//! over the coefficients.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ff::PrimeField;
+
+use super::{best_fft, mixed_radix_fft, EvaluationDomain, Group, Scalar};
+use crate::multicore::Worker;
+
+/// A scratch file, removed when it is dropped.
+struct ScratchFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl ScratchFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = dir.join(format!(
+            "bellman-fft-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(ScratchFile { file, path })
+    }
+}
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn repr_len<S: PrimeField>() -> usize {
+    S::Repr::default().as_ref().len()
+}
+
+fn write_elems<S: PrimeField>(writer: &mut impl Write, elems: &[Scalar<S>]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(elems.len() * repr_len::<S>());
+    for e in elems {
+        bytes.extend_from_slice(e.0.to_repr().as_ref());
+    }
+    writer.write_all(&bytes)
+}
+
+fn read_elems<S: PrimeField>(reader: &mut impl Read, elems: &mut [Scalar<S>]) -> io::Result<()> {
+    let len = repr_len::<S>();
+    let mut bytes = vec![0u8; elems.len() * len];
+    reader.read_exact(&mut bytes)?;
+    for (e, bytes) in elems.iter_mut().zip(bytes.chunks(len)) {
+        let mut repr = S::Repr::default();
+        repr.as_mut().copy_from_slice(bytes);
+        let s = S::from_repr(repr);
+        if bool::from(s.is_none()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "scratch file holds a non-canonical scalar",
+            ));
+        }
+        e.0 = s.unwrap();
+    }
+    Ok(())
+}
+
+impl<S: PrimeField> EvaluationDomain<S, Scalar<S>> {
+    /// The same as [`EvaluationDomain::fft`], but staging the intermediate
+    /// results through a scratch file in `scratch_dir`, for domains too large
+    /// to transform in memory. The file is removed before returning.
+    ///
+    /// [`EvaluationDomain::fft`] needs about twice the memory of the domain;
+    /// this needs about as much as the domain, plus an eighth of it to
+    /// transpose the matrix in batches. An I/O error while the scratch file is
+    /// written leaves the domain unchanged. The output is written over the
+    /// coefficients as the file is read back, so an error then leaves the
+    /// domain its size, but with its coefficients partly transformed.
+    pub fn fft_out_of_core<P: AsRef<Path>>(
+        &mut self,
+        worker: &Worker,
+        scratch_dir: P,
+    ) -> io::Result<()> {
+        let n = self.coeffs.len();
+        // n1 = 2^log_n1 columns of height n2 = radix * 2^log_n2, so that the
+        // rows carry the odd factor of a mixed-radix domain.
+        let log_n1 = self.exp / 2;
+        let log_n2 = self.exp - log_n1;
+        let (n1, n2) = (1 << log_n1, n >> log_n1);
+        let len = repr_len::<S>() as u64;
+        let mut scratch = ScratchFile::create(scratch_dir.as_ref())?;
+
+        // Column j holds a[j], a[j + n2], ..., and is written transformed to
+        // positions j * n1..(j + 1) * n1 of the file.
+        let column_omega = self.omega.pow_vartime([n2 as u64]);
+        let mut column = vec![Scalar(S::zero()); n1];
+        let mut w_j = S::one();
+        for j in 0..n2 {
+            for (c, a) in column
+                .iter_mut()
+                .zip(self.coeffs.iter().skip(j).step_by(n2))
+            {
+                *c = *a;
+            }
+            best_fft(&mut column, worker, &column_omega, log_n1);
+            // Twiddle by omega^(j * k1).
+            let mut twiddle = S::one();
+            for c in column.iter_mut() {
+                c.group_mul_assign(&twiddle);
+                twiddle.mul_assign(&w_j);
+            }
+            w_j.mul_assign(&self.omega);
+            write_elems(&mut scratch.file, &column)?;
+        }
+        drop(column);
+
+        // Row k1 of the transformed matrix becomes A[k1], A[k1 + n1], ... .
+        // The coefficients are all in the scratch file by now, so the rows are
+        // written over them, and the buffer is put back in the domain whether
+        // or not reading the file back succeeds.
+        let row_omega = self.omega.pow_vartime([n1 as u64]);
+        let radix = self.radix;
+        let mut coeffs = std::mem::take(&mut self.coeffs);
+        let mut read_back = || -> io::Result<()> {
+            let batch = std::cmp::max(1, n1 / 8);
+            let mut segment = vec![Scalar(S::zero()); batch];
+            let mut rows = vec![vec![Scalar(S::zero()); n2]; batch];
+            for k1 in (0..n1).step_by(batch) {
+                let batch = std::cmp::min(batch, n1 - k1);
+                for j in 0..n2 {
+                    let pos = (j * n1 + k1) as u64 * len;
+                    scratch.file.seek(SeekFrom::Start(pos))?;
+                    read_elems(&mut scratch.file, &mut segment[..batch])?;
+                    for (row, s) in rows.iter_mut().zip(&segment[..batch]) {
+                        row[j] = *s;
+                    }
+                }
+                for (r, row) in rows[..batch].iter_mut().enumerate() {
+                    mixed_radix_fft(row, worker, &row_omega, log_n2, radix);
+                    for (c, a) in coeffs.iter_mut().skip(k1 + r).step_by(n1).zip(row.iter()) {
+                        *c = *a;
+                    }
+                }
+            }
+            Ok(())
+        };
+        let result = read_back();
+        self.coeffs = coeffs;
+        result
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn matches_fft() {
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+    let dir = std::env::temp_dir();
+
+    for log_d in 0..12 {
+        let d = 1 << log_d;
+        let coeffs = (0..d)
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+        let mut expected = EvaluationDomain::from_coeffs(coeffs.clone()).unwrap();
+        let mut actual = EvaluationDomain::from_coeffs(coeffs).unwrap();
+
+        expected.fft(&worker);
+        actual.fft_out_of_core(&worker, &dir).unwrap();
+        assert!(actual.coeffs == expected.coeffs);
+
+        // The inverse still applies.
+        actual.ifft(&worker);
+        expected.ifft(&worker);
+        assert!(actual.coeffs == expected.coeffs);
+    }
+
+    // A missing scratch directory fails, leaving the domain unchanged.
+    let mut domain = EvaluationDomain::from_coeffs(vec![Scalar(Fr::one()); 4]).unwrap();
+    assert!(domain
+        .fft_out_of_core(&worker, dir.join("bellman-missing-dir"))
+        .is_err());
+    assert!(domain.coeffs == vec![Scalar(Fr::one()); 4]);
+}
+
+#[cfg(all(feature = "mixed-radix", feature = "mirage"))]
+#[test]
+fn matches_mixed_radix_fft() {
+    use crate::mirage::tests::Fr;
+    use ff::Field;
+
+    let rng = &mut crate::testing::test_rng();
+    let worker = Worker::new();
+
+    // 3 * 2^8 and 9 * 2^7 points.
+    for n in [600, 1000] {
+        let coeffs = (0..n)
+            .map(|_| Scalar::<Fr>(Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+        let mut expected = EvaluationDomain::from_coeffs(coeffs.clone()).unwrap();
+        let mut actual = EvaluationDomain::from_coeffs(coeffs).unwrap();
+
+        expected.fft(&worker);
+        actual
+            .fft_out_of_core(&worker, std::env::temp_dir())
+            .unwrap();
+        assert!(actual.coeffs == expected.coeffs);
+    }
+}
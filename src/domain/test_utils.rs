@@ -0,0 +1,92 @@
+//! Helpers for checking [`Group`] implementations.
+
+use ff::PrimeField;
+use rand_core::RngCore;
+
+use super::Group;
+
+/// Check that `G` satisfies the group and module laws the FFT relies on, for
+/// a few random elements drawn with `random` and random scalars.
+///
+/// Panics naming the first law that fails.
+pub fn check_laws<S, G, R>(rng: &mut R, mut random: impl FnMut(&mut R) -> G)
+where
+    S: PrimeField,
+    G: Group<S> + PartialEq,
+    R: RngCore,
+{
+    let add = |a: G, b: &G| {
+        let mut r = a;
+        r.group_add_assign(b);
+        r
+    };
+    let sub = |a: G, b: &G| {
+        let mut r = a;
+        r.group_sub_assign(b);
+        r
+    };
+    let mul = |a: G, x: &S| {
+        let mut r = a;
+        r.group_mul_assign(x);
+        r
+    };
+    let zero = G::group_zero();
+
+    for _ in 0..10 {
+        let a = random(rng);
+        let b = random(rng);
+        let c = random(rng);
+        let x = S::random(&mut *rng);
+        let y = S::random(&mut *rng);
+
+        assert!(add(zero, &a) == a, "zero + a == a");
+        assert!(add(a, &zero) == a, "a + zero == a");
+        assert!(sub(a, &a) == zero, "a - a == zero");
+        assert!(sub(add(a, &b), &b) == a, "a + b - b == a");
+        assert!(add(a, &b) == add(b, &a), "a + b == b + a");
+        assert!(
+            add(add(a, &b), &c) == add(a, &add(b, &c)),
+            "(a + b) + c == a + (b + c)"
+        );
+        assert!(mul(a, &S::one()) == a, "a * 1 == a");
+        assert!(mul(a, &S::zero()) == zero, "a * 0 == zero");
+        assert!(
+            mul(mul(a, &x), &y) == mul(a, &(x * y)),
+            "(a * x) * y == a * (x * y)"
+        );
+        assert!(
+            mul(add(a, &b), &x) == add(mul(a, &x), &mul(b, &x)),
+            "(a + b) * x == a * x + b * x"
+        );
+        assert!(
+            mul(a, &(x + y)) == add(mul(a, &x), &mul(a, &y)),
+            "a * (x + y) == a * x + a * y"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{Point, Scalar};
+    use bls12_381::Scalar as Fr;
+    use ff::Field;
+
+    #[test]
+    fn scalar_laws() {
+        check_laws(&mut crate::testing::test_rng(), |rng| {
+            Scalar(Fr::random(rng))
+        });
+    }
+
+    #[test]
+    fn point_laws() {
+        // BLS12-381's groups don't implement `CofactorCurve`, so use the Jubjub curve. Its
+        // points must be in the prime-order subgroup for scalar multiplication to be a
+        // module action of its scalar field.
+        check_laws(&mut crate::testing::test_rng(), |rng| {
+            let p = <jubjub::SubgroupPoint as group::Group>::random(rng);
+            Point(jubjub::ExtendedPoint::from(p))
+        });
+    }
+}
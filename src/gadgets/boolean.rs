@@ -511,6 +511,16 @@ impl Boolean {
         }
     }
 
+    /// Perform OR over two boolean operands
+    pub fn or<'a, Scalar, CS>(cs: CS, a: &'a Self, b: &'a Self) -> Result<Self, SynthesisError>
+    where
+        Scalar: PrimeField,
+        CS: ConstraintSystem<Scalar>,
+    {
+        // a OR b = NOT((NOT a) AND (NOT b))
+        Ok(Boolean::and(cs, &a.not(), &b.not())?.not())
+    }
+
     /// Computes (a and b) xor ((not a) and c)
     pub fn sha256_ch<'a, Scalar, CS>(
         mut cs: CS,
@@ -1523,6 +1533,64 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_boolean_or() {
+        let variants = [
+            OperandType::True,
+            OperandType::False,
+            OperandType::AllocatedTrue,
+            OperandType::AllocatedFalse,
+            OperandType::NegatedAllocatedTrue,
+            OperandType::NegatedAllocatedFalse,
+        ];
+
+        for first_operand in variants.iter().cloned() {
+            for second_operand in variants.iter().cloned() {
+                let mut cs = TestConstraintSystem::<Scalar>::new();
+
+                let mut dyn_construct = |operand, name| {
+                    let cs = cs.namespace(|| name);
+
+                    match operand {
+                        OperandType::True => Boolean::constant(true),
+                        OperandType::False => Boolean::constant(false),
+                        OperandType::AllocatedTrue => {
+                            Boolean::from(AllocatedBit::alloc(cs, Some(true)).unwrap())
+                        }
+                        OperandType::AllocatedFalse => {
+                            Boolean::from(AllocatedBit::alloc(cs, Some(false)).unwrap())
+                        }
+                        OperandType::NegatedAllocatedTrue => {
+                            Boolean::from(AllocatedBit::alloc(cs, Some(true)).unwrap()).not()
+                        }
+                        OperandType::NegatedAllocatedFalse => {
+                            Boolean::from(AllocatedBit::alloc(cs, Some(false)).unwrap()).not()
+                        }
+                    }
+                };
+
+                let a = dyn_construct(first_operand, "a");
+                let b = dyn_construct(second_operand, "b");
+                let c = Boolean::or(&mut cs, &a, &b).unwrap();
+
+                assert!(cs.is_satisfied());
+                assert_eq!(
+                    c.get_value().unwrap(),
+                    a.get_value().unwrap() | b.get_value().unwrap()
+                );
+                // Only two constants fold to a constant, as does a true
+                // constant with anything.
+                let constant = matches!(
+                    (first_operand, second_operand),
+                    (OperandType::True, _)
+                        | (_, OperandType::True)
+                        | (OperandType::False, OperandType::False)
+                );
+                assert_eq!(c.is_constant(), constant);
+            }
+        }
+    }
+
     #[test]
     fn test_u64_into_boolean_vec_le() {
         let mut cs = TestConstraintSystem::<Scalar>::new();
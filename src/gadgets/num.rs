@@ -218,6 +218,101 @@ impl<Scalar: PrimeField> AllocatedNum<Scalar> {
         Ok(bits.into_iter().map(Boolean::from).collect())
     }
 
+    /// Decompose the allocated number into `n` little-endian bits, enforcing
+    /// that it is the weighted sum of those bits. This range-checks the
+    /// number to `[0, 2^n)`.
+    ///
+    /// Panics if `n` exceeds the capacity of the field, since the
+    /// recomposition could then wrap around the modulus.
+    pub fn decompose_bits<CS>(
+        &self,
+        mut cs: CS,
+        n: usize,
+    ) -> Result<Vec<AllocatedBit>, SynthesisError>
+    where
+        Scalar: PrimeFieldBits,
+        CS: ConstraintSystem<Scalar>,
+    {
+        assert!(n <= Scalar::CAPACITY as usize);
+
+        let values: Vec<Option<bool>> = match self.value {
+            Some(ref value) => value
+                .to_le_bits()
+                .iter()
+                .by_vals()
+                .take(n)
+                .map(Some)
+                .collect(),
+            None => vec![None; n],
+        };
+
+        let bits = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), b))
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let mut lc = LinearCombination::zero();
+        let mut coeff = Scalar::one();
+
+        for bit in bits.iter() {
+            lc = lc + (coeff, bit.get_variable());
+
+            coeff = coeff.double();
+        }
+
+        lc = lc - self.variable;
+
+        cs.enforce(|| "recomposition constraint", |lc| lc, |lc| lc, |_| lc);
+
+        Ok(bits)
+    }
+
+    /// Pack little-endian bits into a number, enforcing that it is their
+    /// weighted sum. This is the inverse of [`AllocatedNum::decompose_bits`].
+    ///
+    /// Panics if there are more bits than the capacity of the field, since the
+    /// sum could then wrap around the modulus.
+    pub fn from_bits_le<CS>(mut cs: CS, bits: &[AllocatedBit]) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        assert!(bits.len() <= Scalar::CAPACITY as usize);
+
+        let value = bits.iter().rev().try_fold(Scalar::zero(), |acc, bit| {
+            bit.get_value().map(|b| {
+                if b {
+                    acc.double() + Scalar::one()
+                } else {
+                    acc.double()
+                }
+            })
+        });
+
+        let var = cs.alloc(
+            || "packed num",
+            || value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let mut lc = LinearCombination::zero();
+        let mut coeff = Scalar::one();
+
+        for bit in bits.iter() {
+            lc = lc + (coeff, bit.get_variable());
+
+            coeff = coeff.double();
+        }
+
+        lc = lc - var;
+
+        cs.enforce(|| "packing constraint", |lc| lc, |lc| lc, |_| lc);
+
+        Ok(AllocatedNum {
+            value,
+            variable: var,
+        })
+    }
+
     pub fn mul<CS>(&self, mut cs: CS, other: &Self) -> Result<Self, SynthesisError>
     where
         CS: ConstraintSystem<Scalar>,
@@ -281,6 +376,85 @@ impl<Scalar: PrimeField> AllocatedNum<Scalar> {
         })
     }
 
+    /// Allocates `self / other`, constrained by `quotient * other = self`.
+    ///
+    /// This does not constrain `other` to be nonzero: if both are zero, any
+    /// quotient satisfies the constraint. Use [`AllocatedNum::assert_nonzero`]
+    /// or [`AllocatedNum::invert`] on `other` where that matters.
+    pub fn div<CS>(&self, mut cs: CS, other: &Self) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        let mut value = None;
+
+        let var = cs.alloc(
+            || "quotient num",
+            || {
+                let divisor = *other.value.get()?;
+
+                if divisor.is_zero_vartime() {
+                    return Err(SynthesisError::DivisionByZero);
+                }
+                let tmp = *self.value.get()? * divisor.invert().unwrap();
+
+                value = Some(tmp);
+
+                Ok(tmp)
+            },
+        )?;
+
+        // Constrain: (a/b) * b = a
+        cs.enforce(
+            || "division constraint",
+            |lc| lc + var,
+            |lc| lc + other.variable,
+            |lc| lc + self.variable,
+        );
+
+        Ok(AllocatedNum {
+            value,
+            variable: var,
+        })
+    }
+
+    /// Allocates the inverse of `self`, constrained by `self * inverse = 1`,
+    /// which also proves that `self` is nonzero.
+    pub fn invert<CS>(&self, mut cs: CS) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        let mut value = None;
+
+        let var = cs.alloc(
+            || "inverse num",
+            || {
+                let tmp = *self.value.get()?;
+
+                if tmp.is_zero_vartime() {
+                    return Err(SynthesisError::DivisionByZero);
+                }
+                let tmp = tmp.invert().unwrap();
+
+                value = Some(tmp);
+
+                Ok(tmp)
+            },
+        )?;
+
+        // Constrain: a * a^-1 = 1
+        cs.enforce(
+            || "inversion constraint",
+            |lc| lc + self.variable,
+            |lc| lc + var,
+            |lc| lc + CS::one(),
+        );
+
+        Ok(AllocatedNum {
+            value,
+            variable: var,
+        })
+    }
+
     pub fn assert_nonzero<CS>(&self, mut cs: CS) -> Result<(), SynthesisError>
     where
         CS: ConstraintSystem<Scalar>,
@@ -311,6 +485,22 @@ impl<Scalar: PrimeField> AllocatedNum<Scalar> {
         Ok(())
     }
 
+    /// Constrains `self` and `other` to be equal.
+    pub fn assert_equal<CS>(&self, mut cs: CS, other: &Self) -> Result<(), SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        // Constrain: 0 * 0 = a - b
+        cs.enforce(
+            || "equality constraint",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + self.variable - other.variable,
+        );
+
+        Ok(())
+    }
+
     /// Takes two allocated numbers (a, b) and returns
     /// (b, a) if the condition is true, and (a, b)
     /// otherwise.
@@ -356,6 +546,36 @@ impl<Scalar: PrimeField> AllocatedNum<Scalar> {
         Ok((c, d))
     }
 
+    /// Takes two allocated numbers (a, b) and returns
+    /// a if the condition is true, and b otherwise.
+    pub fn conditionally_select<CS>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        condition: &AllocatedBit,
+    ) -> Result<Self, SynthesisError>
+    where
+        CS: ConstraintSystem<Scalar>,
+    {
+        let c = Self::alloc(cs.namespace(|| "conditional select result"), || {
+            if *condition.get_value().get()? {
+                Ok(*a.value.get()?)
+            } else {
+                Ok(*b.value.get()?)
+            }
+        })?;
+
+        // condition * (a - b) = c - b
+        cs.enforce(
+            || "conditional select",
+            |lc| lc + condition.get_variable(),
+            |lc| lc + a.variable - b.variable,
+            |lc| lc + c.variable - b.variable,
+        );
+
+        Ok(c)
+    }
+
     pub fn get_value(&self) -> Option<Scalar> {
         self.value
     }
@@ -423,7 +643,7 @@ mod test {
     use rand_xorshift::XorShiftRng;
     use std::ops::{Neg, SubAssign};
 
-    use super::{AllocatedNum, Boolean};
+    use super::{AllocatedBit, AllocatedNum, Boolean};
     use crate::gadgets::test::*;
 
     #[test]
@@ -523,6 +743,85 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_num_division() {
+        {
+            let mut cs = TestConstraintSystem::new();
+
+            let n = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Scalar::from(120))).unwrap();
+            let n2 = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(Scalar::from(10))).unwrap();
+            let n3 = n.div(&mut cs, &n2).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(cs.get("quotient num") == Scalar::from(12));
+            assert!(n3.value.unwrap() == Scalar::from(12));
+            cs.set("quotient num", Scalar::from(13));
+            assert!(cs.which_is_unsatisfied() == Some("division constraint"));
+        }
+        {
+            let mut cs = TestConstraintSystem::new();
+
+            let n = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Scalar::from(120))).unwrap();
+            let n2 = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(Scalar::zero())).unwrap();
+            assert!(n.div(&mut cs, &n2).is_err());
+        }
+    }
+
+    #[test]
+    fn test_num_inversion() {
+        {
+            let mut cs = TestConstraintSystem::new();
+
+            let n = AllocatedNum::alloc(&mut cs, || Ok(Scalar::from(3))).unwrap();
+            let inv = n.invert(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(inv.value.unwrap() * Scalar::from(3) == Scalar::one());
+            cs.set("inverse num", Scalar::from(3));
+            assert!(cs.which_is_unsatisfied() == Some("inversion constraint"));
+        }
+        {
+            let mut cs = TestConstraintSystem::new();
+
+            let n = AllocatedNum::alloc(&mut cs, || Ok(Scalar::zero())).unwrap();
+            assert!(n.invert(&mut cs).is_err());
+        }
+    }
+
+    #[test]
+    fn test_num_equality() {
+        for (b, equal) in [(7, true), (8, false)] {
+            let mut cs = TestConstraintSystem::new();
+
+            let n = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(Scalar::from(7))).unwrap();
+            let n2 = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(Scalar::from(b))).unwrap();
+            n.assert_equal(&mut cs, &n2).unwrap();
+
+            assert_eq!(cs.is_satisfied(), equal);
+            if !equal {
+                assert!(cs.which_is_unsatisfied() == Some("equality constraint"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_bits_le() {
+        let mut cs = TestConstraintSystem::new();
+
+        let bits = [true, false, true, true]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(*b)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let n = AllocatedNum::from_bits_le(&mut cs, &bits).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert!(n.value.unwrap() == Scalar::from(13));
+        cs.set("packed num", Scalar::from(12));
+        assert!(cs.which_is_unsatisfied() == Some("packing constraint"));
+    }
+
     #[test]
     fn test_into_bits_strict() {
         let negone = Scalar::one().neg();
@@ -597,4 +896,28 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_decompose_bits() {
+        let mut cs = TestConstraintSystem::new();
+
+        let n = AllocatedNum::alloc(&mut cs, || Ok(Scalar::from(0b1011))).unwrap();
+        let bits = n.decompose_bits(&mut cs, 4).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_constraints(), 5);
+        let values: Vec<bool> = bits.iter().map(|b| b.get_value().unwrap()).collect();
+        assert_eq!(values, vec![true, true, false, true]);
+
+        // A number that doesn't fit in the given width can't be decomposed.
+        let mut cs = TestConstraintSystem::new();
+
+        let n = AllocatedNum::alloc(&mut cs, || Ok(Scalar::from(16))).unwrap();
+        n.decompose_bits(&mut cs, 4).unwrap();
+
+        assert_eq!(
+            cs.which_is_unsatisfied().unwrap(),
+            "recomposition constraint"
+        );
+    }
 }
@@ -5,21 +5,28 @@
 use group::{prime::PrimeCurveAffine, GroupEncoding, UncompressedEncoding};
 use pairing::{Engine, MultiMillerLoop};
 
+#[cfg(feature = "prover")]
 use crate::SynthesisError;
 
+#[cfg(feature = "prover")]
 use crate::multiexp::SourceBuilder;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{self, Read, Write};
+#[cfg(feature = "prover")]
 use std::sync::Arc;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod tests;
 
+#[cfg(feature = "prover")]
 pub mod generator;
+#[cfg(feature = "prover")]
 pub mod prover;
 mod verifier;
 
+#[cfg(feature = "prover")]
 pub use self::generator::*;
+#[cfg(feature = "prover")]
 pub use self::prover::*;
 pub use self::verifier::*;
 
@@ -219,6 +226,7 @@ impl<E: Engine> VerifyingKey<E> {
     }
 }
 
+#[cfg(feature = "prover")]
 #[derive(Clone)]
 pub struct Parameters<E: Engine> {
     pub vk: VerifyingKey<E>,
@@ -244,6 +252,7 @@ pub struct Parameters<E: Engine> {
     pub b_g2: Arc<Vec<E::G2Affine>>,
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> PartialEq for Parameters<E> {
     fn eq(&self, other: &Self) -> bool {
         self.vk == other.vk
@@ -255,6 +264,7 @@ impl<E: Engine> PartialEq for Parameters<E> {
     }
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> Parameters<E> {
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         self.vk.write(&mut writer)?;
@@ -409,6 +419,7 @@ pub struct PreparedVerifyingKey<E: MultiMillerLoop> {
     ic: Vec<E::G1Affine>,
 }
 
+#[cfg(feature = "prover")]
 pub trait ParameterSource<E: Engine> {
     type G1Builder: SourceBuilder<E::G1Affine>;
     type G2Builder: SourceBuilder<E::G2Affine>;
@@ -433,6 +444,7 @@ pub trait ParameterSource<E: Engine> {
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError>;
 }
 
+#[cfg(feature = "prover")]
 impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
     type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
     type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
@@ -474,7 +486,7 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod test_with_bls12_381 {
     use super::*;
     use crate::{Circuit, ConstraintSystem, SynthesisError};
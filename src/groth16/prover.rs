@@ -26,7 +26,7 @@ fn eval<S: PrimeField>(
 ) -> S {
     let mut acc = S::zero();
 
-    for &(index, coeff) in lc.0.iter() {
+    for (index, coeff) in lc.iter() {
         let mut tmp;
 
         if !coeff.is_zero_vartime() {
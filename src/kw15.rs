@@ -12,20 +12,55 @@
 //!    * [key_gen]
 //!    * [prove]
 //!    * [verify]
+//!    * [verify_parallel]
 //!
 //! [KW15]: https://eprint.iacr.org/2015/216
 //! [LegoSNARK]: https://eprint.iacr.org/2019/142
 
-use crate::curve_io::{GroupReader, GroupWriter};
+use crate::curve_io::{read_framed, Compression, GroupReader, GroupWriter};
 use crate::multicore::Worker;
-use crate::multiexp::{multiexp, Exponent, FullDensity};
+#[cfg(feature = "prover")]
+use crate::multiexp::{try_multiexp, Exponent, FullDensity};
+use crate::pairing_check::PairingCheck;
+#[cfg(feature = "prover")]
+use crate::SynthesisError;
+#[cfg(feature = "prover")]
 use ff::{Field, PrimeFieldBits};
-use group::{Curve, Group};
-use pairing::{Engine, MillerLoopResult, MultiMillerLoop};
+use group::Curve;
+#[cfg(feature = "prover")]
+use group::Group;
+use pairing::{Engine, MultiMillerLoop};
+#[cfg(feature = "prover")]
 use rand_core::RngCore;
+use std::fmt;
 use std::io::{self, Read, Write};
+#[cfg(feature = "prover")]
 use std::sync::{Arc, Mutex};
 
+/// Header kinds for serialized objects.
+const KIND_PROVING_KEY: u8 = 0;
+const KIND_VERIFYING_KEY: u8 = 1;
+const KIND_PROOF: u8 = 2;
+const KIND_PROVING_KEY_COMPRESSED: u8 = 3;
+const KIND_VERIFYING_KEY_COMPRESSED: u8 = 4;
+const KIND_PROOF_COMPRESSED: u8 = 5;
+
+/// The header kind of an object written with `compression`. The kinds differ
+/// so that compressed data can't be misread as uncompressed, and vice versa.
+fn kind(compression: Compression, uncompressed: u8, compressed: u8) -> u8 {
+    match compression {
+        Compression::Uncompressed => uncompressed,
+        Compression::Compressed => compressed,
+    }
+}
+
+/// Whether to check that points are in the prime-order subgroup when reading:
+/// compressed data is checked, and uncompressed data is trusted.
+fn checked(compression: Compression) -> bool {
+    compression == Compression::Compressed
+}
+
+#[cfg(feature = "prover")]
 pub struct Matrix<E: Engine> {
     /// The number of commitments (l)
     num_cmts: usize,
@@ -35,6 +70,7 @@ pub struct Matrix<E: Engine> {
     nonzero_entries: Vec<(usize, usize, E::G1Affine)>,
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> Matrix<E> {
     /// Create a new, all-zero, matrix
     pub fn new(num_cmts: usize, num_wits: usize) -> Self {
@@ -50,12 +86,66 @@ impl<E: Engine> Matrix<E> {
         assert!(wit_i < self.num_wits);
         self.nonzero_entries.push((cmt_i, wit_i, value));
     }
+
+    /// The block-diagonal matrix with `self` above and to the left of
+    /// `other`: the commitments of `other` follow those of `self`, and are to
+    /// witnesses that follow those of `self`.
+    pub fn block_diag(&self, other: &Matrix<E>) -> Self {
+        let mut m = Self::new(
+            self.num_cmts + other.num_cmts,
+            self.num_wits + other.num_wits,
+        );
+        m.nonzero_entries = self.nonzero_entries.clone();
+        m.extend_offset(other, self.num_cmts, self.num_wits);
+        m
+    }
+
+    /// The matrix with `other` to the right of `self`: each commitment is to
+    /// the witnesses of `self` followed by those of `other`.
+    ///
+    /// Panics if the matrices have different numbers of commitments.
+    pub fn hstack(&self, other: &Matrix<E>) -> Self {
+        assert_eq!(self.num_cmts, other.num_cmts);
+        let mut m = Self::new(self.num_cmts, self.num_wits + other.num_wits);
+        m.nonzero_entries = self.nonzero_entries.clone();
+        m.extend_offset(other, 0, self.num_wits);
+        m
+    }
+
+    /// The matrix with `other` below `self`: the commitments of `other`
+    /// follow those of `self`, and are to the same witnesses.
+    ///
+    /// Panics if the matrices have different numbers of witnesses.
+    pub fn vstack(&self, other: &Matrix<E>) -> Self {
+        assert_eq!(self.num_wits, other.num_wits);
+        let mut m = Self::new(self.num_cmts + other.num_cmts, self.num_wits);
+        m.nonzero_entries = self.nonzero_entries.clone();
+        m.extend_offset(other, self.num_cmts, 0);
+        m
+    }
+
+    /// Add the entries of `other`, moved down by `cmt_offset` and right by
+    /// `wit_offset`.
+    fn extend_offset(&mut self, other: &Matrix<E>, cmt_offset: usize, wit_offset: usize) {
+        for (cmt_i, wit_i, value) in &other.nonzero_entries {
+            self.add_entry(cmt_i + cmt_offset, wit_i + wit_offset, *value);
+        }
+    }
 }
 
 pub struct ProvingKey<E: Engine> {
     p_g1: Vec<E::G1Affine>,
 }
 
+/// Shows the number of points, not the points themselves.
+impl<E: Engine> fmt::Debug for ProvingKey<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProvingKey")
+            .field("p_g1", &format_args!("{} points", self.p_g1.len()))
+            .finish()
+    }
+}
+
 impl<E: Engine> std::cmp::PartialEq for ProvingKey<E> {
     fn eq(&self, other: &Self) -> bool {
         self.p_g1 == other.p_g1
@@ -63,12 +153,83 @@ impl<E: Engine> std::cmp::PartialEq for ProvingKey<E> {
 }
 
 impl<E: Engine> ProvingKey<E> {
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        writer.write_groups_uncompressed(&self.p_g1)
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Uncompressed)
+    }
+
+    /// Read a key written by [`Self::write`]. The points are trusted, and not
+    /// checked to be in the prime-order subgroup: use [`Self::read_checked`]
+    /// for input from an untrusted source.
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Uncompressed)
+    }
+
+    /// Like [`Self::read`], but checking that every point is in the prime-order
+    /// subgroup.
+    pub fn read_checked<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with_checked(reader, Compression::Uncompressed, true)
+    }
+
+    /// Read a key in the format used before the versioned header was added:
+    /// that of [`Self::write`], without the header. Like [`Self::read`], the
+    /// points are trusted.
+    pub fn read_legacy<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_body(reader, Compression::Uncompressed, false)
+    }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// key.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader))
+    }
+
+    /// Write using compressed points: about half the size of [`Self::write`],
+    /// but reading requires a square root per point.
+    pub fn write_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Compressed)
+    }
+
+    /// Read a key written by [`Self::write_compressed`], checking that every
+    /// point is in the prime-order subgroup.
+    pub fn read_compressed<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Compressed)
+    }
+
+    /// Write with the points encoded as chosen by `compression`.
+    pub fn write_with<W: Write>(&self, mut writer: W, compression: Compression) -> io::Result<()> {
+        writer.write_header(kind(
+            compression,
+            KIND_PROVING_KEY,
+            KIND_PROVING_KEY_COMPRESSED,
+        ))?;
+        writer.write_groups_with(&self.p_g1, compression)
+    }
+
+    /// Read a key written by [`Self::write_with`] with the same `compression`.
+    /// Compressed points are checked to be in the prime-order subgroup.
+    pub fn read_with<R: Read>(reader: R, compression: Compression) -> io::Result<Self> {
+        Self::read_with_checked(reader, compression, checked(compression))
+    }
+
+    fn read_with_checked<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        reader.read_header(kind(
+            compression,
+            KIND_PROVING_KEY,
+            KIND_PROVING_KEY_COMPRESSED,
+        ))?;
+        Self::read_body(reader, compression, checked)
     }
 
-    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
-        let p_g1 = reader.read_groups_uncompressed::<E::G1Affine>(false, true)?;
+    fn read_body<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        let p_g1 = reader.read_groups_with::<E::G1Affine>(compression, checked, true)?;
         Ok(ProvingKey { p_g1 })
     }
 }
@@ -78,6 +239,15 @@ pub struct VerifyingKey<E: Engine> {
     a_g2: E::G2Affine,
 }
 
+/// Shows the number of points, not the points themselves.
+impl<E: Engine> fmt::Debug for VerifyingKey<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyingKey")
+            .field("c_g2", &format_args!("{} points", self.c_g2.len()))
+            .finish_non_exhaustive()
+    }
+}
+
 impl<E: Engine> std::cmp::PartialEq for VerifyingKey<E> {
     fn eq(&self, other: &Self) -> bool {
         self.a_g2 == other.a_g2 && self.c_g2 == other.c_g2
@@ -85,15 +255,86 @@ impl<E: Engine> std::cmp::PartialEq for VerifyingKey<E> {
 }
 
 impl<E: Engine> VerifyingKey<E> {
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        writer.write_groups_uncompressed(&self.c_g2)?;
-        writer.write_group_uncompressed(&self.a_g2)?;
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Uncompressed)
+    }
+
+    /// Read a key written by [`Self::write`]. The points are trusted, and not
+    /// checked to be in the prime-order subgroup: use [`Self::read_checked`]
+    /// for input from an untrusted source.
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Uncompressed)
+    }
+
+    /// Like [`Self::read`], but checking that every point is in the prime-order
+    /// subgroup.
+    pub fn read_checked<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with_checked(reader, Compression::Uncompressed, true)
+    }
+
+    /// Read a key in the format used before the versioned header was added:
+    /// that of [`Self::write`], without the header. Like [`Self::read`], the
+    /// points are trusted.
+    pub fn read_legacy<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_body(reader, Compression::Uncompressed, false)
+    }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// key.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader))
+    }
+
+    /// Write using compressed points: about half the size of [`Self::write`],
+    /// but reading requires a square root per point.
+    pub fn write_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Compressed)
+    }
+
+    /// Read a key written by [`Self::write_compressed`], checking that every
+    /// point is in the prime-order subgroup.
+    pub fn read_compressed<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Compressed)
+    }
+
+    /// Write with the points encoded as chosen by `compression`.
+    pub fn write_with<W: Write>(&self, mut writer: W, compression: Compression) -> io::Result<()> {
+        writer.write_header(kind(
+            compression,
+            KIND_VERIFYING_KEY,
+            KIND_VERIFYING_KEY_COMPRESSED,
+        ))?;
+        writer.write_groups_with(&self.c_g2, compression)?;
+        writer.write_group_with(&self.a_g2, compression)?;
         Ok(())
     }
 
-    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
-        let c_g2 = reader.read_groups_uncompressed::<E::G2Affine>(false, true)?;
-        let a_g2 = reader.read_group_uncompressed::<E::G2Affine>(false, true)?;
+    /// Read a key written by [`Self::write_with`] with the same `compression`.
+    /// Compressed points are checked to be in the prime-order subgroup.
+    pub fn read_with<R: Read>(reader: R, compression: Compression) -> io::Result<Self> {
+        Self::read_with_checked(reader, compression, checked(compression))
+    }
+
+    fn read_with_checked<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        reader.read_header(kind(
+            compression,
+            KIND_VERIFYING_KEY,
+            KIND_VERIFYING_KEY_COMPRESSED,
+        ))?;
+        Self::read_body(reader, compression, checked)
+    }
+
+    fn read_body<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        let c_g2 = reader.read_groups_with::<E::G2Affine>(compression, checked, true)?;
+        let a_g2 = reader.read_group_with::<E::G2Affine>(compression, checked, true)?;
         Ok(VerifyingKey { c_g2, a_g2 })
     }
 }
@@ -109,17 +350,81 @@ impl<E: Engine> std::cmp::PartialEq for Proof<E> {
 }
 
 impl<E: Engine> Proof<E> {
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        writer.write_group_uncompressed(&self.pi_g1)?;
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Uncompressed)
+    }
+
+    /// Read a proof written by [`Self::write`]. The points are trusted, and not
+    /// checked to be in the prime-order subgroup: use [`Self::read_checked`]
+    /// for input from an untrusted source.
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Uncompressed)
+    }
+
+    /// Like [`Self::read`], but checking that the point is in the prime-order
+    /// subgroup.
+    pub fn read_checked<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with_checked(reader, Compression::Uncompressed, true)
+    }
+
+    /// Read a proof in the format used before the versioned header was added:
+    /// that of [`Self::write`], without the header. Like [`Self::read`], the
+    /// point is trusted.
+    pub fn read_legacy<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_body(reader, Compression::Uncompressed, false)
+    }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// proof.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader))
+    }
+
+    /// Write using a compressed point: about half the size of [`Self::write`],
+    /// but reading requires a square root.
+    pub fn write_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_with(writer, Compression::Compressed)
+    }
+
+    /// Read a proof written by [`Self::write_compressed`], checking that the
+    /// point is in the prime-order subgroup.
+    pub fn read_compressed<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with(reader, Compression::Compressed)
+    }
+
+    /// Write with the point encoded as chosen by `compression`.
+    pub fn write_with<W: Write>(&self, mut writer: W, compression: Compression) -> io::Result<()> {
+        writer.write_header(kind(compression, KIND_PROOF, KIND_PROOF_COMPRESSED))?;
+        writer.write_group_with(&self.pi_g1, compression)?;
         Ok(())
     }
 
-    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
-        let pi_g1 = reader.read_group_uncompressed::<E::G1Affine>(false, true)?;
+    /// Read a proof written by [`Self::write_with`] with the same `compression`.
+    /// A compressed point is checked to be in the prime-order subgroup.
+    pub fn read_with<R: Read>(reader: R, compression: Compression) -> io::Result<Self> {
+        Self::read_with_checked(reader, compression, checked(compression))
+    }
+
+    fn read_with_checked<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        reader.read_header(kind(compression, KIND_PROOF, KIND_PROOF_COMPRESSED))?;
+        Self::read_body(reader, compression, checked)
+    }
+
+    fn read_body<R: Read>(
+        mut reader: R,
+        compression: Compression,
+        checked: bool,
+    ) -> io::Result<Self> {
+        let pi_g1 = reader.read_group_with::<E::G1Affine>(compression, checked, true)?;
         Ok(Proof { pi_g1 })
     }
 }
 
+#[cfg(feature = "prover")]
 pub fn key_gen<E, R>(m: &Matrix<E>, mut rng: &mut R) -> (ProvingKey<E>, VerifyingKey<E>)
 where
     E: Engine,
@@ -134,7 +439,7 @@ where
             .map(|_| Mutex::new(E::G1::identity()))
             .collect(),
     );
-    let worker = Worker::new();
+    let worker = Worker::global();
     let nonzero_entries = Arc::new(m.nonzero_entries.clone());
     let k_arc = Arc::new(k.clone());
     worker.scope(nonzero_entries.len(), |scope, chunk| {
@@ -152,41 +457,73 @@ where
         }
     });
 
-    // Compute vk
-    let a_g2 = E::G2::identity() * &a;
-    let c_g2: Vec<E::G2> = k
-        .into_iter()
-        .map(|k_i| E::G2::identity() * (k_i * &a))
-        .collect();
+    // Compute vk, with [C]_2 in parallel
+    let g2 = E::G2::generator();
+    let a_g2 = g2 * a;
+    let mut c_g2 = vec![E::G2::identity(); k.len()];
+    worker.scope(k.len(), |scope, chunk| {
+        if chunk > 0 {
+            for (c_g2, k) in c_g2.chunks_mut(chunk).zip(k.chunks(chunk)) {
+                scope.spawn(move |_scope| {
+                    for (c, k_i) in c_g2.iter_mut().zip(k) {
+                        *c = g2 * (*k_i * a);
+                    }
+                });
+            }
+        }
+    });
+    let mut c_g2_affine = vec![E::G2::identity().to_affine(); c_g2.len()];
+    E::G2::batch_normalize(&c_g2, &mut c_g2_affine);
     (
         ProvingKey {
             p_g1: p_g1.iter().map(|p| p.lock().unwrap().to_affine()).collect(),
         },
         VerifyingKey {
             a_g2: a_g2.to_affine(),
-            c_g2: c_g2.iter().map(Curve::to_affine).collect(),
+            c_g2: c_g2_affine,
         },
     )
 }
 
-pub fn prove<E>(pk: &ProvingKey<E>, wits: &[E::Fr]) -> Proof<E>
+/// Prove the statement of `pk`, with the witness `wits`.
+///
+/// Fails with [`SynthesisError::LengthMismatch`] if there is not one witness
+/// per point of `pk`, and with [`SynthesisError::UnexpectedIdentity`] if `pk`
+/// has an identity point.
+#[cfg(feature = "prover")]
+pub fn prove<E>(pk: &ProvingKey<E>, wits: &[E::Fr]) -> Result<Proof<E>, SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+{
+    prove_with(Worker::global(), pk, wits)
+}
+
+/// Like [`prove`], but running the multiexp on `worker`.
+#[cfg(feature = "prover")]
+pub fn prove_with<E>(
+    worker: &Worker,
+    pk: &ProvingKey<E>,
+    wits: &[E::Fr],
+) -> Result<Proof<E>, SynthesisError>
 where
     E: Engine,
     E::Fr: PrimeFieldBits,
 {
-    let worker = Worker::new();
     let bases: Arc<Vec<E::G1Affine>> = Arc::new(pk.p_g1.clone());
     let coeffs: Arc<Vec<Exponent<E::Fr>>> =
         Arc::new(wits.iter().map(|w| Exponent::from(w)).collect());
-    assert_eq!(pk.p_g1.len(), coeffs.len());
-    let pi_g1: E::G1 = multiexp(&worker, (bases, 0), FullDensity, coeffs)
-        .wait()
-        .unwrap();
-    Proof {
+    let pi_g1: E::G1 = try_multiexp(worker, (bases, 0), FullDensity, coeffs)?.wait()?;
+    Ok(Proof {
         pi_g1: pi_g1.to_affine(),
-    }
+    })
 }
 
+/// A [`VerifyingKey`] with its G2 points prepared for pairing.
+///
+/// Preparing a key costs about a Miller loop per point of the key, so
+/// when verifying many proofs against one key, build this once with
+/// [`From`] and pass it to every [`verify`] call.
 pub struct PreparedVerifyingKey<E: MultiMillerLoop> {
     c_g2: Vec<E::G2Prepared>,
     neg_a_g2: E::G2Prepared,
@@ -208,30 +545,101 @@ where
     if cmts.is_empty() {
         return true;
     }
-    assert_eq!(cmts.len(), vk.c_g2.len());
-    let mut multi_miller_inputs: Vec<(&E::G1Affine, &E::G2Prepared)> = Vec::new();
+    if cmts.len() != vk.c_g2.len() {
+        return false;
+    }
+    let mut check = PairingCheck::<E>::with_capacity(cmts.len() + 1);
     for (cmt, c) in cmts.iter().zip(&vk.c_g2) {
-        multi_miller_inputs.push((cmt, c));
+        check.add(cmt, c);
     }
-    multi_miller_inputs.push((&pf.pi_g1, &vk.neg_a_g2));
-    let res = E::multi_miller_loop(multi_miller_inputs.as_slice()).final_exponentiation();
-    bool::from(res.is_identity())
+    check.add(&pf.pi_g1, &vk.neg_a_g2);
+    check.finalize()
+}
+
+/// The terms of [`verify`]'s equation with their G1 points multiplied by
+/// `coeff`, for batching it with other equations in one [`PairingCheck`].
+///
+/// Returns `None` if `cmts` and `vk` have different lengths.
+pub(crate) fn scaled_terms<'a, E>(
+    vk: &'a PreparedVerifyingKey<E>,
+    cmts: &[E::G1Affine],
+    pf: &Proof<E>,
+    coeff: E::Fr,
+) -> Option<Vec<(E::G1Affine, &'a E::G2Prepared)>>
+where
+    E: MultiMillerLoop,
+{
+    if cmts.is_empty() {
+        return Some(vec![]);
+    }
+    if cmts.len() != vk.c_g2.len() {
+        return None;
+    }
+    Some(
+        cmts.iter()
+            .chain(std::iter::once(&pf.pi_g1))
+            .map(|p| (*p * coeff).to_affine())
+            .zip(vk.c_g2.iter().chain(std::iter::once(&vk.neg_a_g2)))
+            .collect(),
+    )
+}
+
+/// Verify many statements against one key, spreading the independent
+/// verifications across `worker`'s threads.
+///
+/// Returns whether each `(cmts, pf)` pair verified, in order.
+pub fn verify_parallel<E>(
+    vk: &PreparedVerifyingKey<E>,
+    statements: &[(Vec<E::G1Affine>, Proof<E>)],
+    worker: &Worker,
+) -> Vec<bool>
+where
+    E: MultiMillerLoop,
+{
+    let mut results = vec![false; statements.len()];
+    worker.scope(statements.len(), |scope, chunk| {
+        if chunk > 0 {
+            for (statements, results) in statements.chunks(chunk).zip(results.chunks_mut(chunk)) {
+                scope.spawn(move |_scope| {
+                    for ((cmts, pf), result) in statements.iter().zip(results.iter_mut()) {
+                        *result = verify(vk, cmts, pf);
+                    }
+                });
+            }
+        }
+    });
+    results
+}
+
+/// Commit to `data` with the key `ck`, without a blind.
+///
+/// Fails as [`prove`] does, if `ck` and `data` have different lengths or `ck`
+/// has an identity point.
+#[cfg(feature = "prover")]
+pub fn commit<E>(ck: Arc<Vec<E::G1Affine>>, data: &[E::Fr]) -> Result<E::G1, SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+{
+    commit_with::<E>(Worker::global(), ck, data)
 }
 
-pub fn commit<E>(ck: Arc<Vec<E::G1Affine>>, data: &[E::Fr]) -> E::G1
+/// Like [`commit`], but running the multiexp on `worker`.
+#[cfg(feature = "prover")]
+pub fn commit_with<E>(
+    worker: &Worker,
+    ck: Arc<Vec<E::G1Affine>>,
+    data: &[E::Fr],
+) -> Result<E::G1, SynthesisError>
 where
     E: Engine,
     E::Fr: PrimeFieldBits,
 {
-    assert_eq!(ck.len(), data.len());
-    let worker = Worker::new();
     let bases: Arc<Vec<E::G1Affine>> = ck;
     let coeffs: Arc<Vec<Exponent<E::Fr>>> =
         Arc::new(data.iter().map(|w| Exponent::from(w)).collect());
-    multiexp(&worker, (bases, 0), FullDensity, coeffs)
-        .wait()
-        .unwrap()
+    try_multiexp(worker, (bases, 0), FullDensity, coeffs)?.wait()
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 pub mod test;
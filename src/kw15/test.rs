@@ -1,14 +1,10 @@
 use super::*;
 
 use crate::mirage::tests::DummyEngine;
+use crate::testing::test_rng;
 use bls12_381::Bls12;
 use group::Group;
-use rand_chacha::ChaChaRng;
-use rand_core::{RngCore, SeedableRng};
-
-pub fn test_rng() -> Box<dyn RngCore> {
-    Box::new(ChaChaRng::from_seed([0u8; 32]))
-}
+use rand_core::RngCore;
 
 fn random_statement<E: Engine, R: RngCore>(
     num_cmts: usize,
@@ -22,11 +18,16 @@ fn random_statement<E: Engine, R: RngCore>(
         }
     }
     let wits: Vec<E::Fr> = (0..num_wits).map(|_| E::Fr::random(&mut *rng)).collect();
-    let mut cmts = vec![E::G1::identity(); num_cmts];
+    let cmts = commitments(&matrix, &wits);
+    (matrix, cmts, wits)
+}
+
+fn commitments<E: Engine>(matrix: &Matrix<E>, wits: &[E::Fr]) -> Vec<E::G1Affine> {
+    let mut cmts = vec![E::G1::identity(); matrix.num_cmts];
     for (cmt_i, wit_i, value) in matrix.nonzero_entries.iter() {
         cmts[*cmt_i] += value.clone() * wits[*wit_i];
     }
-    (matrix, cmts.iter().map(Curve::to_affine).collect(), wits)
+    cmts.iter().map(Curve::to_affine).collect()
 }
 
 fn random_test<E>(num_cmts: usize, num_wits: usize)
@@ -37,9 +38,17 @@ where
     let rng = &mut test_rng();
     let (matrix, cmts, wits) = random_statement::<E, _>(num_cmts, num_wits, rng);
     let (pk, vk) = key_gen(&matrix, rng);
-    let pf = prove(&pk, &wits);
+    let pf = prove(&pk, &wits).unwrap();
     let pvk = PreparedVerifyingKey::from(&vk);
     assert!(verify(&pvk, &cmts, &pf));
+
+    // Perturbing one witness after committing must break the proof.
+    if num_cmts > 0 && num_wits > 0 {
+        let mut bad_wits = wits;
+        bad_wits[num_wits - 1] += E::Fr::one();
+        let bad_pf = prove(&pk, &bad_wits).unwrap();
+        assert!(!verify(&pvk, &cmts, &bad_pf));
+    }
 }
 
 #[test]
@@ -62,6 +71,186 @@ fn bls12_381_two_by_ten() {
     random_test::<Bls12>(2, 10);
 }
 
+/// A proof for one witness doesn't verify against another's commitments.
+fn wrong_witness_test<E>()
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut test_rng();
+    let (matrix, cmts, _) = random_statement::<E, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pvk = PreparedVerifyingKey::from(&vk);
+
+    let wrong_wits: Vec<E::Fr> = (0..10).map(|_| E::Fr::random(&mut *rng)).collect();
+    let pf = prove(&pk, &wrong_wits).unwrap();
+    assert!(!verify(&pvk, &cmts, &pf));
+    assert!(verify(&pvk, &commitments(&matrix, &wrong_wits), &pf));
+}
+
+#[test]
+fn dummy_wrong_witness() {
+    wrong_witness_test::<DummyEngine>();
+}
+
+#[test]
+fn bls12_381_wrong_witness() {
+    wrong_witness_test::<Bls12>();
+}
+
+fn key_dependence_test<E>()
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut test_rng();
+    let (matrix, cmts, wits) = random_statement::<E, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let (_, other_vk) = key_gen(&matrix, rng);
+    use group::prime::PrimeCurveAffine;
+    assert!(!bool::from(PrimeCurveAffine::is_identity(&vk.a_g2)));
+    assert!(!bool::from(PrimeCurveAffine::is_identity(&vk.c_g2[0])));
+
+    let pf = prove(&pk, &wits).unwrap();
+    assert!(verify(&PreparedVerifyingKey::from(&vk), &cmts, &pf));
+    assert!(!verify(&PreparedVerifyingKey::from(&other_vk), &cmts, &pf));
+
+    // Swapping in another key's `a` alone breaks the check.
+    let wrong_a = VerifyingKey {
+        c_g2: vk.c_g2.clone(),
+        a_g2: other_vk.a_g2,
+    };
+    assert!(!verify(&PreparedVerifyingKey::from(&wrong_a), &cmts, &pf));
+}
+
+#[test]
+fn dummy_key_dependence() {
+    key_dependence_test::<DummyEngine>();
+}
+
+#[test]
+fn bls12_381_key_dependence() {
+    key_dependence_test::<Bls12>();
+}
+
+fn composed_test<E>()
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut test_rng();
+    let (m1, cmts1, wits1) = random_statement::<E, _>(2, 3, rng);
+    let (m2, cmts2, wits2) = random_statement::<E, _>(1, 4, rng);
+    let check = |m: &Matrix<E>, cmts: &[E::G1Affine], wits: &[E::Fr], rng: &mut _| {
+        let (pk, vk) = key_gen(m, rng);
+        let pf = prove(&pk, wits).unwrap();
+        let pvk = PreparedVerifyingKey::from(&vk);
+        verify(&pvk, cmts, &pf)
+    };
+
+    // Both statements at once.
+    let m = m1.block_diag(&m2);
+    assert_eq!((m.num_cmts, m.num_wits), (3, 7));
+    let cmts = [&cmts1[..], &cmts2[..]].concat();
+    let wits = [&wits1[..], &wits2[..]].concat();
+    assert_eq!(commitments(&m, &wits), cmts);
+    assert!(check(&m, &cmts, &wits, rng));
+
+    // Shared commitments, each to both witness vectors.
+    let (m3, _, _) = random_statement::<E, _>(2, 4, rng);
+    let m = m1.hstack(&m3);
+    assert_eq!((m.num_cmts, m.num_wits), (2, 7));
+    let wits = [&wits1[..], &wits2[..]].concat();
+    let cmts: Vec<E::G1Affine> = cmts1
+        .iter()
+        .zip(commitments(&m3, &wits2))
+        .map(|(c1, c3)| (E::G1::from(*c1) + c3).to_affine())
+        .collect();
+    assert_eq!(commitments(&m, &wits), cmts);
+    assert!(check(&m, &cmts, &wits, rng));
+
+    // Shared witnesses, committed to by both matrices.
+    let (m4, _, _) = random_statement::<E, _>(1, 3, rng);
+    let m = m1.vstack(&m4);
+    assert_eq!((m.num_cmts, m.num_wits), (3, 3));
+    let cmts = [&cmts1[..], &commitments(&m4, &wits1)[..]].concat();
+    assert_eq!(commitments(&m, &wits1), cmts);
+    assert!(check(&m, &cmts, &wits1, rng));
+}
+
+#[test]
+fn dummy_composed() {
+    composed_test::<DummyEngine>();
+}
+
+#[test]
+fn bls12_381_composed() {
+    composed_test::<Bls12>();
+}
+
+#[test]
+#[should_panic]
+fn hstack_mismatched() {
+    let rng = &mut test_rng();
+    let (m1, _, _) = random_statement::<DummyEngine, _>(2, 3, rng);
+    let (m2, _, _) = random_statement::<DummyEngine, _>(1, 3, rng);
+    m1.hstack(&m2);
+}
+
+#[test]
+fn debug_shows_sizes() {
+    let rng = &mut test_rng();
+    let (matrix, _, _) = random_statement::<DummyEngine, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    assert_eq!(format!("{:?}", pk), "ProvingKey { p_g1: 10 points }");
+    assert_eq!(format!("{:?}", vk), "VerifyingKey { c_g2: 2 points, .. }");
+}
+
+fn random_parallel_test<E>(num_statements: usize, num_cmts: usize, num_wits: usize)
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut test_rng();
+    let (matrix, cmts, wits) = random_statement::<E, _>(num_cmts, num_wits, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pvk = PreparedVerifyingKey::from(&vk);
+    // One worker is shared by every proof and the verification.
+    let worker = Worker::new();
+    let mut statements: Vec<(Vec<E::G1Affine>, Proof<E>)> = (0..num_statements)
+        .map(|i| {
+            let mut wits = wits.clone();
+            // Every other proof is for the wrong witness.
+            if i % 2 == 1 {
+                wits[0] += E::Fr::one();
+            }
+            (cmts.clone(), prove_with(&worker, &pk, &wits).unwrap())
+        })
+        .collect();
+    assert!(statements[0].1 == prove(&pk, &wits).unwrap());
+    // A statement with too few commitments fails rather than panicking.
+    statements.push((cmts[1..].to_vec(), prove(&pk, &wits).unwrap()));
+
+    let serial: Vec<bool> = statements
+        .iter()
+        .map(|(cmts, pf)| verify(&pvk, cmts, pf))
+        .collect();
+    let parallel = verify_parallel(&pvk, &statements, &worker);
+    assert_eq!(serial, parallel);
+    assert!(parallel[0]);
+    assert!(!parallel[num_statements]);
+}
+
+#[test]
+fn dummy_parallel() {
+    random_parallel_test::<DummyEngine>(9, 2, 10);
+}
+
+#[test]
+fn bls12_381_parallel() {
+    random_parallel_test::<Bls12>(9, 2, 10);
+}
+
 fn random_serde_test<E>(num_cmts: usize, num_wits: usize)
 where
     E: MultiMillerLoop,
@@ -70,7 +259,7 @@ where
     let rng = &mut test_rng();
     let (matrix, _, wits) = random_statement::<E, _>(num_cmts, num_wits, rng);
     let (pk, vk) = key_gen(&matrix, rng);
-    let pf = prove(&pk, &wits);
+    let pf = prove(&pk, &wits).unwrap();
     let mut ser_pk: Vec<u8> = Vec::new();
     let mut ser_vk: Vec<u8> = Vec::new();
     let mut ser_pf: Vec<u8> = Vec::new();
@@ -83,9 +272,228 @@ where
     assert!(pk == pk2);
     assert!(pf == pf2);
     assert!(vk == vk2);
+
+    // Data written before the header was added, which is the same without
+    // the magic bytes, version and kind, reads with `read_legacy` only.
+    assert!(ProvingKey::<E>::read_legacy(&ser_pk[6..]).unwrap() == pk);
+    assert!(VerifyingKey::<E>::read_legacy(&ser_vk[6..]).unwrap() == vk);
+    assert!(Proof::<E>::read_legacy(&ser_pf[6..]).unwrap() == pf);
+    assert!(ProvingKey::<E>::read(&ser_pk[6..]).is_err());
+
+    // Objects of one kind can't be read as another.
+    let err = VerifyingKey::<E>::read(&ser_pf[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Proof::<E>::read(&ser_pk[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Strict reads reject trailing bytes.
+    assert!(Proof::<E>::read_framed(&ser_pf[..]).unwrap() == pf);
+    ser_pf.push(0);
+    assert!(Proof::<E>::read(&ser_pf[..]).is_ok());
+    let err = Proof::<E>::read_framed(&ser_pf[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(ProvingKey::<E>::read_framed(&ser_pk[..]).unwrap() == pk);
+    assert!(VerifyingKey::<E>::read_framed(&ser_vk[..]).unwrap() == vk);
+    let trailing = |ser: &[u8]| [ser, &[0]].concat();
+    let err = ProvingKey::<E>::read_framed(&trailing(&ser_pk)[..])
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = VerifyingKey::<E>::read_framed(&trailing(&ser_vk)[..])
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // Nor can data from a different version.
+    ser_pf[4] += 1;
+    let err = Proof::<E>::read(&ser_pf[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
 }
 
 #[test]
 fn bls12_381_two_by_ten_serde() {
     random_serde_test::<Bls12>(2, 10);
 }
+
+fn random_compressed_serde_test<E>(num_cmts: usize, num_wits: usize)
+where
+    E: MultiMillerLoop,
+    E::Fr: PrimeFieldBits,
+{
+    let rng = &mut test_rng();
+    let (matrix, _, wits) = random_statement::<E, _>(num_cmts, num_wits, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pf = prove(&pk, &wits).unwrap();
+    let mut ser_pk: Vec<u8> = Vec::new();
+    let mut ser_vk: Vec<u8> = Vec::new();
+    let mut ser_pf: Vec<u8> = Vec::new();
+    pk.write_compressed(&mut ser_pk).unwrap();
+    vk.write_compressed(&mut ser_vk).unwrap();
+    pf.write_compressed(&mut ser_pf).unwrap();
+    let pk2 = ProvingKey::<E>::read_compressed(&ser_pk[..]).unwrap();
+    let vk2 = VerifyingKey::<E>::read_compressed(&ser_vk[..]).unwrap();
+    let pf2 = Proof::<E>::read_compressed(&ser_pf[..]).unwrap();
+    assert!(pk == pk2);
+    assert!(pf == pf2);
+    assert!(vk == vk2);
+
+    // Compressed data is smaller, and can't be read as uncompressed.
+    let mut ser_pk_uncompressed: Vec<u8> = Vec::new();
+    pk.write(&mut ser_pk_uncompressed).unwrap();
+    assert!(ser_pk.len() < ser_pk_uncompressed.len());
+    assert!(ProvingKey::<E>::read(&ser_pk[..]).is_err());
+}
+
+#[test]
+fn bls12_381_two_by_ten_compressed_serde() {
+    random_compressed_serde_test::<Bls12>(2, 10);
+}
+
+#[test]
+fn bls12_381_serde_with_compression() {
+    use crate::curve_io::Compression;
+
+    let rng = &mut test_rng();
+    let (matrix, _, wits) = random_statement::<Bls12, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pf = prove(&pk, &wits).unwrap();
+    for compression in [Compression::Compressed, Compression::Uncompressed] {
+        let mut ser_pk: Vec<u8> = Vec::new();
+        let mut ser_vk: Vec<u8> = Vec::new();
+        let mut ser_pf: Vec<u8> = Vec::new();
+        pk.write_with(&mut ser_pk, compression).unwrap();
+        vk.write_with(&mut ser_vk, compression).unwrap();
+        pf.write_with(&mut ser_pf, compression).unwrap();
+        assert!(pk == ProvingKey::<Bls12>::read_with(&ser_pk[..], compression).unwrap());
+        assert!(vk == VerifyingKey::<Bls12>::read_with(&ser_vk[..], compression).unwrap());
+        assert!(pf == Proof::<Bls12>::read_with(&ser_pf[..], compression).unwrap());
+
+        // The same encoding as the methods for each policy.
+        let mut ser_pf_fixed: Vec<u8> = Vec::new();
+        match compression {
+            Compression::Compressed => pf.write_compressed(&mut ser_pf_fixed),
+            Compression::Uncompressed => pf.write(&mut ser_pf_fixed),
+        }
+        .unwrap();
+        assert_eq!(ser_pf, ser_pf_fixed);
+    }
+}
+
+#[test]
+fn bls12_381_read_checked() {
+    use bls12_381::G1Affine;
+
+    let rng = &mut test_rng();
+    let (matrix, _, wits) = random_statement::<Bls12, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pf = prove(&pk, &wits).unwrap();
+    let mut ser_pk: Vec<u8> = Vec::new();
+    let mut ser_vk: Vec<u8> = Vec::new();
+    let mut ser_pf: Vec<u8> = Vec::new();
+    pk.write(&mut ser_pk).unwrap();
+    vk.write(&mut ser_vk).unwrap();
+    pf.write(&mut ser_pf).unwrap();
+    assert!(pk == ProvingKey::<Bls12>::read_checked(&ser_pk[..]).unwrap());
+    assert!(vk == VerifyingKey::<Bls12>::read_checked(&ser_vk[..]).unwrap());
+    assert!(pf == Proof::<Bls12>::read_checked(&ser_pf[..]).unwrap());
+
+    // (0, 2) is on the curve, but has order 3.
+    let mut small_order = [0u8; 96];
+    small_order[95] = 2;
+    assert!(bool::from(
+        G1Affine::from_uncompressed_unchecked(&small_order)
+            .unwrap()
+            .is_on_curve()
+    ));
+
+    // Swap it in for the proof's point, which follows the header.
+    let header_len = ser_pf.len() - 96;
+    ser_pf[header_len..].copy_from_slice(&small_order);
+    assert!(Proof::<Bls12>::read(&ser_pf[..]).is_ok());
+    let err = Proof::<Bls12>::read_checked(&ser_pf[..]).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // And for the first point of the proving key, which follows the length.
+    let start = header_len + 8;
+    ser_pk[start..start + 96].copy_from_slice(&small_order);
+    assert!(ProvingKey::<Bls12>::read(&ser_pk[..]).is_ok());
+    let err = ProvingKey::<Bls12>::read_checked(&ser_pk[..])
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+/// Truncated and corrupted encodings fail to read, rather than panicking.
+#[test]
+fn bls12_381_read_malformed() {
+    use crate::curve_io::{Compression, DEFAULT_MAX_GROUPS};
+
+    fn check<T>(bytes: &[u8], read: impl Fn(&[u8]) -> io::Result<T>) {
+        assert!(read(bytes).is_ok());
+        for len in 0..bytes.len() {
+            assert!(read(&bytes[..len]).is_err());
+        }
+        for i in (0..bytes.len()).step_by(7) {
+            let mut bad = bytes.to_vec();
+            bad[i] ^= 0xff;
+            let _ = read(&bad);
+        }
+    }
+
+    let rng = &mut test_rng();
+    let (matrix, _, wits) = random_statement::<Bls12, _>(2, 10, rng);
+    let (pk, vk) = key_gen(&matrix, rng);
+    let pf = prove(&pk, &wits).unwrap();
+    for compression in [Compression::Compressed, Compression::Uncompressed] {
+        let mut ser_pk: Vec<u8> = Vec::new();
+        let mut ser_vk: Vec<u8> = Vec::new();
+        let mut ser_pf: Vec<u8> = Vec::new();
+        pk.write_with(&mut ser_pk, compression).unwrap();
+        vk.write_with(&mut ser_vk, compression).unwrap();
+        pf.write_with(&mut ser_pf, compression).unwrap();
+        check(&ser_pk, |b| ProvingKey::<Bls12>::read_with(b, compression));
+        check(&ser_vk, |b| {
+            VerifyingKey::<Bls12>::read_with(b, compression)
+        });
+        check(&ser_pf, |b| Proof::<Bls12>::read_with(b, compression));
+
+        // A length prefix without the points behind it.
+        let mut ser_vk = ser_vk[..6].to_vec();
+        ser_vk.extend_from_slice(&(DEFAULT_MAX_GROUPS as u64).to_be_bytes());
+        let err = VerifyingKey::<Bls12>::read_with(&ser_vk[..], compression)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
+
+#[test]
+fn prove_mismatched() {
+    let rng = &mut test_rng();
+    let (matrix, _, wits) = random_statement::<DummyEngine, _>(2, 10, rng);
+    let (pk, _) = key_gen(&matrix, rng);
+    let worker = Worker::new();
+    assert!(prove_with(&worker, &pk, &wits).unwrap() == prove(&pk, &wits).unwrap());
+    assert!(matches!(
+        prove_with(&worker, &pk, &wits[1..]),
+        Err(SynthesisError::LengthMismatch)
+    ));
+
+    let ck = Arc::new(pk.p_g1.clone());
+    assert_eq!(
+        commit_with::<DummyEngine>(&worker, ck.clone(), &wits).unwrap(),
+        commit::<DummyEngine>(ck.clone(), &wits).unwrap()
+    );
+    assert!(matches!(
+        commit_with::<DummyEngine>(&worker, ck, &[wits.clone(), wits.clone()].concat()),
+        Err(SynthesisError::LengthMismatch)
+    ));
+
+    // An identity point in the key fails the multiexp rather than panicking.
+    let mut pk = pk;
+    pk.p_g1[3] = <DummyEngine as Engine>::G1Affine::identity();
+    assert!(matches!(
+        prove(&pk, &wits),
+        Err(SynthesisError::UnexpectedIdentity)
+    ));
+}
@@ -126,6 +126,16 @@
 //! assert!(groth16::verify_proof(&pvk, &proof, &inputs).is_ok());
 //! ```
 //!
+//! # Verifier-only builds
+//!
+//! The provers are built by the default `prover` feature. A service that only
+//! checks proofs can turn off the default features, keeping `groth16` or
+//! `mirage`. This leaves out the `domain` and `multiexp` modules, the
+//! commitment keys in `commit`, and the key generators and provers of
+//! `groth16`, `mirage`, `kw15` and `commit::cp_link`, along with their
+//! dependencies. Verification, key preparation and [`curve_io`]
+//! (de)serialization of keys and proofs remain available.
+//!
 //! # Roadmap
 //!
 //! `bellman` is being refactored into a generic proving library. Currently it
@@ -136,11 +146,14 @@
 
 // Catch documentation errors caused by code changes.
 #![deny(rustdoc::broken_intra_doc_links)]
+// Module docs still link to the items that are only built with `prover`.
+#![cfg_attr(not(feature = "prover"), allow(rustdoc::broken_intra_doc_links))]
 
 pub mod cc;
 #[cfg(feature = "mirage")]
 pub mod commit;
-mod curve_io;
+pub mod curve_io;
+#[cfg(feature = "prover")]
 pub mod domain;
 pub mod gadgets;
 #[cfg(feature = "groth16")]
@@ -150,8 +163,15 @@ pub mod kw15;
 #[cfg(feature = "mirage")]
 pub mod mirage;
 pub mod multicore;
+#[cfg(feature = "prover")]
 pub mod multiexp;
-mod trace;
+#[cfg(feature = "pairing")]
+pub mod pairing_check;
+#[cfg(feature = "r1cs")]
+pub mod r1cs;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+pub mod trace;
 
 use ff::PrimeField;
 
@@ -171,7 +191,7 @@ pub trait Circuit<Scalar: PrimeField> {
 }
 
 /// Represents a variable in our constraint system.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Variable(Index);
 
 impl Variable {
@@ -190,7 +210,7 @@ impl Variable {
 
 /// Represents the index of either an input variable or
 /// auxiliary variable.
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Index {
     Input(usize),
     Aux(usize),
@@ -221,6 +241,53 @@ impl<Scalar: PrimeField> LinearCombination<Scalar> {
     pub fn zero() -> LinearCombination<Scalar> {
         LinearCombination(vec![])
     }
+
+    /// The terms of the combination, in the order they were added. A variable
+    /// has as many terms as it was added with; see
+    /// [`LinearCombination::coeff_of`] for its total coefficient.
+    pub fn iter(&self) -> impl Iterator<Item = (Variable, Scalar)> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// The number of terms, which may be more than the number of variables.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The sum of the coefficients of `var`'s terms, zero if it has none.
+    pub fn coeff_of(&self, var: Variable) -> Scalar {
+        self.iter()
+            .filter(|(v, _)| *v == var)
+            .fold(Scalar::zero(), |acc, (_, coeff)| acc + coeff)
+    }
+
+    /// Merge the terms of each variable into one, and drop the terms whose
+    /// coefficients are zero, including those that cancel out. The
+    /// combination has the same value, but the terms are sorted by variable,
+    /// inputs first.
+    pub fn simplify(&mut self) {
+        fn key(var: &Variable) -> (bool, usize) {
+            match var.0 {
+                Index::Input(i) => (false, i),
+                Index::Aux(i) => (true, i),
+            }
+        }
+
+        self.0.sort_by_key(|(var, _)| key(var));
+        let mut merged: Vec<(Variable, Scalar)> = Vec::with_capacity(self.0.len());
+        for (var, coeff) in self.0.drain(..) {
+            match merged.last_mut() {
+                Some((last, acc)) if *last == var => *acc += coeff,
+                _ => merged.push((var, coeff)),
+            }
+        }
+        merged.retain(|(_, coeff)| !coeff.is_zero_vartime());
+        self.0 = merged;
+    }
 }
 
 impl<Scalar: PrimeField> Add<(Scalar, Variable)> for LinearCombination<Scalar> {
@@ -323,6 +390,7 @@ impl<'a, Scalar: PrimeField> Sub<(Scalar, &'a LinearCombination<Scalar>)>
 /// This is an error that could occur during circuit synthesis contexts,
 /// such as CRS generation or proving.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum SynthesisError {
     /// During synthesis, we lacked knowledge of a variable assignment.
     AssignmentMissing,
@@ -338,6 +406,20 @@ pub enum SynthesisError {
     IoError(io::Error),
     /// During CRS generation, we observed an unconstrained auxiliary variable
     UnconstrainedVariable,
+    /// During synthesis, the circuit ended a different number of aux blocks
+    /// than it declared
+    AuxBlockCountMismatch,
+    /// During CRS generation, the number of deltas was not one more than the
+    /// number of aux blocks that the circuit declared
+    DeltaCountMismatch,
+    /// During proof generation, a vector's length did not match its key
+    LengthMismatch,
+    /// During proof generation, we built an evaluation domain from no
+    /// coefficients
+    EmptyDomain,
+    /// During proof generation, the circuit absorbed a different message into
+    /// the transcript than the log it resumed from
+    TranscriptMismatch,
 }
 
 impl From<io::Error> for SynthesisError {
@@ -360,6 +442,17 @@ impl fmt::Display for SynthesisError {
             SynthesisError::UnexpectedIdentity => "encountered an identity element in the CRS",
             SynthesisError::IoError(_) => "encountered an I/O error",
             SynthesisError::UnconstrainedVariable => "auxiliary variable was unconstrained",
+            SynthesisError::AuxBlockCountMismatch => {
+                "circuit ended a different number of aux blocks than it declared"
+            }
+            SynthesisError::DeltaCountMismatch => {
+                "expected one delta per aux block, and one more for the rest of the witness"
+            }
+            SynthesisError::LengthMismatch => "vector length does not match the key",
+            SynthesisError::EmptyDomain => "evaluation domain has no coefficients",
+            SynthesisError::TranscriptMismatch => {
+                "circuit diverged from the transcript log it resumed from"
+            }
         };
         if let SynthesisError::IoError(ref e) = *self {
             write!(f, "I/O error: ")?;
@@ -372,11 +465,14 @@ impl fmt::Display for SynthesisError {
 
 /// An error during verification.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum VerificationError {
     /// Verification was attempted with a malformed verifying key.
     InvalidVerifyingKey,
     /// Proof verification failed.
     InvalidProof,
+    /// The proof contains the identity where a non-identity point is required.
+    UnexpectedIdentity,
 }
 
 impl Error for VerificationError {}
@@ -386,6 +482,7 @@ impl fmt::Display for VerificationError {
         let msg = match *self {
             VerificationError::InvalidVerifyingKey => "malformed verifying key",
             VerificationError::InvalidProof => "proof verification failed",
+            VerificationError::UnexpectedIdentity => "proof contains an identity element",
         };
         write!(f, "{}", msg)
     }
@@ -599,6 +696,89 @@ mod test {
         assert!(!err.to_string().is_empty());
     }
 
+    #[cfg(feature = "pairing")]
+    #[test]
+    fn simplify_lc() {
+        use bls12_381::Scalar as Fr;
+
+        let eval = |lc: &LinearCombination<Fr>| {
+            lc.as_ref()
+                .iter()
+                .map(|(var, coeff)| {
+                    let value = match var.get_unchecked() {
+                        Index::Input(i) => Fr::from(i as u64 + 2),
+                        Index::Aux(i) => Fr::from(i as u64 + 100),
+                    };
+                    value * coeff
+                })
+                .fold(Fr::zero(), |acc, term| acc + term)
+        };
+
+        let one = Variable::new_unchecked(Index::Input(0));
+        let x = Variable::new_unchecked(Index::Aux(0));
+        let y = Variable::new_unchecked(Index::Aux(1));
+        let z = Variable::new_unchecked(Index::Input(1));
+        let mut lc = LinearCombination::zero() + y + (Fr::from(3), x) - one
+            + (Fr::zero(), z)
+            + (Fr::from(2), one)
+            - x
+            + y
+            - (Fr::from(2), x);
+        let before = eval(&lc);
+        lc.simplify();
+        assert_eq!(eval(&lc), before);
+        // x cancels out, and z has a zero coefficient.
+        let terms: Vec<_> = lc
+            .as_ref()
+            .iter()
+            .map(|(var, coeff)| (var.get_unchecked(), *coeff))
+            .collect();
+        assert_eq!(
+            terms,
+            vec![(Index::Input(0), Fr::one()), (Index::Aux(1), Fr::from(2))]
+        );
+
+        let mut zero = LinearCombination::<Fr>::zero() + x - x;
+        zero.simplify();
+        assert!(zero.as_ref().is_empty());
+    }
+
+    #[cfg(feature = "pairing")]
+    #[test]
+    fn lc_accessors() {
+        use bls12_381::Scalar as Fr;
+
+        let one = Variable::new_unchecked(Index::Input(0));
+        let x = Variable::new_unchecked(Index::Aux(0));
+        let y = Variable::new_unchecked(Index::Aux(1));
+
+        let lc = LinearCombination::<Fr>::zero();
+        assert!(lc.is_empty());
+        assert_eq!(lc.len(), 0);
+        assert_eq!(lc.coeff_of(x), Fr::zero());
+
+        let lc = lc + (Fr::from(3), x) - one + x + (Fr::from(5), y);
+        assert!(!lc.is_empty());
+        assert_eq!(lc.len(), 4);
+        assert_eq!(
+            lc.iter().collect::<Vec<_>>(),
+            vec![
+                (x, Fr::from(3)),
+                (one, -Fr::one()),
+                (x, Fr::one()),
+                (y, Fr::from(5))
+            ]
+        );
+        assert_eq!(lc.coeff_of(x), Fr::from(4));
+        assert_eq!(lc.coeff_of(one), -Fr::one());
+        assert_eq!(lc.coeff_of(y), Fr::from(5));
+        // Input 1 and aux 1 are different variables.
+        assert_eq!(
+            lc.coeff_of(Variable::new_unchecked(Index::Input(1))),
+            Fr::zero()
+        );
+    }
+
     #[test]
     fn synthesis_error_string() {
         let err = SynthesisError::PolynomialDegreeTooLarge;
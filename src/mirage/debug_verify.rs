@@ -0,0 +1,333 @@
+//! Verification with diagnostics, for debugging proofs that fail to verify.
+
+use std::fmt;
+
+use pairing::{MillerLoopResult, MultiMillerLoop};
+
+use super::verifier::accumulate_inputs;
+use super::{PreparedVerifyingKey, Proof, ProofContext, TranscriptEntry};
+use crate::VerificationError;
+
+/// The pairings of the terms of the verification equation
+/// `e(A, B) * e(inputs, -gamma) * e(C, -delta) * prod_i e(D_i, -delta_i) = target`,
+/// where `target` is `e(alpha, beta)`.
+#[derive(Clone, Debug)]
+pub struct PairingTerms<E: MultiMillerLoop> {
+    pub a_b: E::Gt,
+    /// The term of the public input accumulator, which also covers the random
+    /// coins.
+    pub inputs: E::Gt,
+    pub c: E::Gt,
+    /// One term per aux block commitment.
+    pub ds: Vec<E::Gt>,
+    pub target: E::Gt,
+}
+
+impl<E: MultiMillerLoop> PairingTerms<E> {
+    /// The product of the terms, which equals `target` for a valid proof.
+    pub fn lhs(&self) -> E::Gt {
+        self.ds
+            .iter()
+            .fold(self.a_b + self.inputs + self.c, |acc, d| acc + d)
+    }
+}
+
+/// Why [`verify_proof_verbose`] rejected a proof.
+#[derive(Clone, Debug)]
+pub enum VerifyFailure<E: MultiMillerLoop> {
+    /// [`Proof::validate`] rejected the proof's points.
+    Malformed(VerificationError),
+    /// The key's transcript has a different number of public inputs.
+    PublicInputCount { expected: usize, actual: usize },
+    /// The key's transcript has a different number of aux block commitments.
+    AuxCommitCount { expected: usize, actual: usize },
+    /// The key's `ic` does not have one point per random coin and public
+    /// input, plus one.
+    IcLength { expected: usize, actual: usize },
+    /// The key does not have one delta per aux block commitment, plus one.
+    DeltaCount { expected: usize, actual: usize },
+    /// The counts line up, but the pairing equation does not hold.
+    PairingMismatch(PairingTerms<E>),
+}
+
+impl<E: MultiMillerLoop> fmt::Display for VerifyFailure<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFailure::Malformed(e) => write!(f, "malformed proof: {}", e),
+            VerifyFailure::PublicInputCount { expected, actual } => write!(
+                f,
+                "expected {} public inputs, but there are {}",
+                expected, actual
+            ),
+            VerifyFailure::AuxCommitCount { expected, actual } => write!(
+                f,
+                "expected {} aux block commitments, but there are {}",
+                expected, actual
+            ),
+            VerifyFailure::IcLength { expected, actual } => write!(
+                f,
+                "expected {} ic points in the key, but there are {}",
+                expected, actual
+            ),
+            VerifyFailure::DeltaCount { expected, actual } => write!(
+                f,
+                "expected {} deltas in the key, but there are {}",
+                expected, actual
+            ),
+            VerifyFailure::PairingMismatch(_) => write!(f, "pairing equation does not hold"),
+        }
+    }
+}
+
+/// Like [`verify_proof`](super::verify_proof), but reporting why a proof was
+/// rejected.
+///
+/// The counts of public inputs and aux block commitments are checked against
+/// the key before anything else, so this does not panic where
+/// [`verify_proof`](super::verify_proof) would. When the pairing equation does
+/// not hold, each of its terms is paired separately, at the cost of one final
+/// exponentiation per term, so that they can be compared with those of a
+/// known good proof: a wrong public input changes only
+/// [`PairingTerms::inputs`], while a wrong `C` changes only [`PairingTerms::c`].
+pub fn verify_proof_verbose<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+) -> Result<(), VerifyFailure<E>> {
+    verify_proof_verbose_with_context(pvk, proof, public_inputs, &ProofContext::default())
+}
+
+/// Like [`verify_proof_verbose`], for proofs whose random coins were drawn
+/// from a transcript separated by `ctx`, as with
+/// [`verify_proof_with_context`](super::verify_proof_with_context).
+pub fn verify_proof_verbose_with_context<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+    ctx: &ProofContext,
+) -> Result<(), VerifyFailure<E>> {
+    proof.validate().map_err(VerifyFailure::Malformed)?;
+
+    let count = |entry: TranscriptEntry| pvk.transcript.iter().filter(|t| **t == entry).count();
+    let (coins, inputs, aux_commits) = (
+        count(TranscriptEntry::Coin),
+        count(TranscriptEntry::PublicInput),
+        count(TranscriptEntry::AuxCommit),
+    );
+    if public_inputs.len() != inputs {
+        return Err(VerifyFailure::PublicInputCount {
+            expected: inputs,
+            actual: public_inputs.len(),
+        });
+    }
+    if proof.ds.len() != aux_commits {
+        return Err(VerifyFailure::AuxCommitCount {
+            expected: aux_commits,
+            actual: proof.ds.len(),
+        });
+    }
+    if pvk.ic.len() != 1 + coins + inputs {
+        return Err(VerifyFailure::IcLength {
+            expected: 1 + coins + inputs,
+            actual: pvk.ic.len(),
+        });
+    }
+    if pvk.neg_deltas_g2.len() != aux_commits + 1 {
+        return Err(VerifyFailure::DeltaCount {
+            expected: aux_commits + 1,
+            actual: pvk.neg_deltas_g2.len(),
+        });
+    }
+
+    let acc =
+        accumulate_inputs(pvk, &proof.ds, public_inputs, ctx).expect("the counts were checked");
+    let terms = pairing_terms(pvk, proof, &acc);
+    if terms.lhs() == terms.target {
+        Ok(())
+    } else {
+        Err(VerifyFailure::PairingMismatch(terms))
+    }
+}
+
+fn pairing_terms<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    acc: &E::G1Affine,
+) -> PairingTerms<E> {
+    let pair = |g1: &E::G1Affine, g2: &E::G2Prepared| {
+        E::multi_miller_loop(&[(g1, g2)]).final_exponentiation()
+    };
+    let last = pvk.neg_deltas_g2.len() - 1;
+    PairingTerms {
+        a_b: pair(&proof.a, &proof.b.into()),
+        inputs: pair(acc, &pvk.neg_gamma_g2),
+        c: pair(&proof.c, &pvk.neg_deltas_g2[last]),
+        ds: proof
+            .ds
+            .iter()
+            .zip(&pvk.neg_deltas_g2)
+            .map(|(d, neg_delta)| pair(d, neg_delta))
+            .collect(),
+        target: pvk.alpha_g1_beta_g2,
+    }
+}
+
+#[cfg(all(test, feature = "prover"))]
+mod test {
+    use super::*;
+    use crate::cc::{CcCircuit, CcConstraintSystem};
+    use crate::gadgets::num::AllocatedNum;
+    use crate::gadgets::Assignment;
+    use crate::mirage::{
+        create_proof, create_proof_with_context, generate_random_parameters, prepare_verifying_key,
+    };
+    use crate::SynthesisError;
+    use bls12_381::{Bls12, Scalar};
+    use group::{prime::PrimeCurveAffine, Curve};
+
+    // Commits to x in a block, and exposes x^2.
+    struct Square {
+        x: Option<Scalar>,
+    }
+
+    impl CcCircuit<Scalar> for Square {
+        fn synthesize<CS: CcConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = x.square(cs.namespace(|| "x^2"))?;
+            y.inputize(cs.namespace(|| "input y"))
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn pinpoints_failures() {
+        let mut rng = crate::testing::test_rng();
+        let params =
+            generate_random_parameters::<Bls12, _, _>(Square { x: None }, &mut rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        let (proof, _) = create_proof(
+            Square {
+                x: Some(Scalar::from(3)),
+            },
+            &params,
+            Scalar::from(5),
+            Scalar::from(7),
+            vec![Scalar::from(11)],
+        )
+        .unwrap();
+        let y = Scalar::from(9);
+        assert!(verify_proof_verbose(&pvk, &proof, &[y]).is_ok());
+        let acc = accumulate_inputs(&pvk, &proof.ds, &[y], &ProofContext::default()).unwrap();
+        let good = pairing_terms(&pvk, &proof, &acc);
+
+        assert!(matches!(
+            verify_proof_verbose(&pvk, &proof, &[]),
+            Err(VerifyFailure::PublicInputCount {
+                expected: 1,
+                actual: 0
+            })
+        ));
+        let mut no_ds = proof.clone();
+        no_ds.ds.clear();
+        assert!(matches!(
+            verify_proof_verbose(&pvk, &no_ds, &[y]),
+            Err(VerifyFailure::AuxCommitCount {
+                expected: 1,
+                actual: 0
+            })
+        ));
+
+        // A wrong public input only changes the inputs term.
+        match verify_proof_verbose(&pvk, &proof, &[Scalar::from(10)]) {
+            Err(VerifyFailure::PairingMismatch(terms)) => {
+                assert_ne!(terms.inputs, good.inputs);
+                assert_eq!(terms.c, good.c);
+                assert_eq!(terms.a_b, good.a_b);
+                assert_eq!(terms.ds, good.ds);
+            }
+            _ => panic!("expected a pairing mismatch"),
+        }
+
+        // A wrong C only changes the C term.
+        let mut bad_c = proof.clone();
+        bad_c.c = (bad_c.c.to_curve() + <Bls12 as pairing::Engine>::G1::generator()).to_affine();
+        match verify_proof_verbose(&pvk, &bad_c, &[y]) {
+            Err(VerifyFailure::PairingMismatch(terms)) => {
+                assert_eq!(terms.inputs, good.inputs);
+                assert_ne!(terms.c, good.c);
+                assert_eq!(terms.a_b, good.a_b);
+                assert_ne!(terms.lhs(), terms.target);
+            }
+            _ => panic!("expected a pairing mismatch"),
+        }
+    }
+
+    // Commits to x in a block, then draws a coin r and multiplies it by x.
+    struct CoinAfterBlock {
+        x: Option<Scalar>,
+    }
+
+    impl CcCircuit<Scalar> for CoinAfterBlock {
+        fn synthesize<CS: CcConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let (r, val_r) = cs.alloc_random(|| "r")?;
+            let r = AllocatedNum {
+                variable: r,
+                value: val_r,
+            };
+            r.mul(cs.namespace(|| "r*x"), &x)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn uses_the_context() {
+        let mut rng = crate::testing::test_rng();
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CoinAfterBlock { x: None }, &mut rng)
+                .unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        let ctx = ProofContext::default().with_data(b"session");
+        let (proof, _) = create_proof_with_context(
+            CoinAfterBlock {
+                x: Some(Scalar::from(3)),
+            },
+            &params,
+            Scalar::from(5),
+            Scalar::from(7),
+            vec![Scalar::from(11)],
+            &ctx,
+        )
+        .unwrap();
+        assert!(verify_proof_verbose_with_context(&pvk, &proof, &[], &ctx).is_ok());
+
+        // Under another context the coin differs, which only changes the
+        // inputs term.
+        let acc = accumulate_inputs(&pvk, &proof.ds, &[], &ctx).unwrap();
+        let good = pairing_terms(&pvk, &proof, &acc);
+        match verify_proof_verbose(&pvk, &proof, &[]) {
+            Err(VerifyFailure::PairingMismatch(terms)) => {
+                assert_ne!(terms.inputs, good.inputs);
+                assert_eq!(terms.c, good.c);
+                assert_eq!(terms.a_b, good.a_b);
+                assert_eq!(terms.ds, good.ds);
+            }
+            _ => panic!("expected a pairing mismatch"),
+        }
+    }
+}
@@ -175,6 +175,10 @@ impl<Scalar: PrimeField> CcConstraintSystem<Scalar> for KeypairAssembly<Scalar>
         Ok(())
     }
 
+    fn current_aux_block(&self) -> usize {
+        self.aux_block_indices.len()
+    }
+
     fn alloc_random<A, AR>(
         &mut self,
         annotation: A,
@@ -191,6 +195,13 @@ impl<Scalar: PrimeField> CcConstraintSystem<Scalar> for KeypairAssembly<Scalar>
 }
 
 /// Create parameters for a circuit, given some toxic waste.
+///
+/// The circuit is synthesized exactly once, into a [`KeypairAssembly`] that
+/// records the sizes and the QAP structure together, so there is no separate
+/// counting pass. `C` is taken by value and need not be `Clone`.
+///
+/// `deltas` holds one delta per aux block, then one for the rest of the
+/// witness. Fails with [`SynthesisError::DeltaCountMismatch`] otherwise.
 #[allow(clippy::too_many_arguments)]
 pub fn generate_parameters<E, C>(
     circuit: C,
@@ -208,7 +219,9 @@ where
     E::G2: WnafGroup,
     C: CcCircuit<E::Fr>,
 {
-    assert_eq!(deltas.len(), circuit.num_aux_blocks() + 1);
+    if deltas.len() != circuit.num_aux_blocks() + 1 {
+        return Err(SynthesisError::DeltaCountMismatch);
+    }
     let mut assembly = KeypairAssembly {
         num_inputs: 0,
         num_aux: 0,
@@ -230,7 +243,9 @@ where
     let claimed_num_aux_blocks = circuit.num_aux_blocks();
     // Synthesize the circuit.
     circuit.synthesize(&mut assembly)?;
-    assert_eq!(claimed_num_aux_blocks, assembly.aux_block_indices.len());
+    if claimed_num_aux_blocks != assembly.aux_block_indices.len() {
+        return Err(SynthesisError::AuxBlockCountMismatch);
+    }
 
     // Input constraints to ensure full density of IC query
     // x * 0 = 0
@@ -384,6 +399,11 @@ where
         assert_eq!(a.len(), b_g2.len());
         assert_eq!(a.len(), ext.len());
 
+        // An empty block has nothing to evaluate, and can't be split into chunks.
+        if a.is_empty() {
+            return;
+        }
+
         // Evaluate polynomials in multiple threads
         worker.scope(a.len(), |scope, chunk| {
             for ((((((a, b_g1), b_g2), ext), at), bt), ct) in a
@@ -0,0 +1,318 @@
+//! Proving from a memory-mapped key file, for keys too large to read into
+//! memory at once.
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+use memmap2::Mmap;
+use pairing::Engine;
+
+use super::{ParameterSource, VerifyingKey, KIND_PARAMETERS, MAX_AUX_BLOCKS, MAX_QUERY_LEN};
+use crate::curve_io::GroupReader;
+use crate::multiexp::{Source, SourceBuilder};
+use crate::SynthesisError;
+
+/// [`Parameters`](super::Parameters) in a file written by
+/// [`Parameters::write`](super::Parameters::write), mapped into memory.
+///
+/// Opening the file only reads the verifying key and the lengths of the
+/// queries. The prover reads each query through a [`MappedQuery`], which
+/// decodes one point at a time from the mapping, so only the pages it touches
+/// are read and no query is ever held in memory decoded.
+pub struct MappedParameters<E: Engine> {
+    map: Arc<Mmap>,
+    checked: bool,
+    vk: VerifyingKey<E>,
+    // Byte ranges of the queries in `map`.
+    h: Range<usize>,
+    ls: Vec<Range<usize>>,
+    a: Range<usize>,
+    b_g1: Range<usize>,
+    b_g2: Range<usize>,
+}
+
+impl<E: Engine> MappedParameters<E> {
+    /// Map the parameters in the file at `path`. If `checked`, points are
+    /// checked to be on the curve and in the subgroup as they are decoded,
+    /// as with [`Parameters::read`](super::Parameters::read).
+    ///
+    /// The file must not be modified while it is mapped, and must hold
+    /// nothing after the parameters.
+    pub fn open<P: AsRef<Path>>(path: P, checked: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: the mapping is only read from, and modifying the file while
+        // it is mapped is unsupported, as documented above.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let g1_len = <E::G1Affine as UncompressedEncoding>::Uncompressed::default()
+            .as_ref()
+            .len();
+        let g2_len = <E::G2Affine as UncompressedEncoding>::Uncompressed::default()
+            .as_ref()
+            .len();
+
+        let (vk, h, ls, a, b_g1, b_g2) = {
+            let mut reader = &map[..];
+            reader.read_header(KIND_PARAMETERS)?;
+            let vk = VerifyingKey::<E>::read_body(&mut reader)?;
+
+            // Skips over a length-prefixed query, returning its byte range.
+            let total = map.len();
+            let query = |reader: &mut &[u8], point_len: usize| -> io::Result<Range<usize>> {
+                let len = reader.read_groups_len(MAX_QUERY_LEN)?;
+                let start = total - reader.len();
+                let bytes = len
+                    .checked_mul(point_len)
+                    .filter(|bytes| *bytes <= reader.len())
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "truncated query")
+                    })?;
+                *reader = &reader[bytes..];
+                Ok(start..start + bytes)
+            };
+
+            let h = query(&mut reader, g1_len)?;
+            let ls_len = reader.read_groups_len(MAX_AUX_BLOCKS + 1)?;
+            let ls = (0..ls_len)
+                .map(|_| query(&mut reader, g1_len))
+                .collect::<io::Result<Vec<_>>>()?;
+            let a = query(&mut reader, g1_len)?;
+            let b_g1 = query(&mut reader, g1_len)?;
+            let b_g2 = query(&mut reader, g2_len)?;
+            if !reader.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "trailing bytes after parameters",
+                ));
+            }
+            (vk, h, ls, a, b_g1, b_g2)
+        };
+
+        Ok(MappedParameters {
+            map: Arc::new(map),
+            checked,
+            vk,
+            h,
+            ls,
+            a,
+            b_g1,
+            b_g2,
+        })
+    }
+
+    pub fn vk(&self) -> &VerifyingKey<E> {
+        &self.vk
+    }
+
+    fn query<G: UncompressedEncoding + PrimeCurveAffine>(
+        &self,
+        range: &Range<usize>,
+        skip: usize,
+    ) -> MappedQuery<G> {
+        MappedQuery {
+            map: self.map.clone(),
+            bytes: range.clone(),
+            checked: self.checked,
+            next: skip,
+            point: G::identity(),
+        }
+    }
+}
+
+/// A query in [`MappedParameters`], read by the prover's multiexps straight
+/// from the mapping.
+///
+/// Points are decoded as they are read, and bases the multiexp skips are
+/// never decoded at all.
+#[derive(Clone)]
+pub struct MappedQuery<G> {
+    map: Arc<Mmap>,
+    // Byte range of the query in `map`.
+    bytes: Range<usize>,
+    checked: bool,
+    // Index of the next point to read.
+    next: usize,
+    // The point last read.
+    point: G,
+}
+
+impl<G: UncompressedEncoding + PrimeCurveAffine> MappedQuery<G> {
+    fn point_len() -> usize {
+        G::Uncompressed::default().as_ref().len()
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len() / Self::point_len()
+    }
+}
+
+impl<G: UncompressedEncoding + PrimeCurveAffine> SourceBuilder<G> for MappedQuery<G> {
+    type Source = Self;
+
+    fn build(self) -> Self {
+        self
+    }
+}
+
+impl<G: UncompressedEncoding + PrimeCurveAffine> Source<G> for MappedQuery<G> {
+    fn next(&mut self) -> Result<&G, SynthesisError> {
+        if self.len() <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        let start = self.bytes.start + self.next * Self::point_len();
+        let mut reader = &self.map[start..start + Self::point_len()];
+        self.point = reader.read_group_uncompressed(self.checked, true)?;
+        if self.point.is_identity().into() {
+            return Err(SynthesisError::UnexpectedIdentity);
+        }
+        self.next += 1;
+
+        Ok(&self.point)
+    }
+
+    fn skip(&mut self, amt: usize) -> Result<(), SynthesisError> {
+        if self.len() <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        self.next += amt;
+
+        Ok(())
+    }
+}
+
+impl<E: Engine> ParameterSource<E> for &MappedParameters<E> {
+    type G1Builder = MappedQuery<E::G1Affine>;
+    type G2Builder = MappedQuery<E::G2Affine>;
+
+    fn get_vk(&mut self, _: usize) -> Result<VerifyingKey<E>, SynthesisError> {
+        Ok(self.vk.clone())
+    }
+
+    fn get_h(&mut self, _: usize) -> Result<Self::G1Builder, SynthesisError> {
+        Ok(self.query(&self.h, 0))
+    }
+
+    fn get_l(&mut self, _: usize, l_idx: usize) -> Result<Self::G1Builder, SynthesisError> {
+        // The circuit ended more aux blocks than the parameters were made for.
+        let l = self
+            .ls
+            .get(l_idx)
+            .ok_or(SynthesisError::AuxBlockCountMismatch)?;
+        Ok(self.query(l, 0))
+    }
+
+    fn get_a(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        Ok((self.query(&self.a, 0), self.query(&self.a, num_inputs)))
+    }
+
+    fn get_b_g1(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        Ok((
+            self.query(&self.b_g1, 0),
+            self.query(&self.b_g1, num_inputs),
+        ))
+    }
+
+    fn get_b_g2(
+        &mut self,
+        num_inputs: usize,
+        _: usize,
+    ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        Ok((
+            self.query(&self.b_g2, 0),
+            self.query(&self.b_g2, num_inputs),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::{CcCircuit, CcConstraintSystem};
+    use crate::gadgets::num::AllocatedNum;
+    use crate::gadgets::Assignment;
+    use crate::mirage::{create_proof, generate_random_parameters};
+    use bls12_381::{Bls12, Scalar};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    // Commits to x in a block, and exposes x^2.
+    struct Square {
+        x: Option<Scalar>,
+    }
+
+    impl CcCircuit<Scalar> for Square {
+        fn synthesize<CS: CcConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = x.square(cs.namespace(|| "x^2"))?;
+            y.inputize(cs.namespace(|| "input y"))
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn same_proof_as_in_memory() {
+        let mut rng = crate::testing::test_rng();
+        let params =
+            generate_random_parameters::<Bls12, _, _>(Square { x: None }, &mut rng).unwrap();
+
+        let mut v = vec![];
+        params.write(&mut v).unwrap();
+        let map = |bytes: &[u8]| {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(bytes).unwrap();
+            MappedParameters::<Bls12>::open(file.path(), true)
+        };
+        let mapped = map(&v).unwrap();
+        assert!(mapped.vk() == &params.vk);
+
+        let (r, s, kappa) = (Scalar::from(3), Scalar::from(5), Scalar::from(7));
+        let x = || Square {
+            x: Some(Scalar::from(9)),
+        };
+        let (in_memory, _) = create_proof(x(), &params, r, s, vec![kappa]).unwrap();
+        let (from_map, _) = create_proof(x(), &mapped, r, s, vec![kappa]).unwrap();
+        assert!(in_memory == from_map);
+
+        // There is no l query past the last aux block.
+        assert!(matches!(
+            (&mapped).get_l(0, mapped.ls.len()),
+            Err(SynthesisError::AuxBlockCountMismatch)
+        ));
+
+        // Truncated files, and files with anything after the parameters, are
+        // rejected when they are opened.
+        assert!(map(&v[..v.len() - 1]).is_err());
+        v.push(0);
+        let err = map(&v).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -7,31 +7,125 @@
 //! Notation:
 //! * new proof elements: pi_d(s)
 
-use group::{prime::PrimeCurveAffine, GroupEncoding, UncompressedEncoding};
+use group::{prime::PrimeCurveAffine, Curve, Group, GroupEncoding, UncompressedEncoding};
 use merlin::Transcript;
 use pairing::{Engine, MultiMillerLoop};
 use rand_chacha::ChaChaRng;
 use rand_core::{RngCore, SeedableRng};
+use subtle::Choice;
 
+#[cfg(feature = "prover")]
 use crate::commit::CommitKey;
+use crate::curve_io::{initial_capacity, read_framed, GroupReader, GroupWriter};
+#[cfg(feature = "prover")]
 use crate::SynthesisError;
+use crate::VerificationError;
 
+#[cfg(feature = "prover")]
+use crate::multicore::Worker;
+#[cfg(feature = "prover")]
 use crate::multiexp::SourceBuilder;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "prover")]
+use ff::Field;
+use std::fmt;
 use std::io::{self, Read, Write};
+#[cfg(feature = "prover")]
 use std::sync::Arc;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 pub mod tests;
 
+/// Header kinds of mirage objects, distinct from the [`crate::kw15`] kinds.
+const KIND_PREPARED_VERIFYING_KEY_COMPACT: u8 = 6;
+const KIND_VERIFYING_KEY: u8 = 7;
+#[cfg(feature = "prover")]
+const KIND_PARAMETERS: u8 = 8;
+
+// The longest vectors the readers accept, so that a corrupt or malicious
+// length fails up front rather than after reading as much as it claims.
+/// The most aux blocks in a proof, key or parameters read from bytes. There
+/// is one more delta and L query than aux blocks.
+const MAX_AUX_BLOCKS: usize = 1 << 16;
+/// The most public inputs, counting the constant one and the random coins, in
+/// a verifying key read from bytes.
+const MAX_INPUTS: usize = 1 << 24;
+/// The most points in a query of parameters read from bytes.
+#[cfg(feature = "prover")]
+const MAX_QUERY_LEN: usize = crate::curve_io::DEFAULT_MAX_GROUPS;
+
+/// Read a `u32` length, rejecting lengths above `max_len` with
+/// [`io::ErrorKind::InvalidData`].
+fn read_len<R: Read>(reader: &mut R, max_len: usize) -> io::Result<usize> {
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "length too large",
+        ));
+    }
+    Ok(len)
+}
+
+/// Check that a key read from bytes has the shape its transcript gives it: a
+/// delta for each aux block and Groth's, and an `ic` point for the constant
+/// one and each public input and random coin. The verifier relies on both,
+/// so a key without them fails with [`io::ErrorKind::InvalidData`].
+fn check_key_shape(
+    num_deltas: usize,
+    num_ic: usize,
+    transcript: &[TranscriptEntry],
+) -> io::Result<()> {
+    let count = |entry| TranscriptEntry::count(transcript, entry);
+    if num_deltas != count(TranscriptEntry::AuxCommit) + 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "number of deltas does not match the transcript",
+        ));
+    }
+    if num_ic != 1 + count(TranscriptEntry::PublicInput) + count(TranscriptEntry::Coin) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "number of ic does not match the transcript",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "debug-verify")]
+mod debug_verify;
+#[cfg(feature = "prover")]
 pub mod generator;
+#[cfg(feature = "mmap")]
+mod mapped;
+#[cfg(feature = "prover")]
 pub mod prover;
+#[cfg(feature = "prover")]
+mod transcript;
 mod verifier;
 
+#[cfg(feature = "debug-verify")]
+pub use self::debug_verify::{
+    verify_proof_verbose, verify_proof_verbose_with_context, PairingTerms, VerifyFailure,
+};
+#[cfg(feature = "prover")]
 pub use self::generator::*;
+#[cfg(feature = "mmap")]
+pub use self::mapped::{MappedParameters, MappedQuery};
+#[cfg(feature = "prover")]
 pub use self::prover::*;
+#[cfg(feature = "prover")]
+pub use self::transcript::{TranscriptLog, TranscriptMessage};
 pub use self::verifier::*;
 
+fn fold_ds<E: Engine>(ds: &[E::G1Affine], coeffs: &[E::Fr]) -> E::G1Affine {
+    assert_eq!(coeffs.len(), ds.len());
+    ds.iter()
+        .zip(coeffs)
+        .fold(E::G1::identity(), |acc, (d, coeff)| acc + *d * coeff)
+        .to_affine()
+}
+
 /// Get a crypto RNG from a [merlin] transcript.
 fn merlin_rng(t: &mut Transcript, label: &'static [u8]) -> Box<dyn RngCore> {
     let mut seed = [0u8; 32];
@@ -39,6 +133,45 @@ fn merlin_rng(t: &mut Transcript, label: &'static [u8]) -> Box<dyn RngCore> {
     Box::new(ChaChaRng::from_seed(seed))
 }
 
+/// The domain separator of the [merlin] transcript that the random coins of a
+/// proof are drawn from, and any external data that the proof is bound to.
+///
+/// A proof only verifies under the context it was made in, so two protocols
+/// can keep their proofs from being replayed in each other by using different
+/// `domain_sep`s, and a proof can be bound to e.g. a session nonce through
+/// `data`. Circuits without random coins do not depend on the context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProofContext<'a> {
+    pub domain_sep: &'static [u8],
+    /// Absorbed into the transcript before anything else. Empty data is not
+    /// absorbed, so that it matches the transcript without any.
+    pub data: &'a [u8],
+}
+
+impl<'a> Default for ProofContext<'a> {
+    fn default() -> Self {
+        ProofContext {
+            domain_sep: b"mirage_aozdemir_1",
+            data: &[],
+        }
+    }
+}
+
+impl<'a> ProofContext<'a> {
+    /// This context, with `data` bound into the transcript.
+    pub fn with_data(self, data: &'a [u8]) -> Self {
+        ProofContext { data, ..self }
+    }
+
+    fn transcript(&self) -> Transcript {
+        let mut transcript = Transcript::new(self.domain_sep);
+        if !self.data.is_empty() {
+            transcript.append_message(b"ctx", self.data);
+        }
+        transcript
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Proof<E: Engine> {
     pub a: E::G1Affine,
@@ -54,6 +187,59 @@ impl<E: Engine> PartialEq for Proof<E> {
 }
 
 impl<E: Engine> Proof<E> {
+    /// The number of group elements in the proof, as `(g1_count, g2_count, ds_count)`.
+    ///
+    /// `a`, `c`, and the `ds` (one per aux block) are in G1, and `b` is in G2.
+    pub fn size_breakdown(&self) -> (usize, usize, usize) {
+        (2 + self.ds.len(), 1, self.ds.len())
+    }
+
+    /// The linear combination of the aux block commitments `ds` with
+    /// `coeffs`, as used by [`verify_proof_folded`].
+    ///
+    /// Panics if `coeffs` and `ds` have different lengths.
+    pub fn fold_ds(&self, coeffs: &[E::Fr]) -> E::G1Affine {
+        fold_ds::<E>(&self.ds, coeffs)
+    }
+
+    /// Check that every group element of the proof is a non-identity point
+    /// of the prime-order subgroup.
+    ///
+    /// Points produced by a checked [`Proof::read`] always pass; this is for
+    /// proofs built from unchecked or hand-made points.
+    pub fn validate(&self) -> Result<(), VerificationError> {
+        self.check_identity()?;
+
+        // The checked uncompressed decoding does the on-curve and subgroup checks.
+        fn in_subgroup<G: UncompressedEncoding>(p: &G) -> bool {
+            G::from_uncompressed(&p.to_uncompressed()).is_some().into()
+        }
+        if !(in_subgroup(&self.a)
+            && in_subgroup(&self.b)
+            && in_subgroup(&self.c)
+            && self.ds.iter().all(in_subgroup))
+        {
+            return Err(VerificationError::InvalidProof);
+        }
+        Ok(())
+    }
+
+    /// Fail with [`VerificationError::UnexpectedIdentity`] if any point is
+    /// the identity.
+    fn check_identity(&self) -> Result<(), VerificationError> {
+        let identity = self.a.is_identity()
+            | self.b.is_identity()
+            | self.c.is_identity()
+            | self
+                .ds
+                .iter()
+                .fold(Choice::from(0), |acc, d| acc | d.is_identity());
+        if bool::from(identity) {
+            return Err(VerificationError::UnexpectedIdentity);
+        }
+        Ok(())
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_all(self.a.to_bytes().as_ref())?;
         writer.write_all(self.b.to_bytes().as_ref())?;
@@ -65,73 +251,77 @@ impl<E: Engine> Proof<E> {
         Ok(())
     }
 
+    /// Read a proof written by [`Self::write`].
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if any point is invalid or
+    /// the identity, which no honest proof has. For the identity, the inner
+    /// error is [`VerificationError::UnexpectedIdentity`], as from
+    /// [`Self::validate`]. More than `2^16` aux block commitments fail the
+    /// same way.
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
-        let read_g1 = |reader: &mut R| -> io::Result<E::G1Affine> {
-            let mut g1_repr = <E::G1Affine as GroupEncoding>::Repr::default();
-            reader.read_exact(g1_repr.as_mut())?;
-
-            let affine = E::G1Affine::from_bytes(&g1_repr);
-            let affine = if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G1"))
-            };
-
-            affine.and_then(|e| {
-                if e.is_identity().into() {
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "point at infinity",
-                    ))
-                } else {
-                    Ok(e)
-                }
-            })
-        };
-
-        let read_g2 = |reader: &mut R| -> io::Result<E::G2Affine> {
-            let mut g2_repr = <E::G2Affine as GroupEncoding>::Repr::default();
-            reader.read_exact(g2_repr.as_mut())?;
-
-            let affine = E::G2Affine::from_bytes(&g2_repr);
-            let affine = if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G2"))
-            };
-
-            affine.and_then(|e| {
-                if e.is_identity().into() {
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "point at infinity",
-                    ))
-                } else {
-                    Ok(e)
-                }
-            })
-        };
-
-        let a = read_g1(&mut reader)?;
-        let b = read_g2(&mut reader)?;
-        let c = read_g1(&mut reader)?;
-        let ds_len = reader.read_u32::<BigEndian>()? as usize;
+        let a = reader.read_group(true, true)?;
+        let b = reader.read_group(true, true)?;
+        let c = reader.read_group(true, true)?;
+        let ds_len = read_len(&mut reader, MAX_AUX_BLOCKS)?;
         let mut ds = vec![];
         for _ in 0..ds_len {
-            ds.push(read_g1(&mut reader)?);
+            ds.push(reader.read_group(true, true)?);
         }
 
-        Ok(Proof { a, b, c, ds })
+        let proof = Proof { a, b, c, ds };
+        proof
+            .check_identity()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(proof)
+    }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// proof.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader))
     }
 }
 
+/// One step of the Fiat-Shamir transcript of a circuit, in the order that
+/// the circuit allocated them.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TranscriptEntry {
+    /// A random coin, drawn from the transcript so far.
     Coin,
+    /// A public input, other than the constant one.
     PublicInput,
+    /// The commitment to an aux block.
     AuxCommit,
 }
 
+impl TranscriptEntry {
+    /// The byte this entry is serialized as.
+    fn tag(self) -> u8 {
+        match self {
+            TranscriptEntry::Coin => 0,
+            TranscriptEntry::PublicInput => 1,
+            TranscriptEntry::AuxCommit => 2,
+        }
+    }
+
+    /// The number of `entry`s in `transcript`.
+    fn count(transcript: &[TranscriptEntry], entry: TranscriptEntry) -> usize {
+        transcript.iter().filter(|e| **e == entry).count()
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Self> {
+        match tag {
+            0 => Ok(TranscriptEntry::Coin),
+            1 => Ok(TranscriptEntry::PublicInput),
+            2 => Ok(TranscriptEntry::AuxCommit),
+            i => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("u8 {} is not a valid transcript entry", i),
+            )),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct VerifyingKey<E: Engine> {
     // alpha in g1 for verifying and for creating A/C elements of
@@ -165,6 +355,19 @@ pub struct VerifyingKey<E: Engine> {
     pub transcript: Vec<TranscriptEntry>,
 }
 
+/// Shows the shape of the key: the number of each kind of point, and the
+/// transcript. The points themselves are left out.
+impl<E: Engine> fmt::Debug for VerifyingKey<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifyingKey")
+            .field("ic", &self.ic.len())
+            .field("deltas_g1", &self.deltas_g1.len())
+            .field("deltas_g2", &self.deltas_g2.len())
+            .field("transcript", &self.transcript)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<E: Engine> PartialEq for VerifyingKey<E> {
     fn eq(&self, other: &Self) -> bool {
         self.alpha_g1 == other.alpha_g1
@@ -179,106 +382,114 @@ impl<E: Engine> PartialEq for VerifyingKey<E> {
 }
 
 impl<E: Engine> VerifyingKey<E> {
+    /// The number of public inputs that [`verify_proof`] expects, not
+    /// counting the constant one.
+    pub fn num_public_inputs(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::PublicInput)
+    }
+
+    /// The number of random coins that the circuit draws.
+    pub fn num_coins(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::Coin)
+    }
+
+    /// The number of aux blocks, and so of `ds` in a proof.
+    pub fn num_aux_commits(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::AuxCommit)
+    }
+
+    /// Write the key, after a header: uncompressed points, and a length
+    /// before each vector.
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        writer.write_all(self.alpha_g1.to_uncompressed().as_ref())?;
-        writer.write_all(self.beta_g1.to_uncompressed().as_ref())?;
-        writer.write_all(self.beta_g2.to_uncompressed().as_ref())?;
-        writer.write_all(self.gamma_g2.to_uncompressed().as_ref())?;
-        writer.write_u32::<BigEndian>(self.deltas_g1.len() as u32)?;
-        for d in &self.deltas_g1 {
-            writer.write_all(d.to_uncompressed().as_ref())?;
-        }
-        for d in &self.deltas_g2 {
-            writer.write_all(d.to_uncompressed().as_ref())?;
-        }
-        writer.write_u32::<BigEndian>(self.ic.len() as u32)?;
-        for ic in &self.ic {
-            writer.write_all(ic.to_uncompressed().as_ref())?;
-        }
-        writer.write_u32::<BigEndian>(self.transcript.len() as u32)?;
+        writer.write_header(KIND_VERIFYING_KEY)?;
+        self.write_body(&mut writer)
+    }
+
+    fn write_body<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_group_uncompressed(&self.alpha_g1)?;
+        writer.write_group_uncompressed(&self.beta_g1)?;
+        writer.write_group_uncompressed(&self.beta_g2)?;
+        writer.write_group_uncompressed(&self.gamma_g2)?;
+        writer.write_groups_uncompressed(&self.deltas_g1)?;
+        writer.write_groups_uncompressed(&self.deltas_g2)?;
+        writer.write_groups_uncompressed(&self.ic)?;
+        writer.write_u64::<BigEndian>(self.transcript.len() as u64)?;
         for e in &self.transcript {
-            match e {
-                TranscriptEntry::Coin => writer.write_u8(0),
-                TranscriptEntry::PublicInput => writer.write_u8(1),
-                TranscriptEntry::AuxCommit => writer.write_u8(2),
-            }?;
+            writer.write_u8(e.tag())?;
         }
-
         Ok(())
     }
 
+    /// Read a key written by [`Self::write`]. Fails with
+    /// [`io::ErrorKind::InvalidData`] on a bad header, on an invalid point, on
+    /// more than `2^16` aux blocks or `2^24` inputs, or if the number of
+    /// deltas or `ic` does not match the transcript.
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
-        let read_g1 = |reader: &mut R| -> io::Result<E::G1Affine> {
-            let mut g1_repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(g1_repr.as_mut())?;
-
-            let affine = E::G1Affine::from_uncompressed(&g1_repr);
-            if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G1"))
-            }
-        };
+        reader.read_header(KIND_VERIFYING_KEY)?;
+        Self::read_body(&mut reader)
+    }
 
-        let read_g2 = |reader: &mut R| -> io::Result<E::G2Affine> {
-            let mut g2_repr = <E::G2Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(g2_repr.as_mut())?;
+    fn read_body<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let alpha_g1 = reader.read_group_uncompressed(true, true)?;
+        let beta_g1 = reader.read_group_uncompressed(true, true)?;
+        let beta_g2 = reader.read_group_uncompressed(true, true)?;
+        let gamma_g2 = reader.read_group_uncompressed(true, true)?;
+        let deltas_g1 = reader.read_groups_uncompressed_bounded(true, true, MAX_AUX_BLOCKS + 1)?;
+        let deltas_g2 = reader.read_groups_uncompressed_bounded(true, true, MAX_AUX_BLOCKS + 1)?;
+        let ic = reader.read_groups_uncompressed_bounded(true, false, MAX_INPUTS)?;
+        let transcript_len = reader.read_groups_len(MAX_INPUTS + MAX_AUX_BLOCKS)?;
+        let mut transcript = Vec::with_capacity(initial_capacity(transcript_len));
+        for _ in 0..transcript_len {
+            transcript.push(TranscriptEntry::from_tag(reader.read_u8()?)?);
+        }
+        if deltas_g2.len() != deltas_g1.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "different numbers of deltas in G1 and G2",
+            ));
+        }
+        check_key_shape(deltas_g1.len(), ic.len(), &transcript)?;
 
-            let affine = E::G2Affine::from_uncompressed(&g2_repr);
-            if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G2"))
-            }
-        };
+        Ok(VerifyingKey {
+            alpha_g1,
+            beta_g1,
+            beta_g2,
+            gamma_g2,
+            deltas_g1,
+            deltas_g2,
+            ic,
+            transcript,
+        })
+    }
 
-        let alpha_g1 = read_g1(&mut reader)?;
-        let beta_g1 = read_g1(&mut reader)?;
-        let beta_g2 = read_g2(&mut reader)?;
-        let gamma_g2 = read_g2(&mut reader)?;
-        let deltas_len = reader.read_u32::<BigEndian>()? as usize;
+    /// Read a key in the format used before the versioned header was added,
+    /// with `u32` lengths and one length for the deltas in G1 and G2. It is
+    /// checked as by [`Self::read`].
+    pub fn read_legacy<R: Read>(mut reader: R) -> io::Result<Self> {
+        let alpha_g1 = reader.read_group_uncompressed(true, true)?;
+        let beta_g1 = reader.read_group_uncompressed(true, true)?;
+        let beta_g2 = reader.read_group_uncompressed(true, true)?;
+        let gamma_g2 = reader.read_group_uncompressed(true, true)?;
+        let deltas_len = read_len(&mut reader, MAX_AUX_BLOCKS + 1)?;
         let mut deltas_g1 = vec![];
         for _ in 0..deltas_len {
-            deltas_g1.push(read_g1(&mut reader)?);
+            deltas_g1.push(reader.read_group_uncompressed(true, true)?);
         }
         let mut deltas_g2 = vec![];
         for _ in 0..deltas_len {
-            deltas_g2.push(read_g2(&mut reader)?);
+            deltas_g2.push(reader.read_group_uncompressed(true, true)?);
         }
-
-        let ic_len = reader.read_u32::<BigEndian>()? as usize;
-
+        let ic_len = read_len(&mut reader, MAX_INPUTS)?;
         let mut ic = vec![];
-
         for _ in 0..ic_len {
-            let g1 = read_g1(&mut reader).and_then(|e| {
-                if e.is_identity().into() {
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "point at infinity",
-                    ))
-                } else {
-                    Ok(e)
-                }
-            })?;
-
-            ic.push(g1);
+            ic.push(reader.read_group_uncompressed(true, false)?);
         }
-        let transcript_len = reader.read_u32::<BigEndian>()? as usize;
+        let transcript_len = read_len(&mut reader, MAX_INPUTS + MAX_AUX_BLOCKS)?;
         let mut transcript = vec![];
         for _ in 0..transcript_len {
-            transcript.push(match reader.read_u8()? {
-                0 => TranscriptEntry::Coin,
-                1 => TranscriptEntry::PublicInput,
-                2 => TranscriptEntry::AuxCommit,
-                i => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("u8 {} is not a valid transcript entry", i),
-                    ))
-                }
-            });
+            transcript.push(TranscriptEntry::from_tag(reader.read_u8()?)?);
         }
+        check_key_shape(deltas_g1.len(), ic.len(), &transcript)?;
 
         Ok(VerifyingKey {
             alpha_g1,
@@ -291,8 +502,15 @@ impl<E: Engine> VerifyingKey<E> {
             transcript,
         })
     }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// key.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader))
+    }
 }
 
+#[cfg(feature = "prover")]
 #[derive(Clone)]
 pub struct Parameters<E: Engine> {
     pub vk: VerifyingKey<E>,
@@ -321,6 +539,7 @@ pub struct Parameters<E: Engine> {
     pub b_g2: Arc<Vec<E::G2Affine>>,
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> Parameters<E> {
     /// Get the commitment key for the ith aux block.
     pub fn get_commitment_key(&self, i: usize) -> CommitKey<E> {
@@ -330,8 +549,84 @@ impl<E: Engine> Parameters<E> {
             self.vk.deltas_g1.last().unwrap().clone(),
         )
     }
+
+    /// Rotate the deltas, replacing each `delta_i` with `delta_i * factors[i]`
+    /// without the toxic waste, and rescaling the `ls` and `h` queries to
+    /// match. Proofs made with the old parameters do not verify under the new
+    /// verifying key.
+    ///
+    /// `factors` has one entry per delta, and none may be zero.
+    pub fn rerandomize_deltas(&mut self, factors: &[E::Fr]) -> Result<(), SynthesisError> {
+        if factors.len() != self.vk.deltas_g1.len() {
+            return Err(SynthesisError::DeltaCountMismatch);
+        }
+        let inverses = factors
+            .iter()
+            .map(|factor| {
+                let inverse = factor.invert();
+                if bool::from(inverse.is_some()) {
+                    Ok(inverse.unwrap())
+                } else {
+                    Err(SynthesisError::UnexpectedIdentity)
+                }
+            })
+            .collect::<Result<Vec<E::Fr>, _>>()?;
+
+        let worker = Worker::global();
+        // The L query of each block is divided by its delta, and the H query
+        // by the last delta.
+        for (l, inverse) in self.ls.iter_mut().zip(&inverses) {
+            *l = Arc::new(scale_g1::<E>(worker, l, *inverse));
+        }
+        self.h = Arc::new(scale_g1::<E>(worker, &self.h, *inverses.last().unwrap()));
+
+        for ((delta_g1, delta_g2), factor) in self
+            .vk
+            .deltas_g1
+            .iter_mut()
+            .zip(self.vk.deltas_g2.iter_mut())
+            .zip(factors)
+        {
+            *delta_g1 = (*delta_g1 * factor).to_affine();
+            *delta_g2 = (*delta_g2 * factor).to_affine();
+        }
+
+        Ok(())
+    }
+}
+
+/// `points`, each multiplied by `k`.
+#[cfg(feature = "prover")]
+fn scale_g1<E: Engine>(worker: &Worker, points: &[E::G1Affine], k: E::Fr) -> Vec<E::G1Affine> {
+    let mut scaled = vec![E::G1::identity(); points.len()];
+    worker.scope(points.len(), |scope, chunk| {
+        if chunk > 0 {
+            for (scaled, points) in scaled.chunks_mut(chunk).zip(points.chunks(chunk)) {
+                scope.spawn(move |_scope| {
+                    for (s, p) in scaled.iter_mut().zip(points) {
+                        *s = *p * k;
+                    }
+                });
+            }
+        }
+    });
+    let mut affine = vec![E::G1Affine::identity(); scaled.len()];
+    E::G1::batch_normalize(&scaled, &mut affine);
+    affine
+}
+
+/// The commitment keys for each aux block, in order.
+///
+/// A proof's `ds[i]` is the commitment to the `i`th aux block under the `i`th
+/// key, blinded by the `kappa_3` used for that block.
+#[cfg(feature = "prover")]
+pub fn aux_commit_keys<E: Engine>(params: &Parameters<E>) -> Vec<CommitKey<E>> {
+    (0..params.ls.len() - 1)
+        .map(|i| params.get_commitment_key(i))
+        .collect()
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> PartialEq for Parameters<E> {
     fn eq(&self, other: &Self) -> bool {
         self.vk == other.vk
@@ -343,144 +638,80 @@ impl<E: Engine> PartialEq for Parameters<E> {
     }
 }
 
+#[cfg(feature = "prover")]
 impl<E: Engine> Parameters<E> {
+    /// Write the parameters, after a header: the verifying key as
+    /// [`VerifyingKey::write`] writes it but without its header, then the
+    /// queries, each prefixed with its length. This is also the layout that
+    /// `MappedParameters` maps, with the `mmap` feature.
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        self.vk.write(&mut writer)?;
-
-        writer.write_u32::<BigEndian>(self.h.len() as u32)?;
-        for g in &self.h[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
-        }
-
-        writer.write_u32::<BigEndian>(self.ls.len() as u32)?;
+        writer.write_header(KIND_PARAMETERS)?;
+        self.vk.write_body(&mut writer)?;
+        writer.write_groups_uncompressed(&self.h[..])?;
+        writer.write_u64::<BigEndian>(self.ls.len() as u64)?;
         for l in &self.ls {
-            writer.write_u32::<BigEndian>(l.len() as u32)?;
-            for g in &l[..] {
-                writer.write_all(g.to_uncompressed().as_ref())?;
-            }
-        }
-
-        writer.write_u32::<BigEndian>(self.a.len() as u32)?;
-        for g in &self.a[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
-        }
-
-        writer.write_u32::<BigEndian>(self.b_g1.len() as u32)?;
-        for g in &self.b_g1[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
-        }
-
-        writer.write_u32::<BigEndian>(self.b_g2.len() as u32)?;
-        for g in &self.b_g2[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
+            writer.write_groups_uncompressed(&l[..])?;
         }
-
+        writer.write_groups_uncompressed(&self.a[..])?;
+        writer.write_groups_uncompressed(&self.b_g1[..])?;
+        writer.write_groups_uncompressed(&self.b_g2[..])?;
         Ok(())
     }
 
+    /// Read parameters written by [`Parameters::write`]. If `checked`, points
+    /// are checked to be on the curve and in the subgroup. Points at infinity
+    /// are always rejected, as are queries of more than `2^28` points and
+    /// more than `2^16` aux blocks.
     pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
-        let read_g1 = |reader: &mut R| -> io::Result<E::G1Affine> {
-            let mut repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(repr.as_mut())?;
-
-            let affine = if checked {
-                E::G1Affine::from_uncompressed(&repr)
-            } else {
-                E::G1Affine::from_uncompressed_unchecked(&repr)
-            };
-
-            let affine = if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G1"))
-            };
-
-            affine.and_then(|e| {
-                if e.is_identity().into() {
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "point at infinity",
-                    ))
-                } else {
-                    Ok(e)
-                }
-            })
-        };
-
-        let read_g2 = |reader: &mut R| -> io::Result<E::G2Affine> {
-            let mut repr = <E::G2Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(repr.as_mut())?;
-
-            let affine = if checked {
-                E::G2Affine::from_uncompressed(&repr)
-            } else {
-                E::G2Affine::from_uncompressed_unchecked(&repr)
-            };
-
-            let affine = if affine.is_some().into() {
-                Ok(affine.unwrap())
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid G2"))
-            };
-
-            affine.and_then(|e| {
-                if e.is_identity().into() {
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "point at infinity",
-                    ))
-                } else {
-                    Ok(e)
-                }
-            })
-        };
-
-        let vk = VerifyingKey::<E>::read(&mut reader)?;
-
-        let mut h = vec![];
-        let mut ls = vec![];
-        let mut a = vec![];
-        let mut b_g1 = vec![];
-        let mut b_g2 = vec![];
-
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                h.push(read_g1(&mut reader)?);
-            }
-        }
-
-        {
-            let ls_len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..ls_len {
-                let mut l = vec![];
-                let l_len = reader.read_u32::<BigEndian>()? as usize;
-                for _ in 0..l_len {
-                    l.push(read_g1(&mut reader)?);
-                }
-                ls.push(Arc::new(l));
-            }
+        reader.read_header(KIND_PARAMETERS)?;
+        let vk = VerifyingKey::<E>::read_body(&mut reader)?;
+        let h = reader.read_groups_uncompressed_bounded(checked, false, MAX_QUERY_LEN)?;
+        let ls_len = reader.read_groups_len(MAX_AUX_BLOCKS + 1)?;
+        let mut ls = Vec::with_capacity(initial_capacity(ls_len));
+        for _ in 0..ls_len {
+            let l = reader.read_groups_uncompressed_bounded(checked, false, MAX_QUERY_LEN)?;
+            ls.push(Arc::new(l));
         }
+        let a = reader.read_groups_uncompressed_bounded(checked, false, MAX_QUERY_LEN)?;
+        let b_g1 = reader.read_groups_uncompressed_bounded(checked, false, MAX_QUERY_LEN)?;
+        let b_g2 = reader.read_groups_uncompressed_bounded(checked, false, MAX_QUERY_LEN)?;
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                a.push(read_g1(&mut reader)?);
-            }
-        }
+        Ok(Parameters {
+            vk,
+            h: Arc::new(h),
+            ls,
+            a: Arc::new(a),
+            b_g1: Arc::new(b_g1),
+            b_g2: Arc::new(b_g2),
+        })
+    }
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
+    /// Read parameters in the format used before the versioned header was
+    /// added, with `u32` lengths and the verifying key as
+    /// [`VerifyingKey::read_legacy`] reads it. They are checked as by
+    /// [`Self::read`].
+    pub fn read_legacy<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let vk = VerifyingKey::<E>::read_legacy(&mut reader)?;
+        let query = |reader: &mut R| -> io::Result<Vec<E::G1Affine>> {
+            let len = read_len(reader, MAX_QUERY_LEN)?;
+            let mut query = vec![];
             for _ in 0..len {
-                b_g1.push(read_g1(&mut reader)?);
+                query.push(reader.read_group_uncompressed(checked, false)?);
             }
+            Ok(query)
+        };
+        let h = query(&mut reader)?;
+        let ls_len = read_len(&mut reader, MAX_AUX_BLOCKS + 1)?;
+        let mut ls = vec![];
+        for _ in 0..ls_len {
+            ls.push(Arc::new(query(&mut reader)?));
         }
-
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                b_g2.push(read_g2(&mut reader)?);
-            }
+        let a = query(&mut reader)?;
+        let b_g1 = query(&mut reader)?;
+        let b_g2_len = read_len(&mut reader, MAX_QUERY_LEN)?;
+        let mut b_g2 = vec![];
+        for _ in 0..b_g2_len {
+            b_g2.push(reader.read_group_uncompressed(checked, false)?);
         }
 
         Ok(Parameters {
@@ -492,8 +723,17 @@ impl<E: Engine> Parameters<E> {
             b_g2: Arc::new(b_g2),
         })
     }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// parameters.
+    pub fn read_framed<R: Read>(reader: R, checked: bool) -> io::Result<Self> {
+        read_framed(reader, |reader| Self::read(reader, checked))
+    }
 }
 
+/// A [`VerifyingKey`] prepared for verification by [`prepare_verifying_key`],
+/// which computes a pairing and prepares every G2 point of the key. Prepare a
+/// key once and reuse it for every proof verified against it.
 pub struct PreparedVerifyingKey<E: MultiMillerLoop> {
     /// Pairing result of alpha*beta
     alpha_g1_beta_g2: E::Gt,
@@ -505,8 +745,113 @@ pub struct PreparedVerifyingKey<E: MultiMillerLoop> {
     ic: Vec<E::G1Affine>,
     /// Transcript
     pub transcript: Vec<TranscriptEntry>,
+
+    // The points the pairing inputs were prepared from, for serialization.
+    alpha_g1: E::G1Affine,
+    beta_g2: E::G2Affine,
+    neg_gamma_g2_affine: E::G2Affine,
+    neg_deltas_g2_affine: Vec<E::G2Affine>,
 }
 
+impl<E: MultiMillerLoop> PreparedVerifyingKey<E> {
+    /// See [`VerifyingKey::num_public_inputs`].
+    pub fn num_public_inputs(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::PublicInput)
+    }
+
+    /// See [`VerifyingKey::num_coins`].
+    pub fn num_coins(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::Coin)
+    }
+
+    /// See [`VerifyingKey::num_aux_commits`].
+    pub fn num_aux_commits(&self) -> usize {
+        TranscriptEntry::count(&self.transcript, TranscriptEntry::AuxCommit)
+    }
+
+    /// Read a [`VerifyingKey`] with [`VerifyingKey::read`] and prepare it.
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        let vk = VerifyingKey::<E>::read(reader)?;
+        Ok(prepare_verifying_key(&vk))
+    }
+
+    /// Like [`Self::read`], but fails if `reader` has any data left after the
+    /// key.
+    pub fn read_framed<R: Read>(reader: R) -> io::Result<Self> {
+        let vk = VerifyingKey::<E>::read_framed(reader)?;
+        Ok(prepare_verifying_key(&vk))
+    }
+
+    /// Write only what verification needs: `alpha_g1`, `beta_g2`, `-gamma_g2`,
+    /// the `-deltas_g2`, `ic` and the transcript, with compressed points.
+    ///
+    /// The pairing of `alpha_g1` and `beta_g2` is written as its two inputs,
+    /// since `E::Gt` has no encoding, and is recomputed by
+    /// [`Self::read_compact`].
+    pub fn write_compact<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_header(KIND_PREPARED_VERIFYING_KEY_COMPACT)?;
+        writer.write_group(&self.alpha_g1)?;
+        writer.write_group(&self.beta_g2)?;
+        writer.write_group(&self.neg_gamma_g2_affine)?;
+        writer.write_groups(&self.neg_deltas_g2_affine)?;
+        writer.write_groups(&self.ic)?;
+        writer.write_u64::<BigEndian>(self.transcript.len() as u64)?;
+        for e in &self.transcript {
+            writer.write_u8(e.tag())?;
+        }
+        Ok(())
+    }
+
+    /// Read a key written by [`Self::write_compact`], checking its points,
+    /// the same lengths as [`VerifyingKey::read`], and that the number of
+    /// `-deltas_g2` and `ic` match the transcript.
+    pub fn read_compact<R: Read>(mut reader: R) -> io::Result<Self> {
+        reader.read_header(KIND_PREPARED_VERIFYING_KEY_COMPACT)?;
+        let alpha_g1 = reader.read_group(true, false)?;
+        let beta_g2 = reader.read_group(true, false)?;
+        let neg_gamma_g2 = reader.read_group(true, false)?;
+        let neg_deltas_g2 = reader.read_groups_bounded(true, false, MAX_AUX_BLOCKS + 1)?;
+        let ic = reader.read_groups_bounded(true, false, MAX_INPUTS)?;
+        let transcript_len = reader.read_groups_len(MAX_INPUTS + MAX_AUX_BLOCKS)?;
+        let mut transcript = Vec::with_capacity(initial_capacity(transcript_len));
+        for _ in 0..transcript_len {
+            transcript.push(TranscriptEntry::from_tag(reader.read_u8()?)?);
+        }
+        check_key_shape(neg_deltas_g2.len(), ic.len(), &transcript)?;
+        Ok(prepare_from_parts(
+            alpha_g1,
+            beta_g2,
+            neg_gamma_g2,
+            neg_deltas_g2,
+            ic,
+            transcript,
+        ))
+    }
+
+    /// [`Self::write_compact`], into a new buffer.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_compact(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// [`Self::read_compact`], rejecting trailing data.
+    pub fn from_compact_bytes(bytes: &[u8]) -> io::Result<Self> {
+        read_framed(bytes, |reader| Self::read_compact(reader))
+    }
+}
+
+/// A [`Proof`] whose points have been validated and whose `b` has been
+/// prepared for the pairing, so that it can be verified repeatedly.
+pub struct PreparedProof<E: MultiMillerLoop> {
+    a: E::G1Affine,
+    b: E::G2Prepared,
+    c: E::G1Affine,
+    ds: Vec<E::G1Affine>,
+}
+
+#[cfg(feature = "prover")]
 pub trait ParameterSource<E: Engine> {
     type G1Builder: SourceBuilder<E::G1Affine>;
     type G2Builder: SourceBuilder<E::G2Affine>;
@@ -531,6 +876,7 @@ pub trait ParameterSource<E: Engine> {
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError>;
 }
 
+#[cfg(feature = "prover")]
 impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
     type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
     type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
@@ -572,7 +918,7 @@ impl<'a, E: Engine> ParameterSource<E> for &'a Parameters<E> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod test_with_bls12_381 {
     use super::*;
     use crate::{
@@ -580,46 +926,47 @@ mod test_with_bls12_381 {
         SynthesisError,
     };
 
-    use bls12_381::{Bls12, Scalar};
+    use bls12_381::{Bls12, G1Affine, G2Affine, Scalar};
     use ff::{Field, PrimeField};
+    use group::Curve;
     use rand::thread_rng;
     use std::ops::MulAssign;
 
-    #[test]
-    fn serialization() {
-        struct MySillyCircuit<Scalar: PrimeField> {
-            a: Option<Scalar>,
-            b: Option<Scalar>,
-        }
-
-        impl<Scalar: PrimeField> CcCircuit<Scalar> for MySillyCircuit<Scalar> {
-            fn synthesize<CS: CcConstraintSystem<Scalar>>(
-                self,
-                cs: &mut CS,
-            ) -> Result<(), SynthesisError> {
-                let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
-                let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
-                let c = cs.alloc_input(
-                    || "c",
-                    || {
-                        let mut a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
-                        let b = self.b.ok_or(SynthesisError::AssignmentMissing)?;
-
-                        a.mul_assign(&b);
-                        Ok(a)
-                    },
-                )?;
-
-                cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
-
-                Ok(())
-            }
+    struct MySillyCircuit<Scalar: PrimeField> {
+        a: Option<Scalar>,
+        b: Option<Scalar>,
+    }
 
-            fn num_aux_blocks(&self) -> usize {
-                0
-            }
+    impl<Scalar: PrimeField> CcCircuit<Scalar> for MySillyCircuit<Scalar> {
+        fn synthesize<CS: CcConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || "c",
+                || {
+                    let mut a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                    let b = self.b.ok_or(SynthesisError::AssignmentMissing)?;
+
+                    a.mul_assign(&b);
+                    Ok(a)
+                },
+            )?;
+
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+
+            Ok(())
         }
 
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn serialization() {
         let mut rng = thread_rng();
 
         let params = generate_random_parameters::<Bls12, _, _>(
@@ -632,13 +979,30 @@ mod test_with_bls12_381 {
             let mut v = vec![];
 
             params.write(&mut v).unwrap();
-            assert_eq!(v.len(), 2149);
+            assert_eq!(v.len(), 2199);
 
             let de_params = Parameters::read(&v[..], true).unwrap();
             assert!(params == de_params);
 
             let de_params = Parameters::read(&v[..], false).unwrap();
             assert!(params == de_params);
+
+            let de_params = Parameters::read_framed(&v[..], false).unwrap();
+            assert!(params == de_params);
+            v.push(0);
+            assert!(Parameters::<Bls12>::read_framed(&v[..], false).is_err());
+        }
+
+        {
+            let mut v = vec![];
+            params.vk.write(&mut v).unwrap();
+
+            let de_vk = VerifyingKey::read_framed(&v[..]).unwrap();
+            assert!(params.vk == de_vk);
+            assert!(PreparedVerifyingKey::<Bls12>::read_framed(&v[..]).is_ok());
+            v.push(0);
+            assert!(VerifyingKey::<Bls12>::read_framed(&v[..]).is_err());
+            assert!(PreparedVerifyingKey::<Bls12>::read_framed(&v[..]).is_err());
         }
 
         let pvk = prepare_verifying_key::<Bls12>(&params.vk);
@@ -649,7 +1013,7 @@ mod test_with_bls12_381 {
             let mut c = a;
             c.mul_assign(&b);
 
-            let proof = create_random_proof(
+            let (proof, _) = create_random_proof(
                 MySillyCircuit {
                     a: Some(a),
                     b: Some(b),
@@ -667,8 +1031,808 @@ mod test_with_bls12_381 {
             let de_proof = Proof::read(&v[..]).unwrap();
             assert!(proof == de_proof);
 
+            let de_proof = Proof::read_framed(&v[..]).unwrap();
+            assert!(proof == de_proof);
+            v.push(0);
+            assert!(Proof::<Bls12>::read_framed(&v[..]).is_err());
+
             assert!(verify_proof(&pvk, &proof, &[c]).is_ok());
             assert!(verify_proof(&pvk, &proof, &[a]).is_err());
         }
     }
+
+    #[test]
+    fn prepared_proof() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MySillyCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        let a = Scalar::random(&mut rng);
+        let b = Scalar::random(&mut rng);
+        let c = a * b;
+
+        let (proof, _) = create_random_proof(
+            MySillyCircuit {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        let prepared = prepare_proof(&proof).unwrap();
+        for inputs in [[c], [a], [b]] {
+            assert_eq!(
+                verify_prepared_proof(&pvk, &prepared, &inputs).is_ok(),
+                verify_proof(&pvk, &proof, &inputs).is_ok()
+            );
+        }
+        assert!(verify_prepared_proof(&pvk, &prepared, &[c]).is_ok());
+
+        let mut bad = proof.clone();
+        bad.a = <Bls12 as Engine>::G1Affine::identity();
+        assert!(matches!(
+            prepare_proof(&bad),
+            Err(crate::VerificationError::UnexpectedIdentity)
+        ));
+
+        let mut bad = proof;
+        bad.ds.push(<Bls12 as Engine>::G1Affine::identity());
+        assert!(prepare_proof(&bad).is_err());
+    }
+
+    #[test]
+    fn prepared_inputs() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MySillyCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        let a = Scalar::random(&mut rng);
+        let b = Scalar::random(&mut rng);
+        let c = a * b;
+
+        // Without random coins, one accumulator serves every proof of `c`.
+        let prepared_c = pvk.prepare_inputs(&[c], &[]).unwrap();
+        for (a, b) in [(a, b), (a.double(), b * Scalar::from(2).invert().unwrap())] {
+            let (proof, _) = create_random_proof(
+                MySillyCircuit {
+                    a: Some(a),
+                    b: Some(b),
+                },
+                &params,
+                &mut rng,
+            )
+            .unwrap();
+            assert!(verify_proof_with_prepared_inputs(&pvk, &proof, &prepared_c).is_ok());
+            for inputs in [[c], [a], [b]] {
+                let prepared = pvk.prepare_inputs(&inputs, &proof.ds).unwrap();
+                assert_eq!(
+                    verify_proof_with_prepared_inputs(&pvk, &proof, &prepared).is_ok(),
+                    verify_proof(&pvk, &proof, &inputs).is_ok()
+                );
+            }
+        }
+    }
+
+    /// Too few or too many inputs or `ds` are rejected, rather than
+    /// panicking the verifier.
+    #[test]
+    fn wrong_lengths_are_invalid_proofs() {
+        fn assert_invalid_proof(result: Result<(), VerificationError>) {
+            assert!(matches!(result, Err(VerificationError::InvalidProof)));
+        }
+
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MySillyCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+        let a = Scalar::random(&mut rng);
+        let b = Scalar::random(&mut rng);
+        let (proof, _) = create_random_proof(
+            MySillyCircuit {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify_proof(&pvk, &proof, &[a * b]).is_ok());
+        assert_invalid_proof(verify_proof(&pvk, &proof, &[]));
+        assert_invalid_proof(verify_proof(&pvk, &proof, &[a * b, a * b]));
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        let prepared = pvk.prepare_inputs(&[], &proof.ds).unwrap();
+        assert!(verify_proof_with_prepared_inputs(&pvk, &proof, &prepared).is_ok());
+
+        let mut short = proof.clone();
+        short.ds.pop();
+        assert_invalid_proof(verify_proof(&pvk, &short, &[]));
+        assert_invalid_proof(verify_proof_with_prepared_inputs(&pvk, &short, &prepared));
+
+        let mut long = proof;
+        long.ds.push(G1Affine::generator());
+        assert_invalid_proof(verify_proof(&pvk, &long, &[]));
+        assert_invalid_proof(verify_proof_with_prepared_inputs(&pvk, &long, &prepared));
+    }
+
+    #[test]
+    fn verifying_key_debug() {
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut crate::testing::test_rng(),
+        )
+        .unwrap();
+        // Two aux blocks, and the constant input.
+        assert_eq!(
+            format!("{:?}", params.vk),
+            "VerifyingKey { ic: 1, deltas_g1: 3, deltas_g2: 3, \
+             transcript: [AuxCommit, AuxCommit], .. }"
+        );
+    }
+
+    #[test]
+    fn prepared_verifying_key_compact() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+        let bytes = pvk.to_compact_bytes();
+        let de_pvk = PreparedVerifyingKey::<Bls12>::from_compact_bytes(&bytes).unwrap();
+        assert_eq!(de_pvk.transcript, pvk.transcript);
+        assert_eq!(de_pvk.to_compact_bytes(), bytes);
+
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(verify_proof(&de_pvk, &proof, &[]).is_ok());
+
+        let mut bad = proof;
+        bad.c = (bad.c.to_curve() + <Bls12 as Engine>::G1::generator()).to_affine();
+        assert!(verify_proof(&de_pvk, &bad, &[]).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(PreparedVerifyingKey::<Bls12>::from_compact_bytes(&trailing).is_err());
+        assert!(
+            PreparedVerifyingKey::<Bls12>::from_compact_bytes(&bytes[..bytes.len() - 1]).is_err()
+        );
+
+        // A transcript with an entry more than the deltas or ic allow for.
+        for extra in [TranscriptEntry::AuxCommit, TranscriptEntry::PublicInput] {
+            let mut vk = params.vk.clone();
+            vk.transcript.push(extra);
+            let bytes = prepare_verifying_key::<Bls12>(&vk).to_compact_bytes();
+            let err = PreparedVerifyingKey::<Bls12>::from_compact_bytes(&bytes)
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn prepared_verifying_key_read() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MySillyCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+
+        let mut v = vec![];
+        params.vk.write(&mut v).unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+        let de_pvk = PreparedVerifyingKey::<Bls12>::read(&v[..]).unwrap();
+
+        let a = Scalar::random(&mut rng);
+        let b = Scalar::random(&mut rng);
+        let (proof, _) = create_random_proof(
+            MySillyCircuit {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        for inputs in [[a * b], [a]] {
+            assert_eq!(
+                verify_proof(&de_pvk, &proof, &inputs).is_ok(),
+                verify_proof(&pvk, &proof, &inputs).is_ok()
+            );
+        }
+        assert!(verify_proof(&de_pvk, &proof, &[a * b]).is_ok());
+
+        // A transcript with an entry more than the deltas or ic allow for.
+        for extra in [TranscriptEntry::AuxCommit, TranscriptEntry::Coin] {
+            let mut vk = params.vk.clone();
+            vk.transcript.push(extra);
+            let mut v = vec![];
+            vk.write(&mut v).unwrap();
+            let err = VerifyingKey::<Bls12>::read(&v[..]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            let err = PreparedVerifyingKey::<Bls12>::read(&v[..]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    struct MyBlockCircuit<Scalar: PrimeField> {
+        a: Option<Scalar>,
+        b: Option<Scalar>,
+    }
+
+    impl<Scalar: PrimeField> CcCircuit<Scalar> for MyBlockCircuit<Scalar> {
+        fn synthesize<CS: CcConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.end_aux_block(|| "ab")?;
+            let c = cs.alloc(
+                || "c",
+                || Ok(self.a.ok_or(SynthesisError::AssignmentMissing)? * self.b.unwrap()),
+            )?;
+            cs.end_aux_block(|| "c")?;
+
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn aux_commit_keys_open_ds() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        let kappa_3s = vec![Scalar::random(&mut rng), Scalar::random(&mut rng)];
+        let (proof, aux_blocks) = create_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            Scalar::random(&mut rng),
+            Scalar::random(&mut rng),
+            kappa_3s.clone(),
+        )
+        .unwrap();
+        assert!(verify_proof(&pvk, &proof, &[]).is_ok());
+
+        let keys = aux_commit_keys(&params);
+        assert_eq!(keys.len(), 2);
+        for (((key, block), kappa_3), d) in
+            keys.iter().zip(&aux_blocks).zip(&kappa_3s).zip(&proof.ds)
+        {
+            assert_eq!(key.commit(block, *kappa_3).unwrap().to_affine(), *d);
+            assert!(key.verify_open(&d.to_curve(), block, *kappa_3));
+        }
+    }
+
+    #[test]
+    fn verify_with_link() {
+        use crate::commit::cp_link;
+        use group::Group;
+
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        let kappa_3s = vec![Scalar::random(&mut rng), Scalar::random(&mut rng)];
+        let (proof, aux_blocks) = create_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            Scalar::random(&mut rng),
+            Scalar::random(&mut rng),
+            kappa_3s.clone(),
+        )
+        .unwrap();
+
+        // External commitments to the aux blocks, under a key of their own.
+        let len = aux_blocks.iter().map(Vec::len).max().unwrap();
+        let k = CommitKey::<Bls12>::new(
+            Arc::new(
+                std::iter::repeat_with(|| <Bls12 as Engine>::G1::random(&mut rng).to_affine())
+                    .take(len)
+                    .collect(),
+            ),
+            <Bls12 as Engine>::G1::random(&mut rng).to_affine(),
+        );
+        let rands = vec![Scalar::random(&mut rng), Scalar::random(&mut rng)];
+        let cmts: Vec<_> = aux_blocks
+            .iter()
+            .zip(&rands)
+            .map(|(block, r)| k.commit(block, *r).unwrap().to_affine())
+            .collect();
+
+        let (link_pk, link_vk) = cp_link::key_gen(&k, &aux_commit_keys(&params), &mut rng);
+        let link_proof =
+            cp_link::prove(&link_pk, aux_blocks.clone(), rands, kappa_3s.clone()).unwrap();
+        assert!(cp_link::verify(&link_vk, &cmts, &proof.ds, &link_proof));
+        let link_pvk = cp_link::PreparedVerifyingKey::from(&link_vk);
+        assert!(
+            verify_proof_with_link(&pvk, &proof, &[], &link_pvk, &cmts, &link_proof, &mut rng)
+                .is_ok()
+        );
+
+        // A bad mirage proof fails, even with a good link.
+        let mut bad = proof.clone();
+        bad.c = (bad.c.to_curve() + <Bls12 as Engine>::G1::generator()).to_affine();
+        assert!(
+            verify_proof_with_link(&pvk, &bad, &[], &link_pvk, &cmts, &link_proof, &mut rng)
+                .is_err()
+        );
+
+        // A bad link fails, even with a good mirage proof.
+        let bad_link = cp_link::prove(
+            &link_pk,
+            aux_blocks.clone(),
+            vec![Scalar::random(&mut rng), Scalar::random(&mut rng)],
+            kappa_3s.clone(),
+        )
+        .unwrap();
+        assert!(
+            verify_proof_with_link(&pvk, &proof, &[], &link_pvk, &cmts, &bad_link, &mut rng)
+                .is_err()
+        );
+
+        // So do commitments to other vectors.
+        let mut bad_cmts = cmts.clone();
+        bad_cmts[1] = (bad_cmts[1].to_curve() + <Bls12 as Engine>::G1::generator()).to_affine();
+        assert!(verify_proof_with_link(
+            &pvk,
+            &proof,
+            &[],
+            &link_pvk,
+            &bad_cmts,
+            &link_proof,
+            &mut rng
+        )
+        .is_err());
+
+        // Wrong numbers of commitments are rejected, rather than panicking.
+        assert!(matches!(
+            verify_proof_with_link(
+                &pvk,
+                &proof,
+                &[],
+                &link_pvk,
+                &cmts[..1],
+                &link_proof,
+                &mut rng
+            ),
+            Err(VerificationError::InvalidProof)
+        ));
+        let (_, short_vk) = cp_link::key_gen(&k, &aux_commit_keys(&params)[..1], &mut rng);
+        assert!(matches!(
+            verify_proof_with_link(
+                &pvk,
+                &proof,
+                &[],
+                &cp_link::PreparedVerifyingKey::from(&short_vk),
+                &cmts,
+                &link_proof,
+                &mut rng
+            ),
+            Err(VerificationError::InvalidVerifyingKey)
+        ));
+    }
+
+    #[test]
+    fn proof_deterministic() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        // The MSMs run concurrently, but the proof only depends on the
+        // circuit and the randomness.
+        let (a, b) = (Scalar::random(&mut rng), Scalar::random(&mut rng));
+        let (r, s) = (Scalar::random(&mut rng), Scalar::random(&mut rng));
+        let kappa_3s = vec![Scalar::random(&mut rng), Scalar::random(&mut rng)];
+        let prove = || {
+            create_proof(
+                MyBlockCircuit {
+                    a: Some(a),
+                    b: Some(b),
+                },
+                &params,
+                r,
+                s,
+                kappa_3s.clone(),
+            )
+            .unwrap()
+            .0
+        };
+        let proof = prove();
+        for _ in 0..4 {
+            assert!(prove() == proof);
+        }
+        assert!(verify_proof(&pvk, &proof, &[]).is_ok());
+    }
+
+    #[test]
+    fn size_breakdown() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MySillyCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let (proof, _) = create_random_proof(
+            MySillyCircuit {
+                a: Some(Scalar::one()),
+                b: Some(Scalar::one()),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(proof.size_breakdown(), (2, 1, 0));
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::one()),
+                b: Some(Scalar::one()),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        assert_eq!(proof.size_breakdown(), (4, 1, 2));
+    }
+
+    #[test]
+    fn read_rejects_identity() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        let mut v = vec![];
+        proof.write(&mut v).unwrap();
+        assert!(Proof::<Bls12>::read(&v[..]).unwrap() == proof);
+
+        // a, b, c, the ds length, and two ds.
+        let identity_g1 = <Bls12 as Engine>::G1Affine::identity().to_bytes();
+        let g1_len = identity_g1.as_ref().len();
+        let g2_len = <Bls12 as Engine>::G2Affine::identity()
+            .to_bytes()
+            .as_ref()
+            .len();
+        let d1 = 2 * g1_len + g2_len + 4 + g1_len;
+        for offset in [0, d1] {
+            let mut bad = v.clone();
+            bad[offset..offset + g1_len].copy_from_slice(identity_g1.as_ref());
+            let err = Proof::<Bls12>::read(&bad[..]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_identity() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(proof.validate().is_ok());
+        assert!(verify_proof(&pvk, &proof, &[]).is_ok());
+
+        let mut bad = proof;
+        bad.ds[1] = <Bls12 as Engine>::G1Affine::identity();
+        assert!(matches!(
+            bad.validate(),
+            Err(crate::VerificationError::UnexpectedIdentity)
+        ));
+        assert!(matches!(
+            verify_proof(&pvk, &bad, &[]),
+            Err(crate::VerificationError::UnexpectedIdentity)
+        ));
+        assert!(matches!(
+            verify_proof_with_active_blocks(&pvk, &bad, &[], &[true, false]),
+            Err(crate::VerificationError::UnexpectedIdentity)
+        ));
+
+        // Reading reports the identity the same way.
+        let mut bytes = vec![];
+        bad.write(&mut bytes).unwrap();
+        let err = Proof::<Bls12>::read(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref()
+                .and_then(|e| e.downcast_ref::<crate::VerificationError>()),
+            Some(crate::VerificationError::UnexpectedIdentity)
+        ));
+    }
+
+    /// Truncated and corrupted encodings fail to read, rather than panicking.
+    #[test]
+    fn read_malformed() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let (proof, _) = create_random_proof(
+            MyBlockCircuit {
+                a: Some(Scalar::random(&mut rng)),
+                b: Some(Scalar::random(&mut rng)),
+            },
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        fn check<T>(bytes: &[u8], read: impl Fn(&[u8]) -> io::Result<T>) {
+            assert!(read(bytes).is_ok());
+            for len in 0..bytes.len() {
+                assert!(read(&bytes[..len]).is_err());
+            }
+            for i in (0..bytes.len()).step_by(7) {
+                let mut bad = bytes.to_vec();
+                bad[i] ^= 0xff;
+                let _ = read(&bad);
+            }
+        }
+
+        let mut v = vec![];
+        proof.write(&mut v).unwrap();
+        check(&v, |b| Proof::<Bls12>::read(b));
+        check(&v, |b| Proof::<Bls12>::read_framed(b));
+
+        let mut v = vec![];
+        params.vk.write(&mut v).unwrap();
+        check(&v, |b| VerifyingKey::<Bls12>::read(b));
+        check(&v, |b| VerifyingKey::<Bls12>::read_framed(b));
+        check(&v, |b| PreparedVerifyingKey::<Bls12>::read_framed(b));
+
+        let pvk = prepare_verifying_key(&params.vk);
+        check(&pvk.to_compact_bytes(), |b| {
+            PreparedVerifyingKey::<Bls12>::from_compact_bytes(b)
+        });
+
+        let mut v = vec![];
+        params.write(&mut v).unwrap();
+        check(&v, |b| Parameters::<Bls12>::read(b, false));
+        check(&v, |b| Parameters::<Bls12>::read_framed(b, false));
+
+        // A length prefix without the data behind it: the header, alpha_g1,
+        // beta_g2 and -gamma_g2, then the number of -deltas_g2.
+        let mut v = pvk.to_compact_bytes();
+        v.truncate(6 + 48 + 2 * 96);
+        v.extend_from_slice(&(MAX_AUX_BLOCKS as u64 + 1).to_be_bytes());
+        let err = PreparedVerifyingKey::<Bls12>::from_compact_bytes(&v)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    /// Keys and parameters written before the versioned header was added
+    /// still read with `read_legacy`.
+    #[test]
+    fn read_legacy() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+
+        // The old layout has the same points, but no header, `u32` lengths,
+        // and one length for the deltas in G1 and G2.
+        fn point<G: UncompressedEncoding>(v: &mut Vec<u8>, p: &G) {
+            v.extend_from_slice(p.to_uncompressed().as_ref());
+        }
+        fn points<G: UncompressedEncoding>(v: &mut Vec<u8>, ps: &[G]) {
+            v.extend_from_slice(&(ps.len() as u32).to_be_bytes());
+            for p in ps {
+                point(v, p);
+            }
+        }
+        let vk = &params.vk;
+        let mut v = vec![];
+        point(&mut v, &vk.alpha_g1);
+        point(&mut v, &vk.beta_g1);
+        point(&mut v, &vk.beta_g2);
+        point(&mut v, &vk.gamma_g2);
+        points(&mut v, &vk.deltas_g1);
+        for d in &vk.deltas_g2 {
+            point(&mut v, d);
+        }
+        points(&mut v, &vk.ic);
+        v.extend_from_slice(&(vk.transcript.len() as u32).to_be_bytes());
+        v.extend(vk.transcript.iter().map(|e| e.tag()));
+        assert!(VerifyingKey::<Bls12>::read_legacy(&v[..]).unwrap() == *vk);
+        assert!(VerifyingKey::<Bls12>::read(&v[..]).is_err());
+
+        points(&mut v, &params.h);
+        v.extend_from_slice(&(params.ls.len() as u32).to_be_bytes());
+        for l in &params.ls {
+            points(&mut v, l);
+        }
+        points(&mut v, &params.a);
+        points(&mut v, &params.b_g1);
+        points(&mut v, &params.b_g2);
+        assert!(Parameters::<Bls12>::read_legacy(&v[..], true).unwrap() == params);
+        assert!(Parameters::<Bls12>::read(&v[..], true).is_err());
+    }
+
+    /// Lengths above the readers' caps are rejected as invalid, before any of
+    /// the data behind them is read.
+    #[test]
+    fn read_rejects_long_lengths() {
+        let mut rng = crate::testing::test_rng();
+
+        let params = generate_random_parameters::<Bls12, _, _>(
+            MyBlockCircuit { a: None, b: None },
+            &mut rng,
+        )
+        .unwrap();
+        let vk = &params.vk;
+
+        // `bytes` up to `at`, then the length `len` in place of the one there,
+        // as a `u32` for proofs and a `u64` for keys and parameters.
+        fn with_len(bytes: &[u8], at: usize, len: usize) -> Vec<u8> {
+            let mut v = bytes[..at].to_vec();
+            v.extend_from_slice(&(len as u32).to_be_bytes());
+            v
+        }
+        fn with_long_len(bytes: &[u8], at: usize, len: usize) -> Vec<u8> {
+            let mut v = bytes[..at].to_vec();
+            v.extend_from_slice(&(len as u64).to_be_bytes());
+            v
+        }
+        fn assert_invalid<T>(result: io::Result<T>) {
+            assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+
+        // The compressed a, b and c, then the number of ds.
+        let proof = Proof::<Bls12> {
+            a: G1Affine::generator(),
+            b: G2Affine::generator(),
+            c: G1Affine::generator(),
+            ds: vec![],
+        };
+        let mut v = vec![];
+        proof.write(&mut v).unwrap();
+        let err = Proof::<Bls12>::read(&with_len(&v, 192, MAX_AUX_BLOCKS)[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_invalid(Proof::<Bls12>::read(
+            &with_len(&v, 192, MAX_AUX_BLOCKS + 1)[..],
+        ));
+
+        // The header, alpha_g1, beta_g1, beta_g2 and gamma_g2, then the deltas
+        // in G1 and in G2, the ic and the transcript entries, each after its
+        // length.
+        let mut v = vec![];
+        vk.write(&mut v).unwrap();
+        let deltas_g1_at = 6 + 2 * 96 + 2 * 192;
+        let deltas_g2_at = deltas_g1_at + 8 + vk.deltas_g1.len() * 96;
+        let ic_at = deltas_g2_at + 8 + vk.deltas_g2.len() * 192;
+        let transcript_at = ic_at + 8 + vk.ic.len() * 96;
+        let read_vk = |at, len| VerifyingKey::<Bls12>::read(&with_long_len(&v, at, len)[..]);
+        assert_invalid(read_vk(deltas_g1_at, MAX_AUX_BLOCKS + 2));
+        assert_invalid(read_vk(deltas_g2_at, MAX_AUX_BLOCKS + 2));
+        assert_invalid(read_vk(ic_at, MAX_INPUTS + 1));
+        assert_invalid(read_vk(transcript_at, MAX_INPUTS + MAX_AUX_BLOCKS + 1));
+
+        // The header and the verifying key, as long as the key on its own,
+        // then the number of h, the h, the number of l queries and the length
+        // of the first.
+        let vk_len = v.len();
+        let mut v = vec![];
+        params.write(&mut v).unwrap();
+        let ls_at = vk_len + 8 + params.h.len() * 96;
+        let read_params =
+            |at, len| Parameters::<Bls12>::read(&with_long_len(&v, at, len)[..], false);
+        assert_invalid(read_params(vk_len, MAX_QUERY_LEN + 1));
+        assert_invalid(read_params(ls_at, MAX_AUX_BLOCKS + 2));
+        assert_invalid(read_params(ls_at + 8, MAX_QUERY_LEN + 1));
+
+        // The header, alpha_g1, beta_g2 and -gamma_g2, then the number of
+        // -deltas_g2.
+        let mut v = prepare_verifying_key(vk).to_compact_bytes();
+        v.truncate(6 + 48 + 2 * 96);
+        v.extend_from_slice(&(MAX_AUX_BLOCKS as u64 + 2).to_be_bytes());
+        assert_invalid(PreparedVerifyingKey::<Bls12>::from_compact_bytes(&v));
+    }
 }
@@ -1,16 +1,18 @@
 use rand_core::RngCore;
+use std::io;
+use std::mem;
 use std::ops::{AddAssign, MulAssign};
 use std::sync::Arc;
 
 use ff::{Field, PrimeField, PrimeFieldBits};
-use group::{prime::PrimeCurveAffine, Curve, UncompressedEncoding};
-use merlin::Transcript;
+use group::{prime::PrimeCurveAffine, Curve, Group, UncompressedEncoding};
 use pairing::Engine;
 
-use super::{merlin_rng, ParameterSource, Proof, VerifyingKey};
+use super::transcript::{LoggedTranscript, TranscriptLog};
+use super::{ParameterSource, Proof, ProofContext, VerifyingKey};
 
 use crate::{
-    cc::{CcCircuit, CcConstraintSystem},
+    cc::{CcCircuit, CcConstraintSystem, WitnessMap, WitnessedCircuit},
     ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
 };
 
@@ -18,8 +20,11 @@ use crate::domain::{EvaluationDomain, Scalar};
 
 use crate::multiexp::{multiexp, DensityTracker, FullDensity};
 
-use crate::multicore::Worker;
-use crate::{start_timer, end_timer};
+use crate::multicore::{Waiter, Worker};
+use crate::{end_timer, start_timer};
+
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 fn eval<S: PrimeField>(
     lc: &LinearCombination<S>,
@@ -30,7 +35,7 @@ fn eval<S: PrimeField>(
 ) -> S {
     let mut acc = S::zero();
 
-    for &(index, coeff) in lc.0.iter() {
+    for (index, coeff) in lc.iter() {
         let mut tmp;
 
         if !coeff.is_zero_vartime() {
@@ -59,6 +64,45 @@ fn eval<S: PrimeField>(
     acc
 }
 
+/// Count the variables of `lc` in the densities, as [`eval`] does, without
+/// evaluating it.
+fn track_density<S: PrimeField>(
+    lc: &LinearCombination<S>,
+    mut input_density: Option<&mut DensityTracker>,
+    mut aux_density: Option<&mut DensityTracker>,
+) {
+    for (index, coeff) in lc.iter() {
+        if !coeff.is_zero_vartime() {
+            match index {
+                Variable(Index::Input(i)) => {
+                    if let Some(ref mut v) = input_density {
+                        v.inc(i);
+                    }
+                }
+                Variable(Index::Aux(i)) => {
+                    if let Some(ref mut v) = aux_density {
+                        v.inc(i);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Overwrite `scalars` with zeros, in a way that the compiler can't optimize
+/// away.
+#[cfg(feature = "zeroize")]
+fn zeroize_scalars<'a, S: Field + 'a>(scalars: impl IntoIterator<Item = &'a mut S>) {
+    for s in scalars {
+        // Safety: `s` is a valid, aligned and exclusive reference.
+        unsafe { std::ptr::write_volatile(s, S::zero()) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+/// With the `zeroize` feature, the witness-dependent fields (the assignments,
+/// the evaluations of A, B and C, the `kappa_3s` and the aux blocks) are
+/// zeroized on drop.
 pub struct ProvingAssignment<'p, E: Engine, P: ParameterSource<E> + 'p> {
     // Density of queries
     a_aux_density: DensityTracker,
@@ -66,9 +110,9 @@ pub struct ProvingAssignment<'p, E: Engine, P: ParameterSource<E> + 'p> {
     b_aux_density: DensityTracker,
 
     // Evaluations of A, B, C polynomials
-    a: Vec<Scalar<E::Fr>>,
-    b: Vec<Scalar<E::Fr>>,
-    c: Vec<Scalar<E::Fr>>,
+    a: Vec<E::Fr>,
+    b: Vec<E::Fr>,
+    c: Vec<E::Fr>,
 
     // Assignments of variables
     input_assignment: Vec<E::Fr>,
@@ -81,13 +125,128 @@ pub struct ProvingAssignment<'p, E: Engine, P: ParameterSource<E> + 'p> {
     aux_blocks: Vec<Vec<E::Fr>>,
     vk: &'p VerifyingKey<E>,
     params: &'p mut P,
+    /// Shared by the MSMs of every block commitment.
+    worker: &'p Worker,
 
     /// The length of this is equal to the number of aux blocks.
     /// Each entry indicates the first aux index *after* the block.
     aux_block_indices: Vec<usize>,
-    transcript: Transcript,
+    transcript: LoggedTranscript,
+    /// With batched evaluation, the simplified A, B and C of each constraint
+    /// not yet evaluated. Their densities are already tracked.
+    pending: Option<Vec<[LinearCombination<E::Fr>; 3]>>,
+}
+
+impl<'p, E: Engine, P: ParameterSource<E> + 'p> ProvingAssignment<'p, E, P> {
+    /// Create an empty assignment, with the "one" input allocated.
+    pub(super) fn new(
+        params: &'p mut P,
+        vk: &'p VerifyingKey<E>,
+        worker: &'p Worker,
+        kappa_3s: Vec<E::Fr>,
+        transcript: LoggedTranscript,
+    ) -> Result<Self, SynthesisError> {
+        let mut prover = ProvingAssignment {
+            a_aux_density: DensityTracker::new(),
+            b_input_density: DensityTracker::new(),
+            b_aux_density: DensityTracker::new(),
+            a: vec![],
+            b: vec![],
+            c: vec![],
+            kappa_3s,
+            params,
+            vk,
+            worker,
+            pi_ds: vec![],
+            aux_blocks: vec![],
+            input_assignment: vec![],
+            aux_assignment: vec![],
+            aux_block_indices: vec![],
+            transcript,
+            pending: None,
+        };
+
+        prover.alloc_input(|| "", || Ok(E::Fr::one()))?;
+        Ok(prover)
+    }
+
+    /// The messages absorbed into the transcript so far, in order, from which
+    /// [`TranscriptLog::replay`] rebuilds it.
+    pub fn transcript_log(&self) -> &TranscriptLog {
+        self.transcript.log()
+    }
+
+    /// Enforce `input * 0 = 0` for every input, which gives the inputs full
+    /// density in the A query, and evaluate any constraints still pending.
+    fn finish_synthesis(&mut self) {
+        for i in 0..self.input_assignment.len() {
+            self.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+        }
+        self.eval_pending();
+    }
+
+    /// Evaluate the pending constraints in parallel, appending to `a`, `b` and
+    /// `c` in the order they were enforced.
+    fn eval_pending(&mut self) {
+        let pending = match self.pending.take() {
+            Some(pending) if !pending.is_empty() => pending,
+            _ => return,
+        };
+        let start = self.a.len();
+        let len = start + pending.len();
+        self.a.resize(len, E::Fr::zero());
+        self.b.resize(len, E::Fr::zero());
+        self.c.resize(len, E::Fr::zero());
+
+        let input_assignment = &self.input_assignment[..];
+        let aux_assignment = &self.aux_assignment[..];
+        let (a, b, c) = (
+            &mut self.a[start..],
+            &mut self.b[start..],
+            &mut self.c[start..],
+        );
+        self.worker.scope(pending.len(), |scope, chunk| {
+            for (((lcs, a), b), c) in pending
+                .chunks(chunk)
+                .zip(a.chunks_mut(chunk))
+                .zip(b.chunks_mut(chunk))
+                .zip(c.chunks_mut(chunk))
+            {
+                scope.spawn(move |_| {
+                    for (i, [lc_a, lc_b, lc_c]) in lcs.iter().enumerate() {
+                        a[i] = eval(lc_a, None, None, input_assignment, aux_assignment);
+                        b[i] = eval(lc_b, None, None, input_assignment, aux_assignment);
+                        c[i] = eval(lc_c, None, None, input_assignment, aux_assignment);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<'p, E: Engine, P: ParameterSource<E> + 'p> Zeroize for ProvingAssignment<'p, E, P> {
+    fn zeroize(&mut self) {
+        zeroize_scalars(&mut self.a);
+        zeroize_scalars(&mut self.b);
+        zeroize_scalars(&mut self.c);
+        zeroize_scalars(&mut self.input_assignment);
+        zeroize_scalars(&mut self.aux_assignment);
+        zeroize_scalars(&mut self.kappa_3s);
+        zeroize_scalars(self.aux_blocks.iter_mut().flatten());
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<'p, E: Engine, P: ParameterSource<E> + 'p> Drop for ProvingAssignment<'p, E, P> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
 }
 
+#[cfg(feature = "zeroize")]
+impl<'p, E: Engine, P: ParameterSource<E> + 'p> ZeroizeOnDrop for ProvingAssignment<'p, E, P> {}
+
 impl<'p, E: Engine, P: ParameterSource<E> + 'p> ConstraintSystem<E::Fr>
     for ProvingAssignment<'p, E, P>
 {
@@ -113,10 +272,8 @@ impl<'p, E: Engine, P: ParameterSource<E> + 'p> ConstraintSystem<E::Fr>
         AR: Into<String>,
     {
         self.input_assignment.push(f()?);
-        self.transcript.append_message(
-            b"input",
-            self.input_assignment.last().unwrap().to_repr().as_ref(),
-        );
+        self.transcript
+            .append_input(self.input_assignment.last().unwrap().to_repr().as_ref())?;
         self.b_input_density.add_element();
 
         Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
@@ -130,11 +287,28 @@ impl<'p, E: Engine, P: ParameterSource<E> + 'p> ConstraintSystem<E::Fr>
         LB: FnOnce(LinearCombination<E::Fr>) -> LinearCombination<E::Fr>,
         LC: FnOnce(LinearCombination<E::Fr>) -> LinearCombination<E::Fr>,
     {
-        let a = a(LinearCombination::zero());
-        let b = b(LinearCombination::zero());
-        let c = c(LinearCombination::zero());
+        let mut a = a(LinearCombination::zero());
+        let mut b = b(LinearCombination::zero());
+        let mut c = c(LinearCombination::zero());
+        // Variables whose terms cancel out have no query points, so they must
+        // not count towards the densities.
+        a.simplify();
+        b.simplify();
+        c.simplify();
+
+        if let Some(pending) = &mut self.pending {
+            // The same densities as the evaluations below track.
+            track_density(&a, None, Some(&mut self.a_aux_density));
+            track_density(
+                &b,
+                Some(&mut self.b_input_density),
+                Some(&mut self.b_aux_density),
+            );
+            pending.push([a, b, c]);
+            return;
+        }
 
-        self.a.push(Scalar(eval(
+        self.a.push(eval(
             &a,
             // Inputs have full density in the A query
             // because there are constraints of the
@@ -143,15 +317,15 @@ impl<'p, E: Engine, P: ParameterSource<E> + 'p> ConstraintSystem<E::Fr>
             Some(&mut self.a_aux_density),
             &self.input_assignment,
             &self.aux_assignment,
-        )));
-        self.b.push(Scalar(eval(
+        ));
+        self.b.push(eval(
             &b,
             Some(&mut self.b_input_density),
             Some(&mut self.b_aux_density),
             &self.input_assignment,
             &self.aux_assignment,
-        )));
-        self.c.push(Scalar(eval(
+        ));
+        self.c.push(eval(
             &c,
             // There is no C polynomial query,
             // though there is an (beta)A + (alpha)B + C
@@ -161,7 +335,7 @@ impl<'p, E: Engine, P: ParameterSource<E> + 'p> ConstraintSystem<E::Fr>
             None,
             &self.input_assignment,
             &self.aux_assignment,
-        )));
+        ));
     }
 
     fn push_namespace<NR, N>(&mut self, _: N)
@@ -194,7 +368,7 @@ where
         A: FnOnce() -> AR,
         AR: Into<String>,
     {
-        let mut rng = merlin_rng(&mut self.transcript, b"random");
+        let mut rng = self.transcript.coin_rng()?;
         let value = E::Fr::random(&mut *rng);
         let var = self.alloc_input(annotation, || Ok(value.clone()))?;
         Ok((var, Some(value)))
@@ -206,44 +380,83 @@ where
         A: FnOnce() -> AR,
         AR: Into<String>,
     {
-        let worker = Worker::new();
         let i = self.aux_block_indices.len();
+        if i >= self.kappa_3s.len() {
+            return Err(SynthesisError::AuxBlockCountMismatch);
+        }
         let start = self.aux_block_indices.last().copied().unwrap_or(0);
         let end = self.aux_assignment.len();
-        assert!(end > start);
         self.aux_blocks
             .push(self.aux_assignment[start..end].to_vec());
-        let aux_assignment: Arc<Vec<_>> = Arc::new(
-            self.aux_assignment[start..end]
-                .into_iter()
-                .map(|s| s.clone().into())
-                .collect::<Vec<_>>(),
-        );
-        let mut pi_d: E::G1 = multiexp(
-            &worker,
-            self.params.get_l(end - start, i)?,
-            FullDensity,
-            aux_assignment,
-        )
-        .wait()?;
-        // [ J_i(s)/delta_i + delta_last * k_i ]_1
-        AddAssign::<&E::G1>::add_assign(
-            &mut pi_d,
-            &(self.vk.deltas_g1.last().unwrap().clone() * self.kappa_3s[i]),
-        );
-        let pi_d = pi_d.to_affine();
+        let pi_d = match self.transcript.resumed_aux_commit() {
+            // A commitment from the log being resumed from is reused as is.
+            Some(logged) => {
+                let mut repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
+                if logged.len() != repr.as_ref().len() {
+                    return Err(SynthesisError::TranscriptMismatch);
+                }
+                repr.as_mut().copy_from_slice(logged);
+                Option::from(E::G1Affine::from_uncompressed(&repr))
+                    .ok_or(SynthesisError::TranscriptMismatch)?
+            }
+            None => {
+                let aux_assignment: Arc<Vec<_>> = Arc::new(
+                    self.aux_assignment[start..end]
+                        .into_iter()
+                        .map(|s| s.clone().into())
+                        .collect::<Vec<_>>(),
+                );
+                let mut pi_d: E::G1 = multiexp(
+                    self.worker,
+                    self.params.get_l(end - start, i)?,
+                    FullDensity,
+                    aux_assignment,
+                )
+                .wait()?;
+                // [ J_i(s)/delta_i + delta_last * k_i ]_1
+                AddAssign::<&E::G1>::add_assign(
+                    &mut pi_d,
+                    &(self.vk.deltas_g1.last().unwrap().clone() * self.kappa_3s[i]),
+                );
+                pi_d.to_affine()
+            }
+        };
         self.transcript
-            .append_message(b"aux_commit", pi_d.to_uncompressed().as_ref());
+            .append_aux_commit(pi_d.to_uncompressed().as_ref())?;
         self.pi_ds.push(pi_d);
         self.aux_block_indices.push(self.aux_assignment.len());
         Ok(())
     }
+
+    fn current_aux_block(&self) -> usize {
+        self.aux_block_indices.len()
+    }
 }
 
 pub fn create_random_proof<E, C, R, P: ParameterSource<E>>(
+    circuit: C,
+    params: P,
+    rng: &mut R,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+    R: RngCore,
+{
+    create_random_proof_with_context(circuit, params, rng, &ProofContext::default())
+}
+
+/// Like [`create_random_proof`], but drawing the random coins from a
+/// transcript separated by `ctx`. The proof only verifies with
+/// [`verify_proof_with_context`](super::verify_proof_with_context) under the
+/// same `ctx`.
+#[allow(clippy::type_complexity)]
+pub fn create_random_proof_with_context<E, C, R, P: ParameterSource<E>>(
     circuit: C,
     params: P,
     mut rng: &mut R,
+    ctx: &ProofContext,
 ) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
 where
     E: Engine,
@@ -256,13 +469,12 @@ where
     let num_kappa_3s = circuit.num_aux_blocks();
     let kappa_3s: Vec<_> = (0..num_kappa_3s).map(|_| E::Fr::random(&mut rng)).collect();
 
-    create_proof::<E, C, P>(circuit, params, r, s, kappa_3s)
+    create_proof_with_context::<E, C, P>(circuit, params, r, s, kappa_3s, ctx)
 }
 
-#[allow(clippy::many_single_char_names)]
 pub fn create_proof<E, C, P: ParameterSource<E>>(
     circuit: C,
-    mut params: P,
+    params: P,
     r: E::Fr,
     s: E::Fr,
     kappa_3s: Vec<E::Fr>,
@@ -272,219 +484,848 @@ where
     E::Fr: PrimeFieldBits,
     C: CcCircuit<E::Fr>,
 {
-    assert_eq!(kappa_3s.len(), circuit.num_aux_blocks());
-
-    // we're assuming the arg doesn't matter
-    let vk = params.get_vk(1337)?;
-
-    let mut prover = ProvingAssignment {
-        a_aux_density: DensityTracker::new(),
-        b_input_density: DensityTracker::new(),
-        b_aux_density: DensityTracker::new(),
-        a: vec![],
-        b: vec![],
-        c: vec![],
-        kappa_3s: kappa_3s.clone(),
-        params: &mut params,
-        vk: &vk,
-        pi_ds: vec![],
-        aux_blocks: vec![],
-        input_assignment: vec![],
-        aux_assignment: vec![],
-        aux_block_indices: vec![],
-        transcript: Transcript::new(b"mirage_aozdemir_1"),
-    };
-
-    prover.alloc_input(|| "", || Ok(E::Fr::one()))?;
-
-    let t_synth = start_timer!(|| "synthesis");
-    circuit.synthesize(&mut prover)?;
-    end_timer!(t_synth);
-    let t_nosynth = start_timer!(|| "post-synth");
-
-    for i in 0..prover.input_assignment.len() {
-        prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
-    }
-
-    let worker = Worker::new();
-
-    let t_h = start_timer!(|| "h commit");
-    let h = {
-        let t_h_coeffs = start_timer!(|| "h coeffs");
-        let mut a = EvaluationDomain::from_coeffs(prover.a)?;
-        let mut b = EvaluationDomain::from_coeffs(prover.b)?;
-        let mut c = EvaluationDomain::from_coeffs(prover.c)?;
-        a.ifft(&worker);
-        a.coset_fft(&worker);
-        b.ifft(&worker);
-        b.coset_fft(&worker);
-        c.ifft(&worker);
-        c.coset_fft(&worker);
-
-        a.mul_assign(&worker, &b);
-        drop(b);
-        a.sub_assign(&worker, &c);
-        drop(c);
-        a.divide_by_z_on_coset(&worker);
-        a.icoset_fft(&worker);
-        let mut a = a.into_coeffs();
-        let a_len = a.len() - 1;
-        a.truncate(a_len);
-        // TODO: parallelize if it's even helpful
-        let a = Arc::new(a.into_iter().map(|s| s.0.into()).collect::<Vec<_>>());
-        end_timer!(t_h_coeffs);
-
-        multiexp(&worker, prover.params.get_h(a.len())?, FullDensity, a)
-    };
-    end_timer!(t_h);
-    let t = start_timer!(|| "msm setup");
-
-    // TODO: parallelize if it's even helpful
-    let input_assignment = Arc::new(
-        prover
-            .input_assignment
-            .into_iter()
-            .map(|s| s.into())
-            .collect::<Vec<_>>(),
-    );
-    let final_block_aux_assignment = Arc::new({
-        let start = prover.aux_block_indices.last().cloned().unwrap_or(0);
-        prover.aux_assignment[start..]
-            .iter()
-            .cloned()
-            .into_iter()
-            .map(|s| s.into())
-            .collect::<Vec<_>>()
-    });
-    let aux_assignment = Arc::new(
-        prover
-            .aux_assignment
-            .into_iter()
-            .map(|s| s.into())
-            .collect::<Vec<_>>(),
-    );
-
-    let l = multiexp(
-        &worker,
-        prover.params.get_l(
-            final_block_aux_assignment.len(),
-            prover.aux_block_indices.len(),
-        )?,
-        FullDensity,
-        final_block_aux_assignment.clone(),
-    );
-
-    let a_aux_density_total = prover.a_aux_density.get_total_density();
-
-    let (a_inputs_source, a_aux_source) = prover
-        .params
-        .get_a(input_assignment.len(), a_aux_density_total)?;
-
-    let a_inputs = multiexp(
-        &worker,
-        a_inputs_source,
-        FullDensity,
-        input_assignment.clone(),
-    );
-    let a_aux = multiexp(
-        &worker,
-        a_aux_source,
-        Arc::new(prover.a_aux_density),
-        aux_assignment.clone(),
-    );
-
-    let b_input_density = Arc::new(prover.b_input_density);
-    let b_input_density_total = b_input_density.get_total_density();
-    let b_aux_density = Arc::new(prover.b_aux_density);
-    let b_aux_density_total = b_aux_density.get_total_density();
-
-    let (b_g1_inputs_source, b_g1_aux_source) = prover
-        .params
-        .get_b_g1(b_input_density_total, b_aux_density_total)?;
-
-    let b_g1_inputs = multiexp(
-        &worker,
-        b_g1_inputs_source,
-        b_input_density.clone(),
-        input_assignment.clone(),
-    );
-    let b_g1_aux = multiexp(
-        &worker,
-        b_g1_aux_source,
-        b_aux_density.clone(),
-        aux_assignment.clone(),
-    );
-
-    let (b_g2_inputs_source, b_g2_aux_source) = prover
-        .params
-        .get_b_g2(b_input_density_total, b_aux_density_total)?;
-
-    let b_g2_inputs = multiexp(
-        &worker,
-        b_g2_inputs_source,
-        b_input_density,
-        input_assignment,
-    );
-    let b_g2_aux = multiexp(&worker, b_g2_aux_source, b_aux_density, aux_assignment);
-
-    for i in 0..vk.deltas_g1.len() {
-        if bool::from(vk.deltas_g1[i].is_identity() | vk.deltas_g2[i].is_identity()) {
-            // If this element is zero, someone is trying to perform a
-            // subversion-CRS attack.
-            return Err(SynthesisError::UnexpectedIdentity);
+    ProofBuilder::new(circuit, params, r, s, kappa_3s)?.finish()
+}
+
+/// Like [`create_proof`], but drawing the random coins from a transcript
+/// separated by `ctx`. The proof only verifies with
+/// [`verify_proof_with_context`](super::verify_proof_with_context) under the
+/// same `ctx`.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_with_context<E, C, P: ParameterSource<E>>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+    ctx: &ProofContext,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::new(ctx),
+        true,
+        false,
+        &mut |_| (),
+    )?
+    .finish()
+}
+
+/// Like [`create_proof_with_context`], but also returning the log of the
+/// prover's transcript, which [`resume_proof`] can pick up from.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_with_log<E, C, P: ParameterSource<E>>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+    ctx: &ProofContext,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>, TranscriptLog), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    let builder = ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::new(ctx),
+        true,
+        false,
+        &mut |_| (),
+    )?;
+    let log = builder.transcript_log().clone();
+    let (proof, aux_blocks) = builder.finish()?;
+    Ok((proof, aux_blocks, log))
+}
+
+/// Like [`create_proof_with_context`], but resuming the prover's transcript
+/// from `log`, recorded by [`create_proof_with_log`] under the same `ctx`, or
+/// a prefix of it (see [`TranscriptLog::truncate`]).
+///
+/// The aux block commitments in `log` are reused rather than recomputed, so
+/// the blocks they commit to must have the same witness and `kappa_3s` as
+/// when the log was recorded; otherwise the proof doesn't verify. Every
+/// other message the prover absorbs while `log` lasts must match it, or this
+/// fails with [`SynthesisError::TranscriptMismatch`]. After that, proving
+/// carries on as usual.
+#[allow(clippy::type_complexity)]
+pub fn resume_proof<E, C, P: ParameterSource<E>>(
+    log: &TranscriptLog,
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+    ctx: &ProofContext,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::resume(ctx, log.clone()),
+        true,
+        false,
+        &mut |_| (),
+    )?
+    .finish()
+}
+
+/// Like [`create_proof`], but with `zk` selecting whether the proof is
+/// zero-knowledge.
+///
+/// With `zk` false, `r` and `s` are ignored and taken to be zero, and the
+/// multiexp of the B query in G1, which is only needed to randomize `C`, is
+/// skipped. The proof still verifies, but `A`, `B` and `C` are then
+/// deterministic functions of the witness, and so may leak it. Only turn
+/// `zk` off when the witness is not secret, e.g. for proofs of correct
+/// computation on public data. The block commitments stay blinded by
+/// `kappa_3s` either way.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_opts<E, C, P: ParameterSource<E>>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+    zk: bool,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::new(&ProofContext::default()),
+        zk,
+        false,
+        &mut |_| (),
+    )?
+    .finish()
+}
+
+/// Like [`create_proof`], but deferring the evaluation of each constraint's
+/// `A`, `B` and `C` until synthesis is done, and then evaluating them all in
+/// parallel on the worker.
+///
+/// The densities are still tracked as each constraint is enforced. This keeps
+/// every constraint's linear combinations in memory until synthesis ends, so
+/// it suits circuits whose long linear combinations dominate synthesis. The
+/// proof is the same as [`create_proof`]'s.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_batched<E, C, P: ParameterSource<E>>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::new(&ProofContext::default()),
+        true,
+        true,
+        &mut |_| (),
+    )?
+    .finish()
+}
+
+/// Like [`create_proof`], but with the variables of `structure` assigned from
+/// `witness` instead of by its own `alloc` closures (see
+/// [`WitnessLookup`](crate::cc::WitnessLookup)).
+///
+/// This lets one circuit structure be reused with many witnesses.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_with_witness<E, C, P: ParameterSource<E>>(
+    structure: C,
+    witness: &WitnessMap<E::Fr>,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+{
+    create_proof(
+        WitnessedCircuit::new(structure, witness),
+        params,
+        r,
+        s,
+        kappa_3s,
+    )
+}
+
+/// Like [`create_proof`], but calling `on_progress` as each phase of proving
+/// completes, in the order of the variants of [`ProofPhase`].
+///
+/// The proof is the same as [`create_proof`]'s.
+#[allow(clippy::type_complexity)]
+pub fn create_proof_with_progress<E, C, P, F>(
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+    kappa_3s: Vec<E::Fr>,
+    mut on_progress: F,
+) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+    C: CcCircuit<E::Fr>,
+    P: ParameterSource<E>,
+    F: FnMut(ProofPhase),
+{
+    ProofBuilder::build(
+        circuit,
+        params,
+        r,
+        s,
+        kappa_3s,
+        LoggedTranscript::new(&ProofContext::default()),
+        true,
+        false,
+        &mut on_progress,
+    )?
+    .finish_with_progress(&mut on_progress)
+}
+
+/// Compute the coefficients of the quotient polynomial
+/// `H(x) = (A(x) * B(x) - C(x)) / t(x)`, where `A`, `B` and `C` take the
+/// values `a`, `b` and `c` on the evaluation domain.
+///
+/// `a`, `b` and `c` hold one evaluation per constraint, as collected by the
+/// prover, and are padded to the smallest domain that fits. The result has
+/// one fewer coefficient than the domain size, matching the length of the
+/// H query; [`create_proof`] commits to it with a multiexp.
+pub fn compute_h_coeffs<S: PrimeField>(
+    a: Vec<S>,
+    b: Vec<S>,
+    c: Vec<S>,
+    worker: &Worker,
+) -> Result<Vec<S>, SynthesisError> {
+    let mut a = EvaluationDomain::from_coeffs(a.into_iter().map(Scalar).collect())?;
+    let mut b = EvaluationDomain::from_coeffs(b.into_iter().map(Scalar).collect())?;
+    let mut c = EvaluationDomain::from_coeffs(c.into_iter().map(Scalar).collect())?;
+    a.ifft(worker);
+    a.coset_fft(worker);
+    b.ifft(worker);
+    b.coset_fft(worker);
+    c.ifft(worker);
+    c.coset_fft(worker);
+
+    a.mul_assign(worker, &b);
+    #[cfg(feature = "zeroize")]
+    zeroize_scalars(b.as_mut().iter_mut().map(|s| &mut s.0));
+    drop(b);
+    a.sub_assign(worker, &c);
+    #[cfg(feature = "zeroize")]
+    zeroize_scalars(c.as_mut().iter_mut().map(|s| &mut s.0));
+    drop(c);
+    a.divide_by_z_on_coset(worker);
+    a.icoset_fft(worker);
+    let mut a: Vec<S> = a.into_coeffs().into_iter().map(|s| s.0).collect();
+    let a_len = a.len() - 1;
+    a.truncate(a_len);
+    Ok(a)
+}
+
+/// A phase of proving, as reported by [`create_proof_with_progress`].
+///
+/// These end with the matching spans timed by [`crate::trace`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofPhase {
+    /// The circuit has been synthesized, and the aux blocks committed to.
+    Synthesis,
+    /// The H coefficients have been computed, and their multiexp launched.
+    HCommit,
+    /// The multiexps over the assignment have been launched.
+    MsmSetup,
+    /// All multiexps are done, and folded into the proof.
+    Fold,
+    /// The proof is assembled.
+    Done,
+}
+
+/// A result of a multiexp that may still be running.
+struct PendingMsm<G> {
+    waiter: Option<Waiter<Result<G, SynthesisError>>>,
+    result: Option<Result<G, SynthesisError>>,
+}
+
+impl<G: Copy> PendingMsm<G> {
+    fn new(waiter: Waiter<Result<G, SynthesisError>>) -> Self {
+        PendingMsm {
+            waiter: Some(waiter),
+            result: None,
+        }
+    }
+
+    /// A multiexp that was skipped, with a known result.
+    fn ready(value: G) -> Self {
+        PendingMsm {
+            waiter: None,
+            result: Some(Ok(value)),
+        }
+    }
+
+    /// Block until the multiexp is done. If it failed, every call returns
+    /// its error.
+    fn wait(&mut self) -> Result<G, SynthesisError> {
+        if let Some(waiter) = self.waiter.take() {
+            self.result = Some(waiter.wait());
+        }
+        match self.result.as_ref().expect("waited above") {
+            Ok(value) => Ok(*value),
+            Err(e) => Err(copy_error(e)),
         }
     }
-    end_timer!(t);
-    let t = start_timer!(|| "pre-msm wait");
+}
+
+/// A copy of `e`, which is not `Clone` because of the [`io::Error`] it may
+/// hold. An I/O error is copied as its kind and message.
+fn copy_error(e: &SynthesisError) -> SynthesisError {
+    match e {
+        SynthesisError::AssignmentMissing => SynthesisError::AssignmentMissing,
+        SynthesisError::DivisionByZero => SynthesisError::DivisionByZero,
+        SynthesisError::Unsatisfiable => SynthesisError::Unsatisfiable,
+        SynthesisError::PolynomialDegreeTooLarge => SynthesisError::PolynomialDegreeTooLarge,
+        SynthesisError::UnexpectedIdentity => SynthesisError::UnexpectedIdentity,
+        SynthesisError::IoError(e) => io::Error::new(e.kind(), e.to_string()).into(),
+        SynthesisError::UnconstrainedVariable => SynthesisError::UnconstrainedVariable,
+        SynthesisError::AuxBlockCountMismatch => SynthesisError::AuxBlockCountMismatch,
+        SynthesisError::DeltaCountMismatch => SynthesisError::DeltaCountMismatch,
+        SynthesisError::LengthMismatch => SynthesisError::LengthMismatch,
+        SynthesisError::EmptyDomain => SynthesisError::EmptyDomain,
+        SynthesisError::TranscriptMismatch => SynthesisError::TranscriptMismatch,
+    }
+}
+
+/// A mirage proof whose multiexps are running in the background, so that
+/// parts of it can be sent as soon as they are ready.
+///
+/// With the `zeroize` feature, `r`, `s` and the aux blocks are zeroized when
+/// the builder is dropped. The aux blocks returned by [`Self::finish`] are the
+/// caller's to zeroize. The multiexp exponents, shared with the worker
+/// threads, are not zeroized.
+///
+/// The block commitments are ready as soon as the builder is created. `A`
+/// and `B` each only need their own multiexps, and `C` needs all of them.
+/// If any multiexp fails, every call that needs it returns its error.
+pub struct ProofBuilder<E: Engine> {
+    r: E::Fr,
+    s: E::Fr,
+    // The randomized parts of A, B and C, without the multiexps.
+    g_a: E::G1,
+    g_b: E::G2,
+    g_c: E::G1,
+    a_inputs: PendingMsm<E::G1>,
+    a_aux: PendingMsm<E::G1>,
+    b_g1_inputs: PendingMsm<E::G1>,
+    b_g1_aux: PendingMsm<E::G1>,
+    b_g2_inputs: PendingMsm<E::G2>,
+    b_g2_aux: PendingMsm<E::G2>,
+    h: PendingMsm<E::G1>,
+    l: PendingMsm<E::G1>,
+    pi_ds: Vec<E::G1Affine>,
+    aux_blocks: Vec<Vec<E::Fr>>,
+    transcript_log: TranscriptLog,
+}
+
+#[cfg(feature = "zeroize")]
+impl<E: Engine> Drop for ProofBuilder<E> {
+    fn drop(&mut self) {
+        zeroize_scalars([&mut self.r, &mut self.s]);
+        zeroize_scalars(self.aux_blocks.iter_mut().flatten());
+    }
+}
+
+impl<E> ProofBuilder<E>
+where
+    E: Engine,
+    E::Fr: PrimeFieldBits,
+{
+    /// Synthesize `circuit`, commit to its aux blocks, and launch the
+    /// multiexps for the rest of the proof.
+    ///
+    /// The proof is the same as [`create_proof`]'s for the same arguments.
+    pub fn new<C, P: ParameterSource<E>>(
+        circuit: C,
+        params: P,
+        r: E::Fr,
+        s: E::Fr,
+        kappa_3s: Vec<E::Fr>,
+    ) -> Result<Self, SynthesisError>
+    where
+        C: CcCircuit<E::Fr>,
+    {
+        Self::build(
+            circuit,
+            params,
+            r,
+            s,
+            kappa_3s,
+            LoggedTranscript::new(&ProofContext::default()),
+            true,
+            false,
+            &mut |_| (),
+        )
+    }
 
-    let last = vk.deltas_g1.len() - 1;
-    let mut g_a = vk.deltas_g1[last] * r;
-    AddAssign::<&E::G1Affine>::add_assign(&mut g_a, &vk.alpha_g1);
-    let mut g_b = vk.deltas_g2[last] * s;
-    AddAssign::<&E::G2Affine>::add_assign(&mut g_b, &vk.beta_g2);
-    let mut g_c;
+    /// Without `zk`, `r` and `s` are zero and the B-in-G1 multiexp is
+    /// skipped; see [`create_proof_opts`].
+    /// With `batch`, the constraints are evaluated in parallel after
+    /// synthesis; see [`create_proof_batched`].
+    #[allow(clippy::many_single_char_names, clippy::too_many_arguments)]
+    fn build<C, P: ParameterSource<E>>(
+        circuit: C,
+        mut params: P,
+        r: E::Fr,
+        s: E::Fr,
+        kappa_3s: Vec<E::Fr>,
+        transcript: LoggedTranscript,
+        zk: bool,
+        batch: bool,
+        on_progress: &mut dyn FnMut(ProofPhase),
+    ) -> Result<Self, SynthesisError>
+    where
+        C: CcCircuit<E::Fr>,
     {
-        let mut rs = r;
-        rs.mul_assign(&s);
+        assert_eq!(kappa_3s.len(), circuit.num_aux_blocks());
+        let (r, s) = if zk {
+            (r, s)
+        } else {
+            (E::Fr::zero(), E::Fr::zero())
+        };
+
+        // we're assuming the arg doesn't matter
+        let vk = params.get_vk(1337)?;
+        for i in 0..vk.deltas_g1.len() {
+            if bool::from(vk.deltas_g1[i].is_identity() | vk.deltas_g2[i].is_identity()) {
+                // If this element is zero, someone is trying to perform a
+                // subversion-CRS attack.
+                return Err(SynthesisError::UnexpectedIdentity);
+            }
+        }
+
+        let worker = Worker::global();
 
-        g_c = vk.deltas_g1[last] * rs;
-        let tf = start_timer!(|| "mirage extra group fold");
-        for i in 0..kappa_3s.len() {
-            AddAssign::<&E::G1>::add_assign(&mut g_c, &(-vk.deltas_g1[i] * kappa_3s[i]));
+        let mut prover = ProvingAssignment::new(&mut params, &vk, worker, kappa_3s, transcript)?;
+        if batch {
+            prover.pending = Some(vec![]);
         }
-        end_timer!(tf);
-        AddAssign::<&E::G1>::add_assign(&mut g_c, &(vk.alpha_g1 * s));
-        AddAssign::<&E::G1>::add_assign(&mut g_c, &(vk.beta_g1 * r));
-    }
-    end_timer!(t);
-    let t = start_timer!(|| "wait for MSMs and fold");
-    let mut a_answer = a_inputs.wait()?;
-    AddAssign::<&E::G1>::add_assign(&mut a_answer, &a_aux.wait()?);
-    AddAssign::<&E::G1>::add_assign(&mut g_a, &a_answer);
-    MulAssign::<E::Fr>::mul_assign(&mut a_answer, s);
-    AddAssign::<&E::G1>::add_assign(&mut g_c, &a_answer);
-
-    let mut b1_answer: E::G1 = b_g1_inputs.wait()?;
-    AddAssign::<&E::G1>::add_assign(&mut b1_answer, &b_g1_aux.wait()?);
-    let mut b2_answer = b_g2_inputs.wait()?;
-    AddAssign::<&E::G2>::add_assign(&mut b2_answer, &b_g2_aux.wait()?);
-
-    AddAssign::<&E::G2>::add_assign(&mut g_b, &b2_answer);
-    MulAssign::<E::Fr>::mul_assign(&mut b1_answer, r);
-    AddAssign::<&E::G1>::add_assign(&mut g_c, &b1_answer);
-    AddAssign::<&E::G1>::add_assign(&mut g_c, &h.wait()?);
-    AddAssign::<&E::G1>::add_assign(&mut g_c, &l.wait()?);
-    end_timer!(t);
-
-    let r = Ok((
-        Proof {
-            a: g_a.to_affine(),
-            b: g_b.to_affine(),
+
+        let t_synth = start_timer!(|| "synthesis");
+        circuit.synthesize(&mut prover)?;
+        end_timer!(t_synth);
+        // Catch circuits that end fewer blocks than they declare; ending too many
+        // fails in `end_aux_block`.
+        if prover.aux_block_indices.len() != prover.kappa_3s.len() {
+            return Err(SynthesisError::AuxBlockCountMismatch);
+        }
+        on_progress(ProofPhase::Synthesis);
+        let t_nosynth = start_timer!(|| "post-synth");
+
+        prover.finish_synthesis();
+
+        let t_h = start_timer!(|| "h commit");
+        let h = {
+            let t_h_coeffs = start_timer!(|| "h coeffs");
+            let a = compute_h_coeffs(
+                mem::take(&mut prover.a),
+                mem::take(&mut prover.b),
+                mem::take(&mut prover.c),
+                worker,
+            )?;
+            // TODO: parallelize if it's even helpful
+            let exps = Arc::new(a.iter().map(|s| s.into()).collect::<Vec<_>>());
+            #[cfg(feature = "zeroize")]
+            {
+                let mut a = a;
+                zeroize_scalars(&mut a);
+            }
+            let a = exps;
+            end_timer!(t_h_coeffs);
+
+            multiexp(worker, prover.params.get_h(a.len())?, FullDensity, a)
+        };
+        end_timer!(t_h);
+        on_progress(ProofPhase::HCommit);
+        let t = start_timer!(|| "msm setup");
+
+        // TODO: parallelize if it's even helpful
+        let input_assignment = Arc::new(
+            prover
+                .input_assignment
+                .iter()
+                .map(|s| s.into())
+                .collect::<Vec<_>>(),
+        );
+        let final_block_aux_assignment = Arc::new({
+            let start = prover.aux_block_indices.last().cloned().unwrap_or(0);
+            prover.aux_assignment[start..]
+                .iter()
+                .cloned()
+                .into_iter()
+                .map(|s| s.into())
+                .collect::<Vec<_>>()
+        });
+        let aux_assignment = Arc::new(
+            prover
+                .aux_assignment
+                .iter()
+                .map(|s| s.into())
+                .collect::<Vec<_>>(),
+        );
+
+        let l = multiexp(
+            worker,
+            prover.params.get_l(
+                final_block_aux_assignment.len(),
+                prover.aux_block_indices.len(),
+            )?,
+            FullDensity,
+            final_block_aux_assignment.clone(),
+        );
+
+        let a_aux_density_total = prover.a_aux_density.get_total_density();
+
+        let (a_inputs_source, a_aux_source) = prover
+            .params
+            .get_a(input_assignment.len(), a_aux_density_total)?;
+
+        let a_inputs = multiexp(
+            worker,
+            a_inputs_source,
+            FullDensity,
+            input_assignment.clone(),
+        );
+        let a_aux = multiexp(
+            worker,
+            a_aux_source,
+            Arc::new(mem::take(&mut prover.a_aux_density)),
+            aux_assignment.clone(),
+        );
+
+        let b_input_density = Arc::new(mem::take(&mut prover.b_input_density));
+        let b_input_density_total = b_input_density.get_total_density();
+        let b_aux_density = Arc::new(mem::take(&mut prover.b_aux_density));
+        let b_aux_density_total = b_aux_density.get_total_density();
+
+        // B in G1 is only needed for the r * B term of C.
+        let (b_g1_inputs, b_g1_aux) = if zk {
+            let (b_g1_inputs_source, b_g1_aux_source) = prover
+                .params
+                .get_b_g1(b_input_density_total, b_aux_density_total)?;
+
+            (
+                PendingMsm::new(multiexp(
+                    worker,
+                    b_g1_inputs_source,
+                    b_input_density.clone(),
+                    input_assignment.clone(),
+                )),
+                PendingMsm::new(multiexp(
+                    worker,
+                    b_g1_aux_source,
+                    b_aux_density.clone(),
+                    aux_assignment.clone(),
+                )),
+            )
+        } else {
+            (
+                PendingMsm::ready(E::G1::identity()),
+                PendingMsm::ready(E::G1::identity()),
+            )
+        };
+
+        let (b_g2_inputs_source, b_g2_aux_source) = prover
+            .params
+            .get_b_g2(b_input_density_total, b_aux_density_total)?;
+
+        let b_g2_inputs = multiexp(
+            worker,
+            b_g2_inputs_source,
+            b_input_density,
+            input_assignment,
+        );
+        let b_g2_aux = multiexp(worker, b_g2_aux_source, b_aux_density, aux_assignment);
+
+        end_timer!(t);
+        on_progress(ProofPhase::MsmSetup);
+        let t = start_timer!(|| "pre-msm wait");
+
+        let last = vk.deltas_g1.len() - 1;
+        let mut g_a = vk.deltas_g1[last] * r;
+        AddAssign::<&E::G1Affine>::add_assign(&mut g_a, &vk.alpha_g1);
+        let mut g_b = vk.deltas_g2[last] * s;
+        AddAssign::<&E::G2Affine>::add_assign(&mut g_b, &vk.beta_g2);
+        let mut g_c;
+        {
+            let mut rs = r;
+            rs.mul_assign(&s);
+
+            g_c = vk.deltas_g1[last] * rs;
+            let tf = start_timer!(|| "mirage extra group fold");
+            for (delta_g1, kappa_3) in vk.deltas_g1.iter().zip(&prover.kappa_3s) {
+                AddAssign::<&E::G1>::add_assign(&mut g_c, &(-*delta_g1 * kappa_3));
+            }
+            end_timer!(tf);
+            AddAssign::<&E::G1>::add_assign(&mut g_c, &(vk.alpha_g1 * s));
+            AddAssign::<&E::G1>::add_assign(&mut g_c, &(vk.beta_g1 * r));
+        }
+        end_timer!(t);
+        end_timer!(t_nosynth);
+
+        Ok(ProofBuilder {
+            r,
+            s,
+            g_a,
+            g_b,
+            g_c,
+            a_inputs: PendingMsm::new(a_inputs),
+            a_aux: PendingMsm::new(a_aux),
+            b_g1_inputs,
+            b_g1_aux,
+            b_g2_inputs: PendingMsm::new(b_g2_inputs),
+            b_g2_aux: PendingMsm::new(b_g2_aux),
+            h: PendingMsm::new(h),
+            l: PendingMsm::new(l),
+            pi_ds: mem::take(&mut prover.pi_ds),
+            aux_blocks: mem::take(&mut prover.aux_blocks),
+            transcript_log: prover.transcript.take_log(),
+        })
+    }
+
+    /// The commitments to the aux blocks, which are ready immediately.
+    pub fn ds(&self) -> &[E::G1Affine] {
+        &self.pi_ds
+    }
+
+    /// The messages the prover absorbed into its transcript, in order.
+    pub fn transcript_log(&self) -> &TranscriptLog {
+        &self.transcript_log
+    }
+
+    /// The multiexp of the H query, the largest of the multiexps.
+    pub fn h(&mut self) -> Result<E::G1, SynthesisError> {
+        self.h.wait()
+    }
+
+    /// The proof's `A`, once its multiexps are done.
+    pub fn a(&mut self) -> Result<E::G1Affine, SynthesisError> {
+        Ok((self.g_a + self.a_answer()?).to_affine())
+    }
+
+    /// The proof's `B`, once its multiexps are done.
+    pub fn b(&mut self) -> Result<E::G2Affine, SynthesisError> {
+        let mut b2_answer = self.b_g2_inputs.wait()?;
+        AddAssign::<&E::G2>::add_assign(&mut b2_answer, &self.b_g2_aux.wait()?);
+        Ok((self.g_b + b2_answer).to_affine())
+    }
+
+    fn a_answer(&mut self) -> Result<E::G1, SynthesisError> {
+        let mut a_answer = self.a_inputs.wait()?;
+        AddAssign::<&E::G1>::add_assign(&mut a_answer, &self.a_aux.wait()?);
+        Ok(a_answer)
+    }
+
+    /// Wait for the remaining multiexps and assemble the proof, along with
+    /// the contents of the aux blocks.
+    #[allow(clippy::type_complexity)]
+    pub fn finish(self) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError> {
+        self.finish_with_progress(&mut |_| ())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn finish_with_progress(
+        mut self,
+        on_progress: &mut dyn FnMut(ProofPhase),
+    ) -> Result<(Proof<E>, Vec<Vec<E::Fr>>), SynthesisError> {
+        let t = start_timer!(|| "wait for MSMs and fold");
+        let a = self.a()?;
+        let b = self.b()?;
+
+        let mut g_c = self.g_c;
+        let mut a_answer = self.a_answer()?;
+        MulAssign::<E::Fr>::mul_assign(&mut a_answer, self.s);
+        AddAssign::<&E::G1>::add_assign(&mut g_c, &a_answer);
+
+        let mut b1_answer: E::G1 = self.b_g1_inputs.wait()?;
+        AddAssign::<&E::G1>::add_assign(&mut b1_answer, &self.b_g1_aux.wait()?);
+        MulAssign::<E::Fr>::mul_assign(&mut b1_answer, self.r);
+        AddAssign::<&E::G1>::add_assign(&mut g_c, &b1_answer);
+        AddAssign::<&E::G1>::add_assign(&mut g_c, &self.h.wait()?);
+        AddAssign::<&E::G1>::add_assign(&mut g_c, &self.l.wait()?);
+        end_timer!(t);
+        on_progress(ProofPhase::Fold);
+
+        let proof = Proof {
+            a,
+            b,
             c: g_c.to_affine(),
-            ds: prover.pi_ds,
-        },
-        prover.aux_blocks,
-    ));
-    end_timer!(t_nosynth);
-    r
+            ds: mem::take(&mut self.pi_ds),
+        };
+        on_progress(ProofPhase::Done);
+        Ok((proof, mem::take(&mut self.aux_blocks)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mirage::generate_parameters;
+    use crate::mirage::tests::{DummyEngine, Fr, XorDemo};
+    use crate::multiexp::QueryDensity;
+    use std::marker::PhantomData;
+
+    #[cfg(feature = "zeroize")]
+    struct OneBlock;
+
+    #[cfg(feature = "zeroize")]
+    impl CcCircuit<Fr> for OneBlock {
+        fn synthesize<CS: CcConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || Ok(Fr::from(3)))?;
+            let b = cs.alloc(|| "b", || Ok(Fr::from(5)))?;
+            cs.end_aux_block(|| "block")?;
+            let c = cs.alloc(|| "c", || Ok(Fr::from(15)))?;
+            cs.enforce(|| "a * b = c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn batched_densities() {
+        let circuit = || XorDemo {
+            a: Some(true),
+            b: Some(false),
+            _marker: PhantomData,
+        };
+        let params = generate_parameters::<DummyEngine, _>(
+            circuit(),
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            vec![Fr::from(5481)],
+            Fr::from(3673),
+        )
+        .unwrap();
+        let vk = params.vk.clone();
+        let worker = Worker::new();
+        let (mut source, mut batched_source) = (&params, &params);
+        let transcript = || LoggedTranscript::new(&ProofContext::default());
+
+        let mut prover =
+            ProvingAssignment::new(&mut source, &vk, &worker, vec![], transcript()).unwrap();
+        circuit().synthesize(&mut prover).unwrap();
+        prover.finish_synthesis();
+        let mut batched =
+            ProvingAssignment::new(&mut batched_source, &vk, &worker, vec![], transcript())
+                .unwrap();
+        batched.pending = Some(vec![]);
+        circuit().synthesize(&mut batched).unwrap();
+        assert!(batched.a.is_empty());
+        batched.finish_synthesis();
+        assert!(batched.pending.is_none());
+
+        let bits = |d: &DensityTracker| d.iter().collect::<Vec<_>>();
+        for (d, batched_d) in [
+            (&prover.a_aux_density, &batched.a_aux_density),
+            (&prover.b_input_density, &batched.b_input_density),
+            (&prover.b_aux_density, &batched.b_aux_density),
+        ] {
+            assert_eq!(bits(d), bits(batched_d));
+        }
+        // The same densities that size the A and B queries in
+        // `test_xordemo`.
+        assert_eq!(prover.a_aux_density.get_total_density(), 2);
+        assert_eq!(prover.b_input_density.get_total_density(), 0);
+        assert_eq!(prover.b_aux_density.get_total_density(), 2);
+
+        assert_eq!(prover.a.len(), 5);
+        assert_eq!(prover.a, batched.a);
+        assert_eq!(prover.b, batched.b);
+        assert_eq!(prover.c, batched.c);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_proving_assignment() {
+        let params = generate_parameters::<DummyEngine, _>(
+            OneBlock,
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            vec![Fr::from(2), Fr::from(5481)],
+            Fr::from(3673),
+        )
+        .unwrap();
+        let vk = params.vk.clone();
+        let worker = Worker::new();
+        let mut source = &params;
+        let mut prover = ProvingAssignment::new(
+            &mut source,
+            &vk,
+            &worker,
+            vec![Fr::from(7)],
+            LoggedTranscript::new(&ProofContext::default()),
+        )
+        .unwrap();
+        OneBlock.synthesize(&mut prover).unwrap();
+        assert_eq!(prover.aux_blocks, [[Fr::from(3), Fr::from(5)]]);
+
+        prover.zeroize();
+        let is_zero = |s: &Fr| bool::from(s.is_zero());
+        assert!(prover.aux_assignment.iter().all(is_zero));
+        assert!(prover.input_assignment.iter().all(is_zero));
+        assert!(prover.kappa_3s.iter().all(is_zero));
+        assert!(prover.aux_blocks.iter().flatten().all(is_zero));
+        for evals in [&prover.a, &prover.b, &prover.c] {
+            assert!(!evals.is_empty());
+            assert!(evals.iter().all(is_zero));
+        }
+    }
 }
@@ -470,18 +470,18 @@ impl GroupEncoding for Fr {
 }
 
 impl UncompressedEncoding for Fr {
-    type Uncompressed = FakePoint;
+    type Uncompressed = FrRepr;
 
-    fn from_uncompressed(_bytes: &Self::Uncompressed) -> CtOption<Self> {
-        unimplemented!()
+    fn from_uncompressed(bytes: &Self::Uncompressed) -> CtOption<Self> {
+        Self::from_uncompressed_unchecked(bytes)
     }
 
-    fn from_uncompressed_unchecked(_bytes: &Self::Uncompressed) -> CtOption<Self> {
-        unimplemented!()
+    fn from_uncompressed_unchecked(bytes: &Self::Uncompressed) -> CtOption<Self> {
+        Fr::from_repr(*bytes)
     }
 
     fn to_uncompressed(&self) -> Self::Uncompressed {
-        FakePoint
+        self.to_repr()
     }
 }
 
@@ -7,14 +7,18 @@ use std::marker::PhantomData;
 use std::ops::{AddAssign, MulAssign, SubAssign};
 
 use crate::cc::{CcCircuit, CcConstraintSystem};
-use crate::SynthesisError;
-
-use super::{create_proof, generate_parameters, prepare_verifying_key, verify_proof};
-
-struct XorDemo<Scalar: PrimeField> {
-    a: Option<bool>,
-    b: Option<bool>,
-    _marker: PhantomData<Scalar>,
+use crate::{SynthesisError, VerificationError};
+
+use super::{
+    create_proof, create_proof_with_context, generate_parameters, prepare_verifying_key,
+    verify_proof, verify_proof_folded, verify_proof_with_active_blocks, verify_proof_with_context,
+    verify_proof_with_prepared_inputs, ProofContext,
+};
+
+pub(super) struct XorDemo<Scalar: PrimeField> {
+    pub(super) a: Option<bool>,
+    pub(super) b: Option<bool>,
+    pub(super) _marker: PhantomData<Scalar>,
 }
 
 impl<Scalar: PrimeField> CcCircuit<Scalar> for XorDemo<Scalar> {
@@ -297,7 +301,7 @@ fn test_xordemo() {
             _marker: PhantomData,
         };
 
-        create_proof(c, &params, r, s, vec![]).unwrap()
+        create_proof(c, &params, r, s, vec![]).unwrap().0
     };
 
     // A(x) =
@@ -389,6 +393,479 @@ fn test_xordemo() {
     assert!(verify_proof(&pvk, &proof, &[Fr::one()]).is_ok());
 }
 
+#[test]
+fn test_xordemo_h_coeffs() {
+    use super::compute_h_coeffs;
+    use crate::multicore::Worker;
+
+    // The values of A, B and C at each constraint of `test_xordemo`, for
+    // a_0 = 1, a_1 = 1, a_2 = 1, a_3 = 0.
+    let a = [0, 1, 2, 1, 1];
+    let b = [1, 0, 0, 0, 0];
+    let c = [0, 0, 0, 0, 0];
+    let to_fr = |v: &[u64]| v.iter().map(|v| Fr::from(*v)).collect::<Vec<_>>();
+
+    let h = compute_h_coeffs(to_fr(&a), to_fr(&b), to_fr(&c), &Worker::new()).unwrap();
+
+    // The H query answer of `test_xordemo`.
+    assert_eq!(h, to_fr(&[5040, 11763, 10755, 63633, 128, 9747, 8739]));
+}
+
+#[test]
+fn test_xordemo_timing_spans() {
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+
+    let guard = crate::trace::start_collecting();
+    create_proof(
+        XorDemo {
+            a: Some(true),
+            b: Some(false),
+            _marker: PhantomData,
+        },
+        &params,
+        Fr::from(27134),
+        Fr::from(17146),
+        vec![],
+    )
+    .unwrap();
+    drop(guard);
+
+    let spans = crate::trace::take_spans();
+    let msgs = |spans: &[crate::trace::TimingNode]| -> Vec<String> {
+        spans.iter().map(|s| s.msg.clone()).collect()
+    };
+    assert_eq!(
+        msgs(&spans),
+        ["synthesis", "post-synth", "wait for MSMs and fold"]
+    );
+    let post_synth = &spans[1].children;
+    assert_eq!(msgs(post_synth), ["h commit", "msm setup", "pre-msm wait"]);
+    assert_eq!(msgs(&post_synth[0].children), ["h coeffs"]);
+    assert!(post_synth[1].children.is_empty());
+}
+
+#[test]
+fn test_xordemo_proof_builder() {
+    use super::ProofBuilder;
+
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let circuit = || XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+
+    let (proof, _) = create_proof(circuit(), &params, r, s, vec![]).unwrap();
+
+    // The pieces can be taken in any order, and match the finished proof.
+    let mut builder = ProofBuilder::new(circuit(), &params, r, s, vec![]).unwrap();
+    assert!(builder.ds().is_empty());
+    builder.h().unwrap();
+    assert_eq!(builder.b().unwrap(), proof.b);
+    assert_eq!(builder.a().unwrap(), proof.a);
+    assert_eq!(builder.a().unwrap(), proof.a);
+    let (built, _) = builder.finish().unwrap();
+    assert!(built == proof);
+}
+
+#[test]
+fn test_proof_builder_keeps_msm_error() {
+    use super::ProofBuilder;
+    use std::io;
+    use std::sync::Arc;
+
+    let mut params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    // An H query with no bases fails the H multiexp.
+    params.h = Arc::new(vec![]);
+    let circuit = XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+
+    let mut builder =
+        ProofBuilder::new(circuit, &params, Fr::from(27134), Fr::from(17146), vec![]).unwrap();
+    // The other pieces don't need H.
+    builder.a().unwrap();
+    builder.b().unwrap();
+    for _ in 0..2 {
+        match builder.h() {
+            Err(SynthesisError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+    assert!(matches!(builder.finish(), Err(SynthesisError::IoError(_))));
+}
+
+#[test]
+fn test_xordemo_opts() {
+    use super::create_proof_opts;
+
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let circuit = || XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+
+    let (zk, _) = create_proof_opts(circuit(), &params, r, s, vec![], true).unwrap();
+    let (plain, _) = create_proof(circuit(), &params, r, s, vec![]).unwrap();
+    assert!(zk == plain);
+    verify_proof(&pvk, &zk, &[Fr::one()]).unwrap();
+
+    // Without zero-knowledge, r and s are ignored.
+    let (non_zk, _) = create_proof_opts(circuit(), &params, r, s, vec![], false).unwrap();
+    let (unblinded, _) = create_proof(circuit(), &params, Fr::zero(), Fr::zero(), vec![]).unwrap();
+    assert!(non_zk == unblinded);
+    assert!(non_zk != zk);
+    verify_proof(&pvk, &non_zk, &[Fr::one()]).unwrap();
+    assert!(verify_proof(&pvk, &non_zk, &[Fr::zero()]).is_err());
+}
+
+#[test]
+fn test_xordemo_batched() {
+    use super::create_proof_batched;
+
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let circuit = |a, b| XorDemo {
+        a: Some(a),
+        b: Some(b),
+        _marker: PhantomData,
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+
+    for (a, b) in [(true, false), (true, true), (false, false)] {
+        let (batched, _) = create_proof_batched(circuit(a, b), &params, r, s, vec![]).unwrap();
+        let (plain, _) = create_proof(circuit(a, b), &params, r, s, vec![]).unwrap();
+        assert!(batched == plain);
+        verify_proof(&pvk, &batched, &[Fr::from((a ^ b) as u64)]).unwrap();
+    }
+}
+
+#[test]
+fn test_xordemo_params_serialization() {
+    use super::Parameters;
+
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+
+    let mut v = vec![];
+    params.write(&mut v).unwrap();
+    let de_params = Parameters::<DummyEngine>::read(&v[..], true).unwrap();
+    assert!(params == de_params);
+    assert!(Parameters::<DummyEngine>::read(&v[..v.len() - 1], true).is_err());
+
+    let circuit = || XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+    let (proof, _) = create_proof(circuit(), &params, r, s, vec![]).unwrap();
+    let (de_proof, _) = create_proof(circuit(), &de_params, r, s, vec![]).unwrap();
+    assert!(proof == de_proof);
+
+    let pvk = prepare_verifying_key(&de_params.vk);
+    verify_proof(&pvk, &de_proof, &[Fr::one()]).unwrap();
+    assert!(verify_proof(&pvk, &de_proof, &[Fr::zero()]).is_err());
+}
+
+#[test]
+fn test_xordemo_progress() {
+    use super::{create_proof_with_progress, ProofPhase};
+
+    let params = generate_parameters::<DummyEngine, _>(
+        XorDemo {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let circuit = || XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+
+    let mut phases = vec![];
+    let (proof, _) =
+        create_proof_with_progress(circuit(), &params, r, s, vec![], |phase| phases.push(phase))
+            .unwrap();
+    assert_eq!(
+        phases,
+        [
+            ProofPhase::Synthesis,
+            ProofPhase::HCommit,
+            ProofPhase::MsmSetup,
+            ProofPhase::Fold,
+            ProofPhase::Done
+        ]
+    );
+
+    let (plain, _) = create_proof(circuit(), &params, r, s, vec![]).unwrap();
+    assert!(proof == plain);
+
+    // A key with an identity delta is rejected before synthesis.
+    let mut subverted = params;
+    subverted.vk.deltas_g1[0] = group::prime::PrimeCurveAffine::identity();
+    let mut phases = vec![];
+    let result = create_proof_with_progress(circuit(), &subverted, r, s, vec![], |phase| {
+        phases.push(phase)
+    });
+    assert!(matches!(result, Err(SynthesisError::UnexpectedIdentity)));
+    assert!(phases.is_empty());
+}
+
+#[test]
+fn test_xordemo_with_witness() {
+    use super::create_proof_with_witness;
+    use crate::cc::WitnessMap;
+    use crate::{Index, Variable};
+
+    let structure = || XorDemo::<Fr> {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    };
+    let params = generate_parameters::<DummyEngine, _>(
+        structure(),
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+
+    // a, b | c
+    let mut witness = WitnessMap::new();
+    witness.insert(Variable::new_unchecked(Index::Aux(0)), Fr::one());
+    witness.insert(Variable::new_unchecked(Index::Aux(1)), Fr::zero());
+    witness.insert(Variable::new_unchecked(Index::Input(1)), Fr::one());
+
+    let (proof, _) =
+        create_proof_with_witness(structure(), &witness, &params, r, s, vec![]).unwrap();
+    assert!(verify_proof(&pvk, &proof, &[Fr::one()]).is_ok());
+
+    // The same proof as with the witness inline.
+    let (inline, _) = create_proof(
+        XorDemo {
+            a: Some(true),
+            b: Some(false),
+            _marker: PhantomData,
+        },
+        &params,
+        r,
+        s,
+        vec![],
+    )
+    .unwrap();
+    assert!(proof == inline);
+
+    let mut partial = WitnessMap::new();
+    partial.insert(Variable::new_unchecked(Index::Aux(0)), Fr::one());
+    assert!(matches!(
+        create_proof_with_witness(structure(), &partial, &params, r, s, vec![]),
+        Err(SynthesisError::AssignmentMissing)
+    ));
+}
+
+#[cfg(feature = "r1cs")]
+#[test]
+fn test_xordemo_r1cs_export() {
+    use crate::r1cs::{R1csExtractor, R1csJson};
+
+    let extractor = R1csExtractor::<Fr>::from_circuit(XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    })
+    .unwrap();
+    let witness = extractor.witness().unwrap();
+
+    let mut json = vec![];
+    extractor.write_json(&mut json).unwrap();
+    let r1cs = R1csJson::read(&json[..]).unwrap();
+
+    // one, c | a, b
+    assert_eq!(r1cs.n_vars, 4);
+    assert_eq!(r1cs.n_pub_inputs, 1);
+    assert_eq!(r1cs.n_prv_inputs, 2);
+    assert_eq!(r1cs.n_constraints, 3);
+    assert!(r1cs.is_satisfied(&witness).unwrap());
+
+    // c = 0 is not a valid XOR of (true, false)
+    let mut bad_witness = witness;
+    bad_witness[1] = Fr::zero();
+    assert!(!r1cs.is_satisfied(&bad_witness).unwrap());
+}
+
+#[test]
+fn test_xordemo_domain_exp() {
+    use crate::cc::synthesize_count;
+    use crate::domain::required_domain_exp;
+
+    let stats = synthesize_count::<Fr, _>(XorDemo {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    })
+    .unwrap();
+
+    // 3 constraints, plus one for each of the 2 inputs
+    let num_constraints = stats.num_constraints + stats.num_inputs;
+    assert_eq!(num_constraints, 5);
+    assert_eq!(required_domain_exp(num_constraints), 3);
+}
+
+#[test]
+fn test_xordemo_count() {
+    use crate::cc::{synthesize_count, CircuitStats};
+
+    let stats = synthesize_count::<Fr, _>(XorDemo {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    })
+    .unwrap();
+    assert_eq!(
+        stats,
+        CircuitStats {
+            num_inputs: 2,
+            num_coins: 0,
+            num_aux: 2,
+            num_constraints: 3,
+            num_aux_blocks: 0,
+        }
+    );
+}
+
+#[test]
+fn test_xordemo_memory_estimate() {
+    use crate::cc::{estimate_proving_memory, synthesize_count, CircuitStats};
+    use std::mem::size_of;
+
+    let stats = synthesize_count::<Fr, _>(XorDemo {
+        a: None,
+        b: None,
+        _marker: PhantomData,
+    })
+    .unwrap();
+    let estimate = estimate_proving_memory::<DummyEngine>(&stats);
+
+    // At least the A, B and C evaluations over the 8-point domain, and at
+    // most a few bytes per element for a circuit this small.
+    assert!(estimate >= 3 * 8 * size_of::<Fr>());
+    assert!(estimate <= 1024);
+
+    // Past the domain size, the estimate grows with the circuit.
+    let bigger = CircuitStats {
+        num_constraints: 7,
+        ..stats
+    };
+    assert!(estimate_proving_memory::<DummyEngine>(&bigger) > estimate);
+}
+
 struct MultWithZeroCoeffs<F> {
     a: Option<F>,
     b: Option<F>,
@@ -446,7 +923,7 @@ fn zero_coeff_test(one_var: bool) {
             .unwrap();
     let r = Fr::from(27134);
     let s = Fr::from(17146);
-    let pf = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
     let pvk = prepare_verifying_key(&pk.vk);
     verify_proof(&pvk, &pf, &[]).unwrap();
 }
@@ -461,6 +938,56 @@ fn zero_coeff_non_one_var() {
     zero_coeff_test(false);
 }
 
+#[test]
+fn cancelled_terms() {
+    // a * b = c, where a's terms in B cancel out, so a is not in the B query.
+    struct Mult<F> {
+        a: Option<F>,
+        b: Option<F>,
+        c: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Mult<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || Ok(self.a.unwrap()))?;
+            let b = cs.alloc(|| "b", || Ok(self.b.unwrap()))?;
+            let c = cs.alloc(|| "c", || Ok(self.c.unwrap()))?;
+            cs.enforce(
+                || "cs",
+                |z| z + a,
+                |z| z + (F::from(2), a) + b - (F::from(2), a),
+                |z| z + c,
+            );
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let m = Mult {
+        a: Some(Fr::from(5)),
+        b: Some(Fr::from(6)),
+        c: Some(Fr::from(30)),
+    };
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk =
+        generate_parameters::<DummyEngine, _>(&m, g1, g2, alpha, beta, gamma, vec![delta], tau)
+            .unwrap();
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+    verify_proof(&pvk, &pf, &[]).unwrap();
+}
+
 #[test]
 fn coin1() {
     struct Coin1<F> {
@@ -543,8 +1070,127 @@ fn coin1() {
     let r = Fr::from(27134);
     let s = Fr::from(17146);
     let k = vec![Fr::from(1)];
-    let pf = create_proof(&m, &pk, r, s, k).unwrap();
+    let (pf, _) = create_proof(&m, &pk, r, s, k).unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+    verify_proof(&pvk, &pf, &[]).unwrap();
+}
+
+#[test]
+fn test_coin_domain_separator() {
+    // Commits to x in a block, then draws a coin r and multiplies it by x.
+    struct CoinAfterBlock<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &CoinAfterBlock<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let (r, val_r) = cs.alloc_random(|| "r")?;
+            let r = AllocatedNum {
+                variable: r,
+                value: val_r,
+            };
+            r.mul(cs.namespace(|| "r*x"), &x)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &CoinAfterBlock { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = CoinAfterBlock {
+        x: Some(Fr::from(3)),
+    };
+    let (r, s, k) = (Fr::from(27134), Fr::from(17146), vec![Fr::from(1)]);
+    let (pf, _) = create_proof(&m, &pk, r, s, k.clone()).unwrap();
+    verify_proof(&pvk, &pf, &[]).unwrap();
+
+    // The coin is bound to the domain separator.
+    let ctx = ProofContext {
+        domain_sep: b"another protocol",
+        ..ProofContext::default()
+    };
+    verify_proof_with_context(&pvk, &pf, &[], &ProofContext::default()).unwrap();
+    assert!(verify_proof_with_context(&pvk, &pf, &[], &ctx).is_err());
+    let (pf, _) = create_proof_with_context(&m, &pk, r, s, k, &ctx).unwrap();
+    verify_proof_with_context(&pvk, &pf, &[], &ctx).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[]).is_err());
+}
+
+#[test]
+fn test_coin_context_data() {
+    // Commits to x in a block, then draws a coin r and multiplies it by x.
+    struct CoinAfterBlock<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &CoinAfterBlock<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let (r, val_r) = cs.alloc_random(|| "r")?;
+            let r = AllocatedNum {
+                variable: r,
+                value: val_r,
+            };
+            r.mul(cs.namespace(|| "r*x"), &x)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &CoinAfterBlock { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482)],
+        Fr::from(3673),
+    )
+    .unwrap();
     let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = CoinAfterBlock {
+        x: Some(Fr::from(3)),
+    };
+    let (r, s, k) = (Fr::from(27134), Fr::from(17146), vec![Fr::from(1)]);
+    let (pf, _) = create_proof(&m, &pk, r, s, k.clone()).unwrap();
+    verify_proof(&pvk, &pf, &[]).unwrap();
+
+    // The coin is bound to the context data.
+    let session = ProofContext::default().with_data(b"session 1");
+    let (pf, _) = create_proof_with_context(&m, &pk, r, s, k.clone(), &session).unwrap();
+    verify_proof_with_context(&pvk, &pf, &[], &session).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[]).is_err());
+    let other_session = ProofContext::default().with_data(b"session 2");
+    assert!(verify_proof_with_context(&pvk, &pf, &[], &other_session).is_err());
+    // Empty data is the same as none.
+    let empty = ProofContext::default().with_data(b"");
+    let (pf, _) = create_proof_with_context(&m, &pk, r, s, k, &empty).unwrap();
     verify_proof(&pvk, &pf, &[]).unwrap();
 }
 
@@ -633,7 +1279,1119 @@ fn test_3blocks_2coins() {
     let r = Fr::from(27134);
     let s = Fr::from(17146);
     let k = vec![Fr::from(1), Fr::from(15), Fr::from(5)];
-    let pf = create_proof(&m, &pk, r, s, k).unwrap();
+    let (pf, _) = create_proof(&m, &pk, r, s, k).unwrap();
     let pvk = prepare_verifying_key(&pk.vk);
     verify_proof(&pvk, &pf, &[Fr::from(1)]).unwrap();
 }
+
+#[test]
+fn test_transcript_counts() {
+    // Exposes a, commits to a and b in separate blocks, then draws a coin r
+    // and commits to r * b, and draws another coin t for t * a.
+    struct Interleaved<F> {
+        a: Option<F>,
+        b: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Interleaved<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let coin = |cs: &mut CS, name: &'static str| {
+                let (variable, value) = cs.alloc_random(|| name)?;
+                Ok::<_, SynthesisError>(AllocatedNum { variable, value })
+            };
+            let a = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(*self.a.get()?))?;
+            a.inputize(cs.namespace(|| "input a"))?;
+            cs.end_aux_block(|| "a")?;
+            let b = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(*self.b.get()?))?;
+            cs.end_aux_block(|| "b")?;
+            let r = coin(cs, "r")?;
+            r.mul(cs.namespace(|| "r*b"), &b)?;
+            cs.end_aux_block(|| "r*b")?;
+            let t = coin(cs, "t")?;
+            t.mul(cs.namespace(|| "t*a"), &a)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            3
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Interleaved { a: None, b: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![
+            Fr::from(5481),
+            Fr::from(5482),
+            Fr::from(5483),
+            Fr::from(5484),
+        ],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    {
+        use super::TranscriptEntry::*;
+        assert_eq!(
+            pk.vk.transcript,
+            [PublicInput, AuxCommit, AuxCommit, Coin, AuxCommit, Coin]
+        );
+    }
+    let vk = &pk.vk;
+    assert_eq!(
+        (vk.num_public_inputs(), vk.num_coins(), vk.num_aux_commits()),
+        (1, 2, 3)
+    );
+    assert_eq!(
+        (
+            pvk.num_public_inputs(),
+            pvk.num_coins(),
+            pvk.num_aux_commits()
+        ),
+        (1, 2, 3)
+    );
+
+    let m = Interleaved {
+        a: Some(Fr::from(2)),
+        b: Some(Fr::from(3)),
+    };
+    let k = vec![Fr::from(1), Fr::from(15), Fr::from(5)];
+    let (pf, _) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), k).unwrap();
+    assert_eq!(pvk.num_aux_commits(), pf.ds.len());
+    verify_proof(&pvk, &pf, &[Fr::from(2)]).unwrap();
+}
+
+#[test]
+fn test_range_check() {
+    struct RangeCheck<F> {
+        x: Option<F>,
+        bits: usize,
+    }
+
+    impl<F: ff::PrimeFieldBits> CcCircuit<F> for &RangeCheck<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            x.inputize(cs.namespace(|| "input x"))?;
+            x.decompose_bits(cs.namespace(|| "range"), self.bits)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &RangeCheck { x: None, bits: 8 },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let m = RangeCheck {
+        x: Some(Fr::from(200)),
+        bits: 8,
+    };
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(200)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(201)]).is_err());
+
+    // 300 doesn't fit in 8 bits, so the circuit is unsatisfied.
+    let m = RangeCheck {
+        x: Some(Fr::from(300)),
+        bits: 8,
+    };
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(300)]).is_err());
+}
+
+#[test]
+fn test_pack_bits() {
+    // Packs bits into a number, exposes it, and decomposes it back into the
+    // same bits.
+    struct Pack {
+        bits: Vec<Option<bool>>,
+    }
+
+    impl<F: ff::PrimeFieldBits> CcCircuit<F> for &Pack {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::boolean::{AllocatedBit, Boolean};
+            use crate::gadgets::num::AllocatedNum;
+            let bits = self
+                .bits
+                .iter()
+                .enumerate()
+                .map(|(i, b)| AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), *b))
+                .collect::<Result<Vec<_>, _>>()?;
+            let x = AllocatedNum::from_bits_le(cs.namespace(|| "pack"), &bits)?;
+            x.inputize(cs.namespace(|| "input x"))?;
+            let unpacked = x.decompose_bits(cs.namespace(|| "unpack"), bits.len())?;
+            for (i, (a, b)) in bits.into_iter().zip(unpacked).enumerate() {
+                Boolean::enforce_equal(
+                    cs.namespace(|| format!("round trip {}", i)),
+                    &a.into(),
+                    &b.into(),
+                )?;
+            }
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Pack {
+            bits: vec![None; 8],
+        },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    for x in [0u64, 1, 0b1010_0101, 200, 255] {
+        let m = Pack {
+            bits: (0..8).map(|i| Some((x >> i) & 1 == 1)).collect(),
+        };
+        let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+        verify_proof(&pvk, &pf, &[Fr::from(x)]).unwrap();
+        assert!(verify_proof(&pvk, &pf, &[Fr::from(x + 1)]).is_err());
+    }
+}
+
+#[test]
+fn test_conditionally_select() {
+    struct Select<F> {
+        a: Option<F>,
+        b: Option<F>,
+        condition: Option<bool>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Select<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::boolean::AllocatedBit;
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let a = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(*self.a.get()?))?;
+            let b = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(*self.b.get()?))?;
+            let condition = AllocatedBit::alloc(cs.namespace(|| "condition"), self.condition)?;
+            let selected =
+                AllocatedNum::conditionally_select(cs.namespace(|| "select"), &a, &b, &condition)?;
+            selected.inputize(cs.namespace(|| "input selected"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Select {
+            a: None,
+            b: None,
+            condition: None,
+        },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    for (condition, expected, other) in [(true, 7, 11), (false, 11, 7)] {
+        let m = Select {
+            a: Some(Fr::from(7)),
+            b: Some(Fr::from(11)),
+            condition: Some(condition),
+        };
+        let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+        verify_proof(&pvk, &pf, &[Fr::from(expected)]).unwrap();
+        assert!(verify_proof(&pvk, &pf, &[Fr::from(other)]).is_err());
+    }
+}
+
+#[test]
+fn test_division() {
+    // Exposes a / b and 1 / b.
+    struct Div<F> {
+        a: Option<F>,
+        b: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Div<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let a = AllocatedNum::alloc(cs.namespace(|| "a"), || Ok(*self.a.get()?))?;
+            let b = AllocatedNum::alloc(cs.namespace(|| "b"), || Ok(*self.b.get()?))?;
+            let q = a.div(cs.namespace(|| "a / b"), &b)?;
+            q.inputize(cs.namespace(|| "input quotient"))?;
+            let b_inv = b.invert(cs.namespace(|| "1 / b"))?;
+            b_inv.inputize(cs.namespace(|| "input inverse"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Div { a: None, b: None },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let m = Div {
+        a: Some(Fr::from(84)),
+        b: Some(Fr::from(12)),
+    };
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    let b_inv = Fr::from(12).invert().unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(7), b_inv]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(8), b_inv]).is_err());
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(7), Fr::from(12)]).is_err());
+
+    let m = Div {
+        a: Some(Fr::from(84)),
+        b: Some(Fr::zero()),
+    };
+    assert!(matches!(
+        create_proof(&m, &pk, r, s, vec![]),
+        Err(SynthesisError::DivisionByZero)
+    ));
+}
+
+#[test]
+fn test_boolean_gates() {
+    // Exposes a XOR b, a AND b and a OR b.
+    struct Gates {
+        a: Option<bool>,
+        b: Option<bool>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Gates {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::boolean::{AllocatedBit, Boolean};
+            use crate::gadgets::Assignment;
+            let a = Boolean::from(AllocatedBit::alloc(cs.namespace(|| "a"), self.a)?);
+            let b = Boolean::from(AllocatedBit::alloc(cs.namespace(|| "b"), self.b)?);
+            let gates = [
+                Boolean::xor(cs.namespace(|| "xor"), &a, &b)?,
+                Boolean::and(cs.namespace(|| "and"), &a, &b)?,
+                Boolean::or(cs.namespace(|| "or"), &a, &b)?,
+            ];
+            for (i, gate) in gates.iter().enumerate() {
+                let input = cs.alloc_input(
+                    || format!("input {}", i),
+                    || {
+                        Ok(if *gate.get_value().get()? {
+                            F::one()
+                        } else {
+                            F::zero()
+                        })
+                    },
+                )?;
+                cs.enforce(
+                    || format!("expose {}", i),
+                    |_| gate.lc(CS::one(), F::one()),
+                    |lc| lc + CS::one(),
+                    |lc| lc + input,
+                );
+            }
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Gates { a: None, b: None },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let bit = |b: bool| if b { Fr::one() } else { Fr::zero() };
+    for a in [false, true] {
+        for b in [false, true] {
+            let m = Gates {
+                a: Some(a),
+                b: Some(b),
+            };
+            let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+            let mut inputs = [bit(a ^ b), bit(a & b), bit(a | b)];
+            verify_proof(&pvk, &pf, &inputs).unwrap();
+            for i in 0..inputs.len() {
+                inputs[i] = Fr::one() - inputs[i];
+                assert!(verify_proof(&pvk, &pf, &inputs).is_err());
+                inputs[i] = Fr::one() - inputs[i];
+            }
+        }
+    }
+}
+
+#[test]
+fn test_assert_equal() {
+    // Exposes x, and constrains it to equal y.
+    struct Eq<F> {
+        x: Option<F>,
+        y: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Eq<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            let y = AllocatedNum::alloc(cs.namespace(|| "y"), || Ok(*self.y.get()?))?;
+            x.assert_equal(cs.namespace(|| "x = y"), &y)?;
+            x.assert_nonzero(cs.namespace(|| "x != 0"))?;
+            x.inputize(cs.namespace(|| "input x"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Eq { x: None, y: None },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let m = Eq {
+        x: Some(Fr::from(5)),
+        y: Some(Fr::from(5)),
+    };
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(5)]).unwrap();
+
+    // Unequal witnesses give a proof for no instance.
+    let m = Eq {
+        x: Some(Fr::from(5)),
+        y: Some(Fr::from(6)),
+    };
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(5)]).is_err());
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(6)]).is_err());
+
+    // A zero witness has no inverse to allocate.
+    let m = Eq {
+        x: Some(Fr::zero()),
+        y: Some(Fr::zero()),
+    };
+    assert!(matches!(
+        create_proof(&m, &pk, r, s, vec![]),
+        Err(SynthesisError::DivisionByZero)
+    ));
+}
+
+#[test]
+fn test_composed_circuit() {
+    use crate::cc::ComposedCircuit;
+
+    // Commits to x in a block, and exposes x^2.
+    struct Square<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Square<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = x.square(cs.namespace(|| "x^2"))?;
+            y.inputize(cs.namespace(|| "input y"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let deltas = vec![Fr::from(5481), Fr::from(5482), Fr::from(5483)];
+    let tau = Fr::from(3673);
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+
+    // Two circuits with blocks.
+    let (sq1, sq2) = (
+        Square {
+            x: Some(Fr::from(3)),
+        },
+        Square {
+            x: Some(Fr::from(5)),
+        },
+    );
+    let composed = ComposedCircuit::new(&sq1, &sq2);
+    assert_eq!(composed.num_aux_blocks(), 2);
+    let pk = generate_parameters::<DummyEngine, _>(
+        ComposedCircuit::new(&Square { x: None }, &Square { x: None }),
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        deltas.clone(),
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+    let k = vec![Fr::from(1), Fr::from(15)];
+    let (pf, _) = create_proof(composed, &pk, r, s, k).unwrap();
+    assert_eq!(pf.ds.len(), 2);
+    verify_proof(&pvk, &pf, &[Fr::from(9), Fr::from(25)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(25), Fr::from(9)]).is_err());
+
+    // A circuit without blocks followed by one with a block.
+    let pk = generate_parameters::<DummyEngine, _>(
+        ComposedCircuit::new(
+            XorDemo::<Fr> {
+                a: None,
+                b: None,
+                _marker: PhantomData,
+            },
+            &Square { x: None },
+        ),
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        deltas[..2].to_vec(),
+        tau,
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+    let composed = ComposedCircuit::new(
+        XorDemo {
+            a: Some(true),
+            b: Some(false),
+            _marker: PhantomData,
+        },
+        &sq2,
+    );
+    let (pf, _) = create_proof(composed, &pk, r, s, vec![Fr::from(1)]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::one(), Fr::from(25)]).unwrap();
+}
+
+#[test]
+fn test_alloc_vec() {
+    struct Sum<F> {
+        xs: Vec<Option<F>>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Sum<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::Assignment;
+            let xs = cs.alloc_vec("x", self.xs.iter().map(|x| Ok(*x.get()?)))?;
+            let sum = cs.alloc_input(
+                || "sum",
+                || {
+                    self.xs
+                        .iter()
+                        .try_fold(F::zero(), |acc, x| Ok(acc + x.get()?))
+                },
+            )?;
+            cs.enforce(
+                || "sum",
+                |lc| xs.iter().fold(lc, |lc, x| lc + *x),
+                |lc| lc + CS::one(),
+                |lc| lc + sum,
+            );
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let g1 = Fr::one();
+    let g2 = Fr::one();
+    let alpha = Fr::from(48577);
+    let beta = Fr::from(22580);
+    let gamma = Fr::from(53332);
+    let delta = Fr::from(5481);
+    let tau = Fr::from(3673);
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Sum {
+            xs: vec![None; 100],
+        },
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        vec![delta],
+        tau,
+    )
+    .unwrap();
+    assert_eq!(pk.ls.last().unwrap().len(), 100);
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = Sum {
+        xs: (0..100).map(|i| Some(Fr::from(i))).collect(),
+    };
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let (pf, _) = create_proof(&m, &pk, r, s, vec![]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(4950)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(4951)]).is_err());
+}
+
+#[test]
+fn test_aux_block_count_mismatch() {
+    // Ends one block, but declares `declared` blocks.
+    struct OneBlock<F> {
+        x: Option<F>,
+        declared: usize,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &OneBlock<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            assert_eq!(cs.current_aux_block(), 0);
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            assert_eq!(cs.current_aux_block(), 1);
+            x.inputize(cs.namespace(|| "input x"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            self.declared
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &OneBlock {
+            x: None,
+            declared: 1,
+        },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let r = Fr::from(27134);
+    let s = Fr::from(17146);
+    let m = |declared| OneBlock {
+        x: Some(Fr::from(3)),
+        declared,
+    };
+    let (pf, _) = create_proof(&m(1), &pk, r, s, vec![Fr::from(1)]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(3)]).unwrap();
+
+    for (declared, k) in [(0, vec![]), (2, vec![Fr::from(1), Fr::from(15)])] {
+        match create_proof(&m(declared), &pk, r, s, k) {
+            Err(SynthesisError::AuxBlockCountMismatch) => {}
+            _ => panic!("expected an aux block count mismatch"),
+        }
+    }
+
+    // Parameter generation checks the count too.
+    for declared in [0, 2] {
+        let deltas = vec![Fr::from(5481); declared + 1];
+        match generate_parameters::<DummyEngine, _>(
+            &OneBlock { x: None, declared },
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            deltas,
+            Fr::from(3673),
+        ) {
+            Err(SynthesisError::AuxBlockCountMismatch) => {}
+            _ => panic!("expected an aux block count mismatch"),
+        }
+    }
+
+    // The deltas must match the declared count, with one for the rest.
+    for n in [0, 1, 3] {
+        match generate_parameters::<DummyEngine, _>(
+            &OneBlock {
+                x: None,
+                declared: 1,
+            },
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            vec![Fr::from(5481); n],
+            Fr::from(3673),
+        ) {
+            Err(SynthesisError::DeltaCountMismatch) => {}
+            _ => panic!("expected a delta count mismatch"),
+        }
+    }
+}
+
+#[test]
+fn test_empty_aux_block() {
+    // Ends a block before allocating anything in it, and leaves nothing for
+    // the rest of the witness.
+    struct EmptyFirst<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &EmptyFirst<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            cs.end_aux_block(|| "empty")?;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            x.inputize(cs.namespace(|| "input x"))
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            2
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &EmptyFirst { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482), Fr::from(5483)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    assert!(pk.ls[0].is_empty());
+    assert_eq!(pk.ls[1].len(), 1);
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = EmptyFirst {
+        x: Some(Fr::from(3)),
+    };
+    let k = vec![Fr::from(7), Fr::from(11)];
+    let (pf, aux_blocks) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), k).unwrap();
+    assert!(aux_blocks[0].is_empty());
+    verify_proof(&pvk, &pf, &[Fr::from(3)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(4)]).is_err());
+}
+
+#[test]
+fn test_rerandomize_deltas() {
+    // Commits to x in a block, and exposes x^2.
+    struct Square<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Square<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = x.square(cs.namespace(|| "x^2"))?;
+            y.inputize(cs.namespace(|| "input y"))
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let params = |deltas| {
+        generate_parameters::<DummyEngine, _>(
+            &Square { x: None },
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            deltas,
+            Fr::from(3673),
+        )
+        .unwrap()
+    };
+    let old = params(vec![Fr::from(5481), Fr::from(5482)]);
+    let mut rotated = params(vec![Fr::from(5481), Fr::from(5482)]);
+    rotated
+        .rerandomize_deltas(&[Fr::from(2), Fr::from(3)])
+        .unwrap();
+    // The same as generating with the new deltas directly.
+    assert!(rotated == params(vec![Fr::from(5481 * 2), Fr::from(5482 * 3)]));
+
+    let m = Square {
+        x: Some(Fr::from(3)),
+    };
+    let (r, s) = (Fr::from(27134), Fr::from(17146));
+    let pvk = prepare_verifying_key(&rotated.vk);
+    let (pf, _) = create_proof(&m, &rotated, r, s, vec![Fr::from(1)]).unwrap();
+    verify_proof(&pvk, &pf, &[Fr::from(9)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(10)]).is_err());
+
+    let (old_pf, _) = create_proof(&m, &old, r, s, vec![Fr::from(1)]).unwrap();
+    verify_proof(&prepare_verifying_key(&old.vk), &old_pf, &[Fr::from(9)]).unwrap();
+    assert!(verify_proof(&pvk, &old_pf, &[Fr::from(9)]).is_err());
+
+    assert!(matches!(
+        rotated.rerandomize_deltas(&[Fr::from(2)]),
+        Err(SynthesisError::DeltaCountMismatch)
+    ));
+    assert!(matches!(
+        rotated.rerandomize_deltas(&[Fr::from(2), Fr::zero()]),
+        Err(SynthesisError::UnexpectedIdentity)
+    ));
+}
+
+#[test]
+fn test_inactive_blocks() {
+    // Commits to x and y in separate blocks, and exposes x * y.
+    struct TwoBlocks<F> {
+        x: Option<F>,
+        y: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &TwoBlocks<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = AllocatedNum::alloc(cs.namespace(|| "y"), || Ok(*self.y.get()?))?;
+            cs.end_aux_block(|| "y")?;
+            let xy = x.mul(cs.namespace(|| "x*y"), &y)?;
+            xy.inputize(cs.namespace(|| "input xy"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            2
+        }
+    }
+
+    let deltas = vec![Fr::from(5481), Fr::from(5482), Fr::from(5483)];
+    let pk = generate_parameters::<DummyEngine, _>(
+        &TwoBlocks { x: None, y: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        deltas.clone(),
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = TwoBlocks {
+        x: Some(Fr::from(3)),
+        y: Some(Fr::from(5)),
+    };
+    let k = vec![Fr::from(1), Fr::from(15)];
+    let (pf, _) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), k).unwrap();
+    let inputs = [Fr::from(15)];
+    verify_proof(&pvk, &pf, &inputs).unwrap();
+    verify_proof_with_active_blocks(&pvk, &pf, &inputs, &[true, true]).unwrap();
+    assert!(verify_proof_with_active_blocks(&pvk, &pf, &inputs, &[false, true]).is_err());
+    for active in [&[true][..], &[true, true, true]] {
+        assert!(matches!(
+            verify_proof_with_active_blocks(&pvk, &pf, &inputs, active),
+            Err(VerificationError::InvalidProof)
+        ));
+    }
+
+    // In the dummy engine the pairing is just multiplication, so we can move
+    // the first block's term, d_0 * delta_0, into c * delta.
+    let mut adjusted = pf;
+    let delta_last_inv = deltas[2].invert().unwrap();
+    adjusted.c += adjusted.ds[0] * deltas[0] * delta_last_inv;
+    verify_proof_with_active_blocks(&pvk, &adjusted, &inputs, &[false, true]).unwrap();
+    assert!(verify_proof(&pvk, &adjusted, &inputs).is_err());
+    assert!(verify_proof_with_active_blocks(&pvk, &adjusted, &inputs, &[true, false]).is_err());
+}
+
+#[test]
+fn test_folded_single_block() {
+    // Commits to x in one block, and exposes x * x.
+    struct OneBlock<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &OneBlock<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let xx = x.square(cs.namespace(|| "x*x"))?;
+            xx.inputize(cs.namespace(|| "input xx"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &OneBlock { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = OneBlock {
+        x: Some(Fr::from(3)),
+    };
+    let k = vec![Fr::from(7)];
+    let (pf, _) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), k).unwrap();
+    assert_eq!(pf.fold_ds(&[Fr::one()]), pf.ds[0]);
+
+    // With one block, folding with a coefficient of one is the same check.
+    for inputs in [[Fr::from(9)], [Fr::from(3)]] {
+        assert_eq!(
+            verify_proof_folded(&pvk, &pf, &inputs, &[Fr::one()]).is_ok(),
+            verify_proof(&pvk, &pf, &inputs).is_ok()
+        );
+    }
+    verify_proof_folded(&pvk, &pf, &[Fr::from(9)], &[Fr::one()]).unwrap();
+    assert!(verify_proof_folded(&pvk, &pf, &[Fr::from(9)], &[Fr::from(2)]).is_err());
+    for coeffs in [&[][..], &[Fr::one(), Fr::one()]] {
+        assert!(matches!(
+            verify_proof_folded(&pvk, &pf, &[Fr::from(9)], coeffs),
+            Err(VerificationError::InvalidProof)
+        ));
+    }
+}
+
+#[test]
+fn test_prepared_inputs_with_coins() {
+    // Commits to x in a block, then draws a coin r and multiplies it by x.
+    struct CoinAfterBlock<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &CoinAfterBlock<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let (r, val_r) = cs.alloc_random(|| "r")?;
+            let r = AllocatedNum {
+                variable: r,
+                value: val_r,
+            };
+            r.mul(cs.namespace(|| "r*x"), &x)?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &CoinAfterBlock { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481), Fr::from(5482)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = CoinAfterBlock {
+        x: Some(Fr::from(3)),
+    };
+    let k = vec![Fr::from(7)];
+    let (pf, _) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), k).unwrap();
+    verify_proof(&pvk, &pf, &[]).unwrap();
+
+    // The accumulator depends on the proof's coin, so it can't be reused, and
+    // a key with coins is rejected rather than trusting it.
+    let prepared = pvk.prepare_inputs(&[], &pf.ds).unwrap();
+    assert!(matches!(
+        verify_proof_with_prepared_inputs(&pvk, &pf, &prepared),
+        Err(VerificationError::InvalidVerifyingKey)
+    ));
+}
+
+#[cfg(feature = "mixed-radix")]
+#[test]
+fn test_mixed_radix_domain() {
+    // Squares x 600 times and exposes the result. With the input constraints,
+    // this needs 602 points, more than the 2^9 that Fr's 2-adic subgroup
+    // supports, so the domain falls back to 3 * 2^8.
+    struct Squarings<F> {
+        x: Option<F>,
+    }
+
+    impl<F: ff::PrimeField> CcCircuit<F> for &Squarings<F> {
+        fn synthesize<CS: CcConstraintSystem<F>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use crate::gadgets::num::AllocatedNum;
+            use crate::gadgets::Assignment;
+            let mut x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            for i in 0..600 {
+                x = x.square(cs.namespace(|| format!("square {}", i)))?;
+            }
+            x.inputize(cs.namespace(|| "input"))?;
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            0
+        }
+    }
+
+    let pk = generate_parameters::<DummyEngine, _>(
+        &Squarings { x: None },
+        Fr::one(),
+        Fr::one(),
+        Fr::from(48577),
+        Fr::from(22580),
+        Fr::from(53332),
+        vec![Fr::from(5481)],
+        Fr::from(3673),
+    )
+    .unwrap();
+    assert_eq!(pk.h.len(), 3 * 256 - 1);
+    let pvk = prepare_verifying_key(&pk.vk);
+
+    let m = Squarings {
+        x: Some(Fr::from(3)),
+    };
+    let (pf, _) = create_proof(&m, &pk, Fr::from(27134), Fr::from(17146), vec![]).unwrap();
+    // 3^(2^600) mod 64513
+    verify_proof(&pvk, &pf, &[Fr::from(30022)]).unwrap();
+    assert!(verify_proof(&pvk, &pf, &[Fr::from(30023)]).is_err());
+}
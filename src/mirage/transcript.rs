@@ -0,0 +1,368 @@
+//! A record of the messages absorbed into a prover's [merlin] transcript, so
+//! that the transcript can be saved and rebuilt to resume proving.
+
+use std::io::{self, Read, Write};
+use std::mem;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use merlin::Transcript;
+use rand_core::RngCore;
+
+use super::{merlin_rng, ProofContext};
+use crate::SynthesisError;
+
+/// One operation on a prover's transcript, after its [`ProofContext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranscriptMessage {
+    /// The canonical encoding of an input, including the random coins and the
+    /// constant one.
+    Input(Vec<u8>),
+    /// The uncompressed encoding of an aux block commitment.
+    AuxCommit(Vec<u8>),
+    /// A random coin was drawn, which changes the transcript's state.
+    Coin,
+}
+
+impl TranscriptMessage {
+    /// The byte this message is serialized as.
+    fn tag(&self) -> u8 {
+        match self {
+            TranscriptMessage::Input(_) => 0,
+            TranscriptMessage::AuxCommit(_) => 1,
+            TranscriptMessage::Coin => 2,
+        }
+    }
+
+    fn apply(&self, transcript: &mut Transcript) -> Option<Box<dyn RngCore>> {
+        match self {
+            TranscriptMessage::Input(data) => transcript.append_message(b"input", data),
+            TranscriptMessage::AuxCommit(data) => transcript.append_message(b"aux_commit", data),
+            TranscriptMessage::Coin => return Some(merlin_rng(transcript, b"random")),
+        }
+        None
+    }
+}
+
+/// The messages absorbed into a prover's transcript, in order.
+///
+/// A [`Transcript`] can't be serialized, but replaying its messages into a
+/// fresh one reconstructs it exactly, so a prover that pauses between aux
+/// blocks can save this log and pick up from it with
+/// [`resume_proof`](super::resume_proof).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TranscriptLog {
+    messages: Vec<TranscriptMessage>,
+}
+
+impl TranscriptLog {
+    pub fn messages(&self) -> &[TranscriptMessage] {
+        &self.messages
+    }
+
+    /// Keep only the first `len` messages, so that
+    /// [`resume_proof`](super::resume_proof) picks up from an earlier point,
+    /// such as the last aux block whose witness is unchanged.
+    pub fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
+    /// The transcript that absorbed these messages under `ctx`, which must be
+    /// the context the log was recorded in.
+    pub fn replay(&self, ctx: &ProofContext) -> Transcript {
+        let mut transcript = ctx.transcript();
+        for message in &self.messages {
+            message.apply(&mut transcript);
+        }
+        transcript
+    }
+
+    /// Write the log as its number of messages, as a big-endian `u32`, followed
+    /// by each message's tag byte and, for those with data, the data's length
+    /// as a big-endian `u32` and the data.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.messages.len() as u32)?;
+        for message in &self.messages {
+            writer.write_u8(message.tag())?;
+            match message {
+                TranscriptMessage::Input(data) | TranscriptMessage::AuxCommit(data) => {
+                    writer.write_u32::<BigEndian>(data.len() as u32)?;
+                    writer.write_all(data)?;
+                }
+                TranscriptMessage::Coin => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a log written by [`TranscriptLog::write`].
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let len = reader.read_u32::<BigEndian>()?;
+        let mut messages = vec![];
+        for _ in 0..len {
+            let tag = reader.read_u8()?;
+            let message = match tag {
+                0 => TranscriptMessage::Input(read_data(&mut reader)?),
+                1 => TranscriptMessage::AuxCommit(read_data(&mut reader)?),
+                2 => TranscriptMessage::Coin,
+                i => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("u8 {} is not a valid transcript message", i),
+                    ))
+                }
+            };
+            messages.push(message);
+        }
+        Ok(TranscriptLog { messages })
+    }
+}
+
+/// Read a length-prefixed message, allocating no more than the reader holds.
+fn read_data<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<BigEndian>()? as u64;
+    let mut data = vec![];
+    reader.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+/// A transcript that logs the messages it absorbs.
+///
+/// When resuming from a log, each message absorbed while the log lasts must
+/// match the logged one.
+pub(super) struct LoggedTranscript {
+    transcript: Transcript,
+    log: TranscriptLog,
+    resumed: TranscriptLog,
+}
+
+impl LoggedTranscript {
+    pub(super) fn new(ctx: &ProofContext) -> Self {
+        Self::resume(ctx, TranscriptLog::default())
+    }
+
+    pub(super) fn resume(ctx: &ProofContext, log: TranscriptLog) -> Self {
+        LoggedTranscript {
+            transcript: ctx.transcript(),
+            log: TranscriptLog::default(),
+            resumed: log,
+        }
+    }
+
+    pub(super) fn append_input(&mut self, repr: &[u8]) -> Result<(), SynthesisError> {
+        self.push(TranscriptMessage::Input(repr.to_vec()))?;
+        Ok(())
+    }
+
+    pub(super) fn append_aux_commit(&mut self, point: &[u8]) -> Result<(), SynthesisError> {
+        self.push(TranscriptMessage::AuxCommit(point.to_vec()))?;
+        Ok(())
+    }
+
+    /// The commitment to the next aux block, if it is in the log being
+    /// resumed from.
+    pub(super) fn resumed_aux_commit(&self) -> Option<&[u8]> {
+        match self.resumed.messages.get(self.log.messages.len()) {
+            Some(TranscriptMessage::AuxCommit(point)) => Some(point),
+            _ => None,
+        }
+    }
+
+    /// An RNG for the next random coin.
+    pub(super) fn coin_rng(&mut self) -> Result<Box<dyn RngCore>, SynthesisError> {
+        Ok(self
+            .push(TranscriptMessage::Coin)?
+            .expect("drawing a coin returns an RNG"))
+    }
+
+    pub(super) fn log(&self) -> &TranscriptLog {
+        &self.log
+    }
+
+    pub(super) fn take_log(&mut self) -> TranscriptLog {
+        mem::take(&mut self.log)
+    }
+
+    fn push(
+        &mut self,
+        message: TranscriptMessage,
+    ) -> Result<Option<Box<dyn RngCore>>, SynthesisError> {
+        match self.resumed.messages.get(self.log.messages.len()) {
+            Some(logged) if *logged != message => return Err(SynthesisError::TranscriptMismatch),
+            _ => {}
+        }
+        let rng = message.apply(&mut self.transcript);
+        self.log.messages.push(message);
+        Ok(rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cc::{CcCircuit, CcConstraintSystem};
+    use crate::gadgets::num::AllocatedNum;
+    use crate::gadgets::Assignment;
+    use crate::mirage::tests::{DummyEngine, Fr};
+    use crate::mirage::{
+        create_proof_with_context, create_proof_with_log, generate_parameters, resume_proof,
+    };
+    use ff::{Field, PrimeField};
+
+    #[test]
+    fn replay_matches_coin_stream() {
+        let mut rng = crate::testing::test_rng();
+        let ctx = ProofContext::default().with_data(b"session");
+        let mut logged = LoggedTranscript::new(&ctx);
+        logged.append_input(Fr::one().to_repr().as_ref()).unwrap();
+        logged.coin_rng().unwrap();
+        logged
+            .append_input(Fr::random(&mut rng).to_repr().as_ref())
+            .unwrap();
+        logged.append_aux_commit(&[7u8; 96]).unwrap();
+
+        // Serialize the log, as a paused prover would.
+        let mut bytes = vec![];
+        logged.log().write(&mut bytes).unwrap();
+        let log = TranscriptLog::read(&bytes[..]).unwrap();
+        assert_eq!(&log, logged.log());
+        assert_eq!(log.messages().len(), 4);
+
+        // The replayed transcript draws the same coins as the original.
+        let coin = |rng: &mut Box<dyn RngCore>| {
+            let mut bytes = [0u8; 64];
+            rng.fill_bytes(&mut bytes);
+            bytes
+        };
+        let mut replayed = log.replay(&ctx);
+        for _ in 0..2 {
+            assert_eq!(
+                coin(&mut merlin_rng(&mut replayed, b"random")),
+                coin(&mut logged.coin_rng().unwrap())
+            );
+            replayed.append_message(b"input", &[]);
+            logged.append_input(&[]).unwrap();
+        }
+
+        // Under another context, it doesn't.
+        assert_ne!(
+            coin(&mut merlin_rng(&mut log.replay(&ctx), b"random")),
+            coin(&mut merlin_rng(
+                &mut log.replay(&ProofContext::default()),
+                b"random"
+            ))
+        );
+
+        // Truncated logs and unknown tags are rejected.
+        assert!(TranscriptLog::read(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad_tag = bytes.clone();
+        bad_tag[4] = 3;
+        assert!(TranscriptLog::read(&bad_tag[..]).is_err());
+    }
+
+    // Commits to a block, then draws a coin and exposes it.
+    struct CoinAfterBlock;
+
+    impl CcCircuit<Fr> for CoinAfterBlock {
+        fn synthesize<CS: CcConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || Ok(Fr::from(3)))?;
+            cs.end_aux_block(|| "block")?;
+            let (r, _) = cs.alloc_random(|| "r")?;
+            let c = cs.alloc(|| "c", || Ok(Fr::from(9)))?;
+            cs.enforce(|| "a * a = c", |lc| lc + a, |lc| lc + a, |lc| lc + c);
+            cs.enforce(
+                || "r * 1 = r",
+                |lc| lc + r,
+                |lc| lc + CS::one(),
+                |lc| lc + r,
+            );
+            Ok(())
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    // Commits to x in a block, and exposes x^2.
+    struct Square {
+        x: Option<Fr>,
+    }
+
+    impl CcCircuit<Fr> for Square {
+        fn synthesize<CS: CcConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(*self.x.get()?))?;
+            cs.end_aux_block(|| "x")?;
+            let y = x.square(cs.namespace(|| "x^2"))?;
+            y.inputize(cs.namespace(|| "input y"))
+        }
+
+        fn num_aux_blocks(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn resume_proof_from_log() {
+        let params = generate_parameters::<DummyEngine, _>(
+            CoinAfterBlock,
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            vec![Fr::from(2), Fr::from(5481)],
+            Fr::from(3673),
+        )
+        .unwrap();
+        let ctx = ProofContext::default().with_data(b"session");
+        let (r, s, k) = (Fr::from(27134), Fr::from(17146), vec![Fr::from(7)]);
+        let (proof, _, log) =
+            create_proof_with_log(CoinAfterBlock, &params, r, s, k.clone(), &ctx).unwrap();
+        assert!(matches!(
+            log.messages(),
+            [
+                TranscriptMessage::Input(_),
+                TranscriptMessage::AuxCommit(_),
+                TranscriptMessage::Coin,
+                TranscriptMessage::Input(_),
+            ]
+        ));
+
+        // Resuming from the whole log, or from any point in it, draws the same
+        // coin and gives the same proof.
+        for len in 0..=log.messages().len() {
+            let mut prefix = log.clone();
+            prefix.truncate(len);
+            let (resumed, _) =
+                resume_proof(&prefix, CoinAfterBlock, &params, r, s, k.clone(), &ctx).unwrap();
+            assert!(resumed == proof);
+        }
+        let (unlogged, _) =
+            create_proof_with_context(CoinAfterBlock, &params, r, s, k.clone(), &ctx).unwrap();
+        assert!(unlogged == proof);
+
+        // A circuit that absorbs anything else than the log fails to resume.
+        let square = |x| Square {
+            x: Some(Fr::from(x)),
+        };
+        let params = generate_parameters::<DummyEngine, _>(
+            square(3),
+            Fr::one(),
+            Fr::one(),
+            Fr::from(48577),
+            Fr::from(22580),
+            Fr::from(53332),
+            vec![Fr::from(2), Fr::from(5481)],
+            Fr::from(3673),
+        )
+        .unwrap();
+        let (_, _, log) = create_proof_with_log(square(3), &params, r, s, k.clone(), &ctx).unwrap();
+        assert!(matches!(
+            resume_proof(&log, square(5), &params, r, s, k, &ctx),
+            Err(SynthesisError::TranscriptMismatch)
+        ));
+    }
+}
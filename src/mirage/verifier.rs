@@ -1,32 +1,389 @@
 use ff::{Field, PrimeField};
 use group::{prime::PrimeCurveAffine, Curve, UncompressedEncoding};
-use merlin::Transcript;
-use pairing::{MillerLoopResult, MultiMillerLoop};
+use pairing::MultiMillerLoop;
+use rand_core::RngCore;
 use std::ops::{AddAssign, Neg};
 
-use super::{merlin_rng, PreparedVerifyingKey, Proof, VerifyingKey};
+use super::{
+    fold_ds, merlin_rng, PreparedProof, PreparedVerifyingKey, Proof, ProofContext, TranscriptEntry,
+    VerifyingKey,
+};
 
+use crate::commit::cp_link;
+use crate::kw15;
+use crate::pairing_check::PairingCheck;
 use crate::VerificationError;
 
 pub fn prepare_verifying_key<E: MultiMillerLoop>(vk: &VerifyingKey<E>) -> PreparedVerifyingKey<E> {
-    let gamma = vk.gamma_g2.neg();
-    let neg_deltas_g2: Vec<E::G2Prepared> = vk.deltas_g2.iter().map(|d| d.neg().into()).collect();
+    prepare_from_parts(
+        vk.alpha_g1,
+        vk.beta_g2,
+        vk.gamma_g2.neg(),
+        vk.deltas_g2.iter().map(|d| d.neg()).collect(),
+        vk.ic.clone(),
+        vk.transcript.clone(),
+    )
+}
 
+pub(super) fn prepare_from_parts<E: MultiMillerLoop>(
+    alpha_g1: E::G1Affine,
+    beta_g2: E::G2Affine,
+    neg_gamma_g2: E::G2Affine,
+    neg_deltas_g2: Vec<E::G2Affine>,
+    ic: Vec<E::G1Affine>,
+    transcript: Vec<TranscriptEntry>,
+) -> PreparedVerifyingKey<E> {
     PreparedVerifyingKey {
-        alpha_g1_beta_g2: E::pairing(&vk.alpha_g1, &vk.beta_g2),
-        neg_gamma_g2: gamma.into(),
-        neg_deltas_g2,
-        ic: vk.ic.clone(),
-        transcript: vk.transcript.clone(),
+        alpha_g1_beta_g2: E::pairing(&alpha_g1, &beta_g2),
+        neg_gamma_g2: neg_gamma_g2.into(),
+        neg_deltas_g2: neg_deltas_g2.iter().map(|d| (*d).into()).collect(),
+        ic,
+        transcript,
+        alpha_g1,
+        beta_g2,
+        neg_gamma_g2_affine: neg_gamma_g2,
+        neg_deltas_g2_affine: neg_deltas_g2,
     }
 }
 
+/// Validate a proof with [`Proof::validate`] and prepare it for (repeated)
+/// verification with [`verify_prepared_proof`].
+pub fn prepare_proof<E: MultiMillerLoop>(
+    proof: &Proof<E>,
+) -> Result<PreparedProof<E>, VerificationError> {
+    proof.validate()?;
+
+    Ok(PreparedProof {
+        a: proof.a,
+        b: proof.b.into(),
+        c: proof.c,
+        ds: proof.ds.clone(),
+    })
+}
+
 pub fn verify_proof<'a, E: MultiMillerLoop>(
     pvk: &'a PreparedVerifyingKey<E>,
     proof: &Proof<E>,
     public_inputs: &[E::Fr],
 ) -> Result<(), VerificationError> {
-    let mut transcript = Transcript::new(b"mirage_aozdemir_1");
+    let prepared_inputs = pvk.prepare_inputs(public_inputs, &proof.ds)?;
+    verify_with_inputs(pvk, proof, &prepared_inputs)
+}
+
+/// Like [`verify_proof`], for proofs whose random coins were drawn from a
+/// transcript separated by `ctx`.
+pub fn verify_proof_with_context<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+    ctx: &ProofContext,
+) -> Result<(), VerificationError> {
+    let prepared_inputs = accumulate_inputs(pvk, &proof.ds, public_inputs, ctx)?;
+    verify_with_inputs(pvk, proof, &prepared_inputs)
+}
+
+/// Like [`verify_proof`], with the public input accumulator computed
+/// beforehand by [`PreparedVerifyingKey::prepare_inputs`].
+///
+/// The accumulator is trusted as given: it is only sound to reuse across
+/// proofs when it doesn't depend on their `ds`, so this fails with
+/// [`VerificationError::InvalidVerifyingKey`] for keys whose circuits draw
+/// random coins, whose accumulator would have to be recomputed from each
+/// proof's transcript. Verify those with [`verify_proof`].
+pub fn verify_proof_with_prepared_inputs<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    prepared_inputs: &E::G1Affine,
+) -> Result<(), VerificationError> {
+    if pvk.num_coins() != 0 {
+        return Err(VerificationError::InvalidVerifyingKey);
+    }
+    verify_with_inputs(pvk, proof, prepared_inputs)
+}
+
+fn verify_with_inputs<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    prepared_inputs: &E::G1Affine,
+) -> Result<(), VerificationError> {
+    proof.validate()?;
+    let b = proof.b.into();
+    verify(
+        pvk,
+        &proof.a,
+        &b,
+        &proof.c,
+        &proof.ds,
+        prepared_inputs,
+        DsTerms::All,
+        &[],
+    )
+}
+
+/// Verify a proof, leaving the aux block commitments `ds[i]` with
+/// `!active[i]` out of the pairing check, as if they committed to zero.
+///
+/// The inactive `ds` are still absorbed into the transcript, so the random
+/// coins are unchanged.
+///
+/// This is for experimenting with the aux block structure only, and is not
+/// sound in general: it checks a different equation than [`verify_proof`], so
+/// an honest proof fails it unless every inactive block has a zero
+/// commitment, and a passing proof says nothing about the contents of the
+/// inactive blocks. In particular, their `ds` are not bound to anything.
+///
+/// Fails with [`VerificationError::InvalidProof`] if `active` and `proof.ds`
+/// have different lengths.
+pub fn verify_proof_with_active_blocks<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+    active: &[bool],
+) -> Result<(), VerificationError> {
+    if active.len() != proof.ds.len() {
+        return Err(VerificationError::InvalidProof);
+    }
+    proof.validate()?;
+    let acc = pvk.prepare_inputs(public_inputs, &proof.ds)?;
+    let b = proof.b.into();
+    verify(
+        pvk,
+        &proof.a,
+        &b,
+        &proof.c,
+        &proof.ds,
+        &acc,
+        DsTerms::Active(active),
+        &[],
+    )
+}
+
+/// Verify a proof with its aux block commitments folded into one pairing
+/// term, [`Proof::fold_ds`] with `coeffs` against the first block's delta,
+/// rather than one term per block.
+///
+/// The `ds` are still absorbed into the transcript one by one, so the random
+/// coins are unchanged.
+///
+/// This checks the same equation as [`verify_proof`] only for keys whose
+/// block deltas are `delta_i = coeffs[i] * delta_0`; for example, a key
+/// generated with one delta for all of its blocks, with `coeffs` all one.
+/// The key is not checked, and honest proofs fail under other keys. Such a
+/// key does not bind the blocks separately: a passing proof shows that the
+/// folded commitment is correct, but a prover can move committed values
+/// between blocks. With a single block, `coeffs = [1]` folds nothing away.
+///
+/// Fails with [`VerificationError::InvalidProof`] if `coeffs` and `proof.ds`
+/// have different lengths.
+pub fn verify_proof_folded<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+    coeffs: &[E::Fr],
+) -> Result<(), VerificationError> {
+    if coeffs.len() != proof.ds.len() {
+        return Err(VerificationError::InvalidProof);
+    }
+    proof.validate()?;
+    let acc = pvk.prepare_inputs(public_inputs, &proof.ds)?;
+    let b = proof.b.into();
+    verify(
+        pvk,
+        &proof.a,
+        &b,
+        &proof.c,
+        &proof.ds,
+        &acc,
+        DsTerms::Folded(coeffs),
+        &[],
+    )
+}
+
+pub fn verify_prepared_proof<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &PreparedProof<E>,
+    public_inputs: &[E::Fr],
+) -> Result<(), VerificationError> {
+    let acc = pvk.prepare_inputs(public_inputs, &proof.ds)?;
+    verify(
+        pvk,
+        &proof.a,
+        &proof.b,
+        &proof.c,
+        &proof.ds,
+        &acc,
+        DsTerms::All,
+        &[],
+    )
+}
+
+/// Verify a proof together with a [`cp_link`] proof that its aux block
+/// commitments `proof.ds` commit to the same vectors as the external
+/// commitments `cmts`, with one final exponentiation for both.
+///
+/// `link_pvk` is a prepared key for the
+/// [`aux_commit_keys`](super::aux_commit_keys) of the proof's parameters, as
+/// `cp_link::key_gen(k, &aux_commit_keys(params), rng)` for the key `k` of
+/// `cmts`; like `pvk`, it is meant to be prepared once and reused. Passing is
+/// the same as passing both [`verify_proof`] and [`cp_link::verify`], except
+/// with probability about `1 / |Fr|` over the batching coefficient drawn from
+/// `rng`.
+///
+/// Fails with [`VerificationError::InvalidProof`] if `cmts` and `proof.ds`
+/// have different lengths, and with [`VerificationError::InvalidVerifyingKey`]
+/// if `link_pvk` is for a different number of commitments.
+pub fn verify_proof_with_link<E: MultiMillerLoop, R: RngCore>(
+    pvk: &PreparedVerifyingKey<E>,
+    proof: &Proof<E>,
+    public_inputs: &[E::Fr],
+    link_pvk: &cp_link::PreparedVerifyingKey<E>,
+    cmts: &[E::G1Affine],
+    link_proof: &cp_link::Proof<E>,
+    rng: &mut R,
+) -> Result<(), VerificationError> {
+    if cmts.len() != proof.ds.len() {
+        return Err(VerificationError::InvalidProof);
+    }
+    proof.validate()?;
+    let acc = pvk.prepare_inputs(public_inputs, &proof.ds)?;
+    let b = proof.b.into();
+    // The link equation has a target of one, so scaling all of its terms by
+    // a random coefficient lets it share the mirage equation's target.
+    let link_terms = kw15::scaled_terms(
+        link_pvk,
+        &cp_link::kw15_cmts::<E>(cmts, &proof.ds),
+        link_proof,
+        E::Fr::random(rng),
+    )
+    .ok_or(VerificationError::InvalidVerifyingKey)?;
+    verify(
+        pvk,
+        &proof.a,
+        &b,
+        &proof.c,
+        &proof.ds,
+        &acc,
+        DsTerms::All,
+        &link_terms,
+    )
+}
+
+/// Which pairing terms [`verify`] adds for the aux block commitments.
+enum DsTerms<'a, F> {
+    /// One term per block.
+    All,
+    /// One term per block with `active[i]` set.
+    Active(&'a [bool]),
+    /// A single term for the blocks folded with these coefficients.
+    Folded(&'a [F]),
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    a: &E::G1Affine,
+    b: &E::G2Prepared,
+    c: &E::G1Affine,
+    ds: &[E::G1Affine],
+    acc: &E::G1Affine,
+    ds_terms: DsTerms<'_, E::Fr>,
+    extra_terms: &[(E::G1Affine, &E::G2Prepared)],
+) -> Result<(), VerificationError> {
+    // The original verification equation is:
+    // A * B = alpha * beta + inputs * gamma + C * delta
+    // ... however, we rearrange it so that it is:
+    // A * B - inputs * gamma - C * delta = alpha * beta
+    // or equivalently:
+    // A * B + inputs * (-gamma) + C * (-delta) = alpha * beta
+    // which allows us to do a single final exponentiation.
+
+    if ds.len() != pvk.num_aux_commits() {
+        return Err(VerificationError::InvalidProof);
+    }
+    if pvk.neg_deltas_g2.len() != ds.len() + 1 {
+        return Err(VerificationError::InvalidVerifyingKey);
+    }
+
+    let folded;
+    let last = pvk.neg_deltas_g2.len() - 1;
+    let mut check = PairingCheck::<E>::with_capacity(3 + ds.len() + extra_terms.len());
+    check
+        .add(a, b)
+        .add(acc, &pvk.neg_gamma_g2)
+        .add(c, &pvk.neg_deltas_g2[last]);
+    match ds_terms {
+        DsTerms::All => {
+            for (d, neg_delta) in ds.iter().zip(&pvk.neg_deltas_g2) {
+                check.add(d, neg_delta);
+            }
+        }
+        DsTerms::Active(active) => {
+            for (i, d) in ds.iter().enumerate() {
+                if active[i] {
+                    check.add(d, &pvk.neg_deltas_g2[i]);
+                }
+            }
+        }
+        DsTerms::Folded(coeffs) => {
+            if !ds.is_empty() {
+                folded = fold_ds::<E>(ds, coeffs);
+                check.add(&folded, &pvk.neg_deltas_g2[0]);
+            }
+        }
+    }
+    for (g1, g2) in extra_terms {
+        check.add(g1, g2);
+    }
+    if check.finalize_eq(&pvk.alpha_g1_beta_g2) {
+        Ok(())
+    } else {
+        Err(VerificationError::InvalidProof)
+    }
+}
+
+impl<E: MultiMillerLoop> PreparedVerifyingKey<E> {
+    /// The public input accumulator `ic[0] + sum_i inputs[i] * ic[i]` for a
+    /// proof with aux block commitments `proof_ds`, for
+    /// [`verify_proof_with_prepared_inputs`].
+    ///
+    /// The random coins among the inputs are drawn from the transcript of
+    /// `proof_ds`, so for circuits with random coins the accumulator is
+    /// particular to one proof, and [`verify_proof_with_prepared_inputs`]
+    /// rejects their keys. Without coins it depends on `public_inputs` only,
+    /// and can be computed once for all the proofs of the same inputs.
+    ///
+    /// Fails with [`VerificationError::InvalidProof`] if there are not as many
+    /// `public_inputs` and `proof_ds` as the key expects, and with
+    /// [`VerificationError::InvalidVerifyingKey`] if the key's `ic` does not
+    /// match its transcript.
+    pub fn prepare_inputs(
+        &self,
+        public_inputs: &[E::Fr],
+        proof_ds: &[E::G1Affine],
+    ) -> Result<E::G1Affine, VerificationError> {
+        accumulate_inputs(self, proof_ds, public_inputs, &ProofContext::default())
+    }
+}
+
+/// The public input accumulator `ic[0] + sum_i inputs[i] * ic[i]`, where the
+/// inputs are the random coins drawn from the transcript of `ds` interleaved
+/// with `public_inputs`, as the key's transcript orders them.
+///
+/// Fails as [`PreparedVerifyingKey::prepare_inputs`] does.
+pub(super) fn accumulate_inputs<E: MultiMillerLoop>(
+    pvk: &PreparedVerifyingKey<E>,
+    ds: &[E::G1Affine],
+    public_inputs: &[E::Fr],
+    ctx: &ProofContext,
+) -> Result<E::G1Affine, VerificationError> {
+    if public_inputs.len() != pvk.num_public_inputs() || ds.len() != pvk.num_aux_commits() {
+        return Err(VerificationError::InvalidProof);
+    }
+    if pvk.ic.len() != 1 + pvk.num_public_inputs() + pvk.num_coins() {
+        return Err(VerificationError::InvalidVerifyingKey);
+    }
+
+    let mut transcript = ctx.transcript();
     let mut acc = pvk.ic[0].to_curve();
     transcript.append_message(b"input", E::Fr::from(1).to_repr().as_ref());
 
@@ -53,41 +410,11 @@ pub fn verify_proof<'a, E: MultiMillerLoop>(
                 i += 1;
             }
             crate::mirage::TranscriptEntry::AuxCommit => {
-                transcript.append_message(
-                    b"aux_commit",
-                    proof.ds[aux_commits_i].to_uncompressed().as_ref(),
-                );
+                transcript
+                    .append_message(b"aux_commit", ds[aux_commits_i].to_uncompressed().as_ref());
                 aux_commits_i += 1;
             }
         }
     }
-    if i != pvk.ic.len() || aux_commits_i != proof.ds.len() {
-        return Err(VerificationError::InvalidVerifyingKey);
-    }
-
-    // The original verification equation is:
-    // A * B = alpha * beta + inputs * gamma + C * delta
-    // ... however, we rearrange it so that it is:
-    // A * B - inputs * gamma - C * delta = alpha * beta
-    // or equivalently:
-    // A * B + inputs * (-gamma) + C * (-delta) = alpha * beta
-    // which allows us to do a single final exponentiation.
-
-    let b = proof.b.into();
-    let acc = acc.to_affine();
-    let last = pvk.neg_deltas_g2.len() - 1;
-    let mut multi_miller_input = vec![
-        (&proof.a, &b),
-        (&acc, &pvk.neg_gamma_g2),
-        (&proof.c, &pvk.neg_deltas_g2[last]),
-    ];
-    assert_eq!(pvk.neg_deltas_g2.len(), proof.ds.len() + 1);
-    for (i, d) in proof.ds.iter().enumerate() {
-        multi_miller_input.push((d, &pvk.neg_deltas_g2[i]));
-    }
-    if pvk.alpha_g1_beta_g2 == E::multi_miller_loop(&multi_miller_input).final_exponentiation() {
-        Ok(())
-    } else {
-        Err(VerificationError::InvalidProof)
-    }
+    Ok(acc.to_affine())
 }
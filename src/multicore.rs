@@ -16,18 +16,58 @@ mod implementation {
     lazy_static! {
         // See Worker::compute below for a description of this.
         static ref WORKER_SPAWN_MAX_COUNT: usize = current_num_threads() * 4;
+        // See Worker::global below.
+        static ref GLOBAL_WORKER: Worker = Worker::new();
     }
 
     #[derive(Clone, Default)]
-    pub struct Worker {}
+    pub struct Worker {
+        log_threads: Option<u32>,
+        num_threads: Option<usize>,
+    }
 
     impl Worker {
         pub fn new() -> Worker {
-            Worker {}
+            Worker {
+                log_threads: None,
+                num_threads: None,
+            }
+        }
+
+        /// A worker that splits work as if there were `2^log_threads` threads,
+        /// regardless of the size of the thread pool.
+        pub fn with_log_threads(log_threads: u32) -> Worker {
+            Worker {
+                log_threads: Some(log_threads),
+                num_threads: None,
+            }
+        }
+
+        /// A worker that splits work as if there were `num_threads` threads,
+        /// regardless of the size of the thread pool, both in
+        /// [`Worker::scope`] and in [`Worker::log_num_threads`] (which rounds
+        /// down). Useful for reproducible benchmarks.
+        pub fn with_num_threads(num_threads: usize) -> Worker {
+            Worker {
+                log_threads: Some(log2_floor(num_threads)),
+                num_threads: Some(num_threads),
+            }
+        }
+
+        /// The process-wide worker, used by default throughout the crate.
+        ///
+        /// All work runs on rayon's global thread pool, so concurrent callers
+        /// share threads instead of oversubscribing the machine. The pool is
+        /// created on first use, with `RAYON_NUM_THREADS` threads if that
+        /// environment variable is set, and one per CPU otherwise; set it
+        /// before the first proof to override the thread count.
+        pub fn global() -> &'static Worker {
+            &GLOBAL_WORKER
         }
 
         pub fn log_num_threads(&self) -> u32 {
-            log2_floor(current_num_threads())
+            self.log_threads
+                .unwrap_or_else(|| log2_floor(current_num_threads()))
         }
 
         pub fn compute<F, R>(&self, f: F) -> Waiter<R>
@@ -80,7 +120,7 @@ mod implementation {
             F: FnOnce(&rayon::Scope<'a>, usize) -> R + Send,
             R: Send,
         {
-            let num_threads = current_num_threads();
+            let num_threads = self.num_threads.unwrap_or_else(current_num_threads);
             let chunk_size = if elements < num_threads {
                 1
             } else {
@@ -140,6 +180,16 @@ mod implementation {
         assert_eq!(log2_floor(7), 2);
         assert_eq!(log2_floor(8), 3);
     }
+
+    #[test]
+    fn test_with_num_threads() {
+        assert_eq!(Worker::with_num_threads(1).log_num_threads(), 0);
+        assert_eq!(Worker::with_num_threads(6).log_num_threads(), 2);
+        assert_eq!(Worker::with_num_threads(8).log_num_threads(), 3);
+
+        let chunk = Worker::with_num_threads(4).scope(100, |_, chunk| chunk);
+        assert_eq!(chunk, 25);
+    }
 }
 
 #[cfg(not(feature = "multicore"))]
@@ -152,6 +202,21 @@ mod implementation {
             Worker
         }
 
+        /// Without multicore support, all work happens on the current thread.
+        pub fn with_log_threads(_log_threads: u32) -> Worker {
+            Worker
+        }
+
+        /// Without multicore support, all work happens on the current thread.
+        pub fn with_num_threads(_num_threads: usize) -> Worker {
+            Worker
+        }
+
+        /// The process-wide worker, used by default throughout the crate.
+        pub fn global() -> &'static Worker {
+            &Worker
+        }
+
         pub fn log_num_threads(&self) -> u32 {
             0
         }
@@ -197,12 +262,14 @@ mod implementation {
     }
 
     /// A fake rayon ParallelIterator that is just a serial iterator.
+    #[cfg(feature = "prover")]
     pub(crate) trait FakeParallelIterator {
         type Iter: Iterator<Item = Self::Item>;
         type Item: Send;
         fn into_par_iter(self) -> Self::Iter;
     }
 
+    #[cfg(feature = "prover")]
     impl FakeParallelIterator for core::ops::Range<u32> {
         type Iter = Self;
         type Item = u32;
@@ -2,6 +2,8 @@ use super::multicore::{Waiter, Worker};
 use bitvec::vec::BitVec;
 use ff::{FieldBits, PrimeField, PrimeFieldBits};
 use group::prime::{PrimeCurve, PrimeCurveAffine};
+use std::error::Error;
+use std::fmt;
 use std::io;
 use std::iter;
 use std::ops::AddAssign;
@@ -300,14 +302,100 @@ where
         })
 }
 
+/// Below this many exponents, [`multiexp`] uses [`multiexp_small`] rather than
+/// the bucket method, whose buckets and per-window tasks cost more than they
+/// save on a handful of bases. The `multiexp::small` benchmark compares the
+/// two; the bucket method's windows run in parallel, so check any change on
+/// a multicore machine too.
+const SMALL_MULTIEXP: usize = 16;
+
+/// Double-and-add over all the bases at once, so that the doublings are
+/// shared and each base costs one mixed addition per set bit.
+fn multiexp_small<Q, D, G, S>(
+    bases: S,
+    density_map: D,
+    exponents: Arc<Vec<Exponent<G::Scalar>>>,
+) -> Result<G, SynthesisError>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: Send + Sync + 'static + Clone + AsRef<Q>,
+    G: PrimeCurve,
+    G::Scalar: PrimeFieldBits,
+    S: SourceBuilder<<G as PrimeCurve>::Affine>,
+{
+    let mut bases = bases.build();
+    let mut ones = G::identity();
+    let mut terms = vec![];
+    for (exp, density) in exponents.iter().zip(density_map.as_ref().iter()) {
+        if density {
+            match exp {
+                Exponent::Zero => bases.skip(1)?,
+                Exponent::One => ones.add_assign_from_source(&mut bases)?,
+                Exponent::Bits(bits) => terms.push((*bases.next()?, bits)),
+            }
+        }
+    }
+
+    let mut acc = G::identity();
+    for i in (0..G::Scalar::NUM_BITS as usize).rev() {
+        acc = acc.double();
+        for (base, bits) in &terms {
+            if bits[i] {
+                AddAssign::<&<G as PrimeCurve>::Affine>::add_assign(&mut acc, base);
+            }
+        }
+    }
+    Ok(acc + ones)
+}
+
 /// Perform multi-exponentiation. The caller is responsible for ensuring the
-/// query size is the same as the number of exponents.
+/// query size is the same as the number of exponents; [`try_multiexp`] checks
+/// this, and the number of bases, up front.
 pub fn multiexp<Q, D, G, S>(
     pool: &Worker,
     bases: S,
     density_map: D,
     exponents: Arc<Vec<Exponent<G::Scalar>>>,
 ) -> Waiter<Result<G, SynthesisError>>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: Send + Sync + 'static + Clone + AsRef<Q>,
+    G: PrimeCurve,
+    G::Scalar: PrimeFieldBits,
+    S: SourceBuilder<<G as PrimeCurve>::Affine>,
+{
+    let small = exponents.len() < SMALL_MULTIEXP;
+    multiexp_dispatch(pool, bases, density_map, exponents, small)
+}
+
+/// [`multiexp`], with [`multiexp_small`] if `small` and the bucket method
+/// otherwise, whatever the number of exponents. For the `multiexp::small`
+/// benchmark, which compares the two around [`SMALL_MULTIEXP`].
+#[cfg(feature = "test-utils")]
+pub fn multiexp_with_method<Q, D, G, S>(
+    pool: &Worker,
+    bases: S,
+    density_map: D,
+    exponents: Arc<Vec<Exponent<G::Scalar>>>,
+    small: bool,
+) -> Waiter<Result<G, SynthesisError>>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: Send + Sync + 'static + Clone + AsRef<Q>,
+    G: PrimeCurve,
+    G::Scalar: PrimeFieldBits,
+    S: SourceBuilder<<G as PrimeCurve>::Affine>,
+{
+    multiexp_dispatch(pool, bases, density_map, exponents, small)
+}
+
+fn multiexp_dispatch<Q, D, G, S>(
+    pool: &Worker,
+    bases: S,
+    density_map: D,
+    exponents: Arc<Vec<Exponent<G::Scalar>>>,
+    small: bool,
+) -> Waiter<Result<G, SynthesisError>>
 where
     for<'a> &'a Q: QueryDensity,
     D: Send + Sync + 'static + Clone + AsRef<Q>,
@@ -328,9 +416,92 @@ where
         assert!(query_size == exponents.len());
     }
 
+    if small {
+        return pool.compute(move || multiexp_small(bases, density_map, exponents));
+    }
     pool.compute(move || multiexp_inner(bases, density_map, exponents, c))
 }
 
+/// An error from [`try_multiexp`]: the lengths of its inputs don't line up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultiexpError {
+    /// The density map has a different query size than the number of
+    /// exponents.
+    DensityMismatch { query_size: usize, exponents: usize },
+    /// There are a different number of bases than exponents the density map
+    /// selects.
+    BasesMismatch { bases: usize, expected: usize },
+}
+
+impl Error for MultiexpError {}
+
+impl fmt::Display for MultiexpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            MultiexpError::DensityMismatch {
+                query_size,
+                exponents,
+            } => write!(
+                f,
+                "density map has query size {}, but there are {} exponents",
+                query_size, exponents
+            ),
+            MultiexpError::BasesMismatch { bases, expected } => {
+                write!(f, "expected {} bases, but there are {}", expected, bases)
+            }
+        }
+    }
+}
+
+impl From<MultiexpError> for SynthesisError {
+    fn from(_: MultiexpError) -> SynthesisError {
+        SynthesisError::LengthMismatch
+    }
+}
+
+/// Like [`multiexp`], but checks up front that the density map, bases and
+/// exponents have matching lengths, rather than panicking or failing part way.
+///
+/// The bases are those of `bases.0` from index `bases.1` on, and there must be
+/// exactly one for each exponent selected by the density map.
+pub fn try_multiexp<Q, D, G>(
+    pool: &Worker,
+    bases: (Arc<Vec<<G as PrimeCurve>::Affine>>, usize),
+    density_map: D,
+    exponents: Arc<Vec<Exponent<G::Scalar>>>,
+) -> Result<Waiter<Result<G, SynthesisError>>, MultiexpError>
+where
+    for<'a> &'a Q: QueryDensity,
+    D: Send + Sync + 'static + Clone + AsRef<Q>,
+    G: PrimeCurve,
+    G::Scalar: PrimeFieldBits,
+{
+    if let Some(query_size) = density_map.as_ref().get_query_size() {
+        if query_size != exponents.len() {
+            return Err(MultiexpError::DensityMismatch {
+                query_size,
+                exponents: exponents.len(),
+            });
+        }
+    }
+
+    let expected = density_map
+        .as_ref()
+        .iter()
+        .take(exponents.len())
+        .filter(|&d| d)
+        .count();
+    let available = bases.0.len().saturating_sub(bases.1);
+    if available != expected {
+        return Err(MultiexpError::BasesMismatch {
+            bases: available,
+            expected,
+        });
+    }
+
+    Ok(multiexp(pool, bases, density_map, exponents))
+}
+
 #[cfg(feature = "pairing")]
 #[test]
 fn test_with_bls12() {
@@ -376,3 +547,105 @@ fn test_with_bls12() {
 
     assert_eq!(naive, fast);
 }
+
+#[cfg(feature = "pairing")]
+#[test]
+fn small_matches_windowed() {
+    use bls12_381::{G1Affine, G1Projective, Scalar};
+    use ff::Field;
+    use group::{Curve, Group};
+
+    let mut rng = crate::testing::test_rng();
+    for n in 1..=64 {
+        let bases = Arc::new(
+            (0..n)
+                .map(|_| G1Projective::random(&mut rng).to_affine())
+                .collect::<Vec<G1Affine>>(),
+        );
+        // Mix in the trivial exponents, which both paths special-case.
+        let exponents = Arc::new(
+            (0..n)
+                .map(|i| match i % 5 {
+                    0 => Scalar::zero(),
+                    1 => Scalar::one(),
+                    _ => Scalar::random(&mut rng),
+                })
+                .map(Exponent::from)
+                .collect::<Vec<_>>(),
+        );
+        let small: G1Projective =
+            multiexp_small((bases.clone(), 0), FullDensity, exponents.clone()).unwrap();
+        let windowed: G1Projective =
+            multiexp_inner((bases.clone(), 0), FullDensity, exponents, 3).unwrap();
+        assert_eq!(small, windowed);
+    }
+}
+
+#[cfg(feature = "pairing")]
+#[test]
+fn try_multiexp_lengths() {
+    use bls12_381::{G1Affine, G1Projective, Scalar};
+
+    let pool = Worker::new();
+    let bases = Arc::new(vec![G1Affine::generator(); 4]);
+    let exponents = |n: usize| Arc::new((0..n).map(|_| Scalar::one().into()).collect());
+    let g = G1Projective::generator();
+
+    // One base per exponent.
+    let acc: G1Projective = try_multiexp(&pool, (bases.clone(), 0), FullDensity, exponents(4))
+        .unwrap()
+        .wait()
+        .unwrap();
+    assert_eq!(acc, g * Scalar::from(4));
+    assert_eq!(
+        try_multiexp::<_, _, G1Projective>(&pool, (bases.clone(), 0), FullDensity, exponents(3))
+            .err(),
+        Some(MultiexpError::BasesMismatch {
+            bases: 4,
+            expected: 3
+        })
+    );
+    assert_eq!(
+        try_multiexp::<_, _, G1Projective>(&pool, (bases.clone(), 0), FullDensity, exponents(5))
+            .err(),
+        Some(MultiexpError::BasesMismatch {
+            bases: 4,
+            expected: 5
+        })
+    );
+
+    // Only the selected exponents have bases.
+    let mut density = DensityTracker::new();
+    for i in 0..6 {
+        density.add_element();
+        if i % 2 == 0 {
+            density.inc(i);
+        }
+    }
+    let density = Arc::new(density);
+    let acc: G1Projective = try_multiexp(&pool, (bases.clone(), 1), density.clone(), exponents(6))
+        .unwrap()
+        .wait()
+        .unwrap();
+    assert_eq!(acc, g * Scalar::from(3));
+    assert_eq!(
+        try_multiexp::<_, _, G1Projective>(
+            &pool,
+            (bases.clone(), 0),
+            density.clone(),
+            exponents(6)
+        )
+        .err(),
+        Some(MultiexpError::BasesMismatch {
+            bases: 4,
+            expected: 3
+        })
+    );
+    assert_eq!(
+        try_multiexp::<_, _, G1Projective>(&pool, (bases, 1), density, exponents(5)).err(),
+        Some(MultiexpError::DensityMismatch {
+            query_size: 6,
+            exponents: 5
+        })
+    );
+}
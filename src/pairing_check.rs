@@ -0,0 +1,105 @@
+//! A builder for pairing product equations, checked with a single multi-Miller
+//! loop and final exponentiation.
+//!
+//! The [`mirage`](crate::mirage) and [`kw15`](crate::kw15) verifiers are both
+//! built on [`PairingCheck`], which can also be used to check custom equations
+//! over the same keys.
+
+use group::Group;
+use pairing::{MillerLoopResult, MultiMillerLoop};
+
+/// The equation `prod_i e(g1_i, g2_i) = target`, built one term at a time.
+///
+/// Negate one side of a term to move it across the equation:
+/// `e(a, b) = e(c, d)` is checked as `e(a, b) * e(c, -d) = 1`.
+pub struct PairingCheck<'a, E: MultiMillerLoop> {
+    terms: Vec<(&'a E::G1Affine, &'a E::G2Prepared)>,
+}
+
+impl<'a, E: MultiMillerLoop> Default for PairingCheck<'a, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, E: MultiMillerLoop> PairingCheck<'a, E> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// An empty check, with room for `capacity` terms.
+    pub fn with_capacity(capacity: usize) -> Self {
+        PairingCheck {
+            terms: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Multiply the product by `e(g1, g2)`.
+    pub fn add(&mut self, g1: &'a E::G1Affine, g2: &'a E::G2Prepared) -> &mut Self {
+        self.terms.push((g1, g2));
+        self
+    }
+
+    /// The number of terms added so far.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The product of the pairings of the terms.
+    pub fn product(&self) -> E::Gt {
+        E::multi_miller_loop(&self.terms).final_exponentiation()
+    }
+
+    /// Whether the product of the pairings is the identity. An empty product
+    /// is.
+    pub fn finalize(&self) -> bool {
+        bool::from(self.product().is_identity())
+    }
+
+    /// Whether the product of the pairings is `target`, compared in constant
+    /// time.
+    pub fn finalize_eq(&self, target: &E::Gt) -> bool {
+        bool::from((*target - self.product()).is_identity())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls12_381::{Bls12, G1Affine, G2Affine, G2Prepared, Scalar};
+    use group::Curve;
+    use pairing::Engine;
+
+    #[test]
+    fn equations() {
+        let a = Scalar::from(7);
+        let p = G1Affine::generator();
+        let q = G2Prepared::from(G2Affine::generator());
+        let ap = (p * a).to_affine();
+        let aq = G2Prepared::from((G2Affine::generator() * a).to_affine());
+        let neg_q = G2Prepared::from(-G2Affine::generator());
+
+        // e(aP, Q) = e(P, aQ)
+        let mut check = PairingCheck::<Bls12>::new();
+        check.add(&ap, &neg_q).add(&p, &aq);
+        assert_eq!(check.len(), 2);
+        assert!(check.finalize());
+
+        // e(P, Q) != e(aP, Q)
+        let mut check = PairingCheck::<Bls12>::new();
+        check.add(&p, &q).add(&ap, &neg_q);
+        assert!(!check.finalize());
+
+        let target = Bls12::pairing(&ap, &G2Affine::generator());
+        let mut check = PairingCheck::<Bls12>::new();
+        check.add(&p, &aq);
+        assert!(check.finalize_eq(&target));
+        assert!(!check.finalize_eq(&Bls12::pairing(&p, &G2Affine::generator())));
+
+        assert!(PairingCheck::<Bls12>::new().finalize());
+    }
+}
@@ -0,0 +1,342 @@
+//! Export of constraint systems to the JSON R1CS format used by circom/snarkjs.
+//!
+//! The schema matches the output of `snarkjs r1cs export json`:
+//!
+//! ```text
+//! {
+//!   "n8": <bytes per field element>,
+//!   "prime": "<field modulus, decimal>",
+//!   "nVars": <number of wires, including the constant one>,
+//!   "nOutputs": 0,
+//!   "nPubInputs": <public inputs, excluding the constant one>,
+//!   "nPrvInputs": <private (auxiliary) variables>,
+//!   "nLabels": <same as nVars>,
+//!   "nConstraints": <number of constraints>,
+//!   "constraints": [[{"<wire>": "<coeff>", ...}, {A}, {B}, {C}], ...]
+//! }
+//! ```
+//!
+//! Wire 0 is the constant one, followed by the public inputs and then the
+//! auxiliary variables, each in allocation order. Coefficients are decimal
+//! strings of their canonical representatives.
+
+use ff::{PrimeField, PrimeFieldBits};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use crate::cc::{CcCircuit, CcConstraintSystem};
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// A constraint system that records the constraints (and, if available, the
+/// witness) of a circuit, for export.
+pub struct R1csExtractor<Scalar: PrimeField> {
+    input_assignment: Vec<Option<Scalar>>,
+    aux_assignment: Vec<Option<Scalar>>,
+    constraints: Vec<[LinearCombination<Scalar>; 3]>,
+    num_aux_blocks: usize,
+}
+
+impl<Scalar: PrimeField> Default for R1csExtractor<Scalar> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Scalar: PrimeField> R1csExtractor<Scalar> {
+    /// Create an empty extractor, with the "one" input allocated.
+    pub fn new() -> Self {
+        R1csExtractor {
+            input_assignment: vec![Some(Scalar::one())],
+            aux_assignment: vec![],
+            constraints: vec![],
+            num_aux_blocks: 0,
+        }
+    }
+
+    /// Synthesize `circuit` into a new extractor.
+    pub fn from_circuit<C: CcCircuit<Scalar>>(circuit: C) -> Result<Self, SynthesisError> {
+        let mut extractor = Self::new();
+        circuit.synthesize(&mut extractor)?;
+        Ok(extractor)
+    }
+
+    /// The number of wires, including the constant one.
+    pub fn num_variables(&self) -> usize {
+        self.input_assignment.len() + self.aux_assignment.len()
+    }
+
+    /// The number of public inputs, including the constant one.
+    pub fn num_inputs(&self) -> usize {
+        self.input_assignment.len()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// The full assignment in wire order, if every variable had a value
+    /// during synthesis.
+    pub fn witness(&self) -> Option<Vec<Scalar>> {
+        self.input_assignment
+            .iter()
+            .chain(self.aux_assignment.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn wire(&self, var: Variable) -> usize {
+        match var.get_unchecked() {
+            Index::Input(i) => i,
+            Index::Aux(i) => self.input_assignment.len() + i,
+        }
+    }
+}
+
+impl<Scalar: PrimeFieldBits> R1csExtractor<Scalar> {
+    /// Convert the recorded constraints into the snarkjs JSON schema.
+    pub fn to_json(&self) -> R1csJson {
+        let lc_to_json = |lc: &LinearCombination<Scalar>| {
+            let mut terms: BTreeMap<usize, Scalar> = BTreeMap::new();
+            for (var, coeff) in lc.as_ref() {
+                *terms.entry(self.wire(*var)).or_insert_with(Scalar::zero) += coeff;
+            }
+            terms
+                .into_iter()
+                .filter(|(_, coeff)| !coeff.is_zero_vartime())
+                .map(|(wire, coeff)| (wire.to_string(), field_to_decimal(&coeff)))
+                .collect()
+        };
+
+        let n_vars = self.num_variables();
+        R1csJson {
+            n8: field_n8::<Scalar>(),
+            prime: modulus_decimal::<Scalar>(),
+            n_vars,
+            n_outputs: 0,
+            n_pub_inputs: self.input_assignment.len() - 1,
+            n_prv_inputs: self.aux_assignment.len(),
+            n_labels: n_vars,
+            n_constraints: self.constraints.len(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|[a, b, c]| [lc_to_json(a), lc_to_json(b), lc_to_json(c)])
+                .collect(),
+        }
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.to_json().write(writer)
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for R1csExtractor<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // The witness is optional: we only need it to export an assignment.
+        self.aux_assignment.push(f().ok());
+
+        Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.input_assignment.push(f().ok());
+
+        Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.constraints.push([
+            a(LinearCombination::zero()),
+            b(LinearCombination::zero()),
+            c(LinearCombination::zero()),
+        ]);
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+impl<Scalar: PrimeField> CcConstraintSystem<Scalar> for R1csExtractor<Scalar> {
+    fn end_aux_block<A, AR>(&mut self, _: A) -> Result<(), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // Plain R1CS has no notion of committed blocks.
+        self.num_aux_blocks += 1;
+        Ok(())
+    }
+
+    fn current_aux_block(&self) -> usize {
+        self.num_aux_blocks
+    }
+
+    fn alloc_random<A, AR>(
+        &mut self,
+        annotation: A,
+    ) -> Result<(Variable, Option<Scalar>), SynthesisError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // Random coins are public inputs whose value is only known to a prover.
+        let var = self.alloc_input(annotation, || Err(SynthesisError::AssignmentMissing))?;
+        Ok((var, None))
+    }
+}
+
+/// An R1CS instance in the snarkjs JSON schema (see the [module docs](self)).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct R1csJson {
+    pub n8: usize,
+    pub prime: String,
+    pub n_vars: usize,
+    pub n_outputs: usize,
+    pub n_pub_inputs: usize,
+    pub n_prv_inputs: usize,
+    pub n_labels: usize,
+    pub n_constraints: usize,
+    pub constraints: Vec<[BTreeMap<String, String>; 3]>,
+}
+
+impl R1csJson {
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer(writer, self).map_err(io::Error::from)
+    }
+
+    pub fn read<R: Read>(reader: R) -> io::Result<Self> {
+        serde_json::from_reader(reader).map_err(io::Error::from)
+    }
+
+    /// Check whether `witness` (in wire order, starting with the constant one)
+    /// satisfies every constraint.
+    ///
+    /// Fails if the instance is over a different field or is malformed.
+    pub fn is_satisfied<S: PrimeFieldBits>(&self, witness: &[S]) -> io::Result<bool> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        if self.prime != modulus_decimal::<S>() {
+            return Err(invalid("R1CS is over a different field"));
+        }
+        if witness.len() != self.n_vars {
+            return Err(invalid("witness length does not match the number of wires"));
+        }
+
+        let eval = |lc: &BTreeMap<String, String>| -> io::Result<S> {
+            let mut acc = S::zero();
+            for (wire, coeff) in lc {
+                let wire: usize = wire.parse().map_err(|_| invalid("invalid wire index"))?;
+                let value = witness
+                    .get(wire)
+                    .ok_or_else(|| invalid("wire index out of range"))?;
+                let coeff =
+                    S::from_str_vartime(coeff).ok_or_else(|| invalid("invalid coefficient"))?;
+                acc += coeff * value;
+            }
+            Ok(acc)
+        };
+
+        for [a, b, c] in &self.constraints {
+            if eval(a)? * eval(b)? != eval(c)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The number of bytes snarkjs uses to store one field element.
+fn field_n8<S: PrimeField>() -> usize {
+    ((S::NUM_BITS as usize - 1) / 64 + 1) * 8
+}
+
+fn modulus_decimal<S: PrimeFieldBits>() -> String {
+    bits_to_decimal(S::char_le_bits().iter().by_vals())
+}
+
+fn field_to_decimal<S: PrimeFieldBits>(s: &S) -> String {
+    bits_to_decimal(s.to_le_bits().iter().by_vals())
+}
+
+/// Render a little-endian bit string as a decimal number.
+fn bits_to_decimal<I: DoubleEndedIterator<Item = bool>>(bits: I) -> String {
+    const BASE: u64 = 1_000_000_000;
+
+    // Little-endian limbs in base 10^9.
+    let mut limbs: Vec<u64> = vec![0];
+    for bit in bits.rev() {
+        let mut carry = bit as u64;
+        for limb in limbs.iter_mut() {
+            let v = *limb * 2 + carry;
+            *limb = v % BASE;
+            carry = v / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+
+    let mut limbs = limbs.into_iter().rev();
+    let mut out = limbs.next().unwrap().to_string();
+    for limb in limbs {
+        out.push_str(&format!("{:09}", limb));
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls12_381::Scalar;
+
+    #[test]
+    fn decimal_rendering() {
+        assert_eq!(field_to_decimal(&Scalar::zero()), "0");
+        assert_eq!(
+            field_to_decimal(&Scalar::from(1_000_000_000_123)),
+            "1000000000123"
+        );
+        assert_eq!(
+            modulus_decimal::<Scalar>(),
+            "52435875175126190479447740508185965837690552500527637822603658699938581184513"
+        );
+        assert_eq!(
+            Scalar::from_str_vartime(&field_to_decimal(&-Scalar::one())),
+            Some(-Scalar::one())
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Deterministic randomness for reproducible tests.
+//!
+//! Available to downstream crates with the `test-utils` feature.
+
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+
+/// The seed of [`test_rng`].
+pub const DEFAULT_SEED: [u8; 32] = [0; 32];
+
+/// An RNG seeded with `seed`, so that the parameters, witnesses and proofs
+/// drawn from it are the same on every run.
+///
+/// Anyone who knows the seed can reproduce its output, so it must never be
+/// used to generate real parameters or proofs.
+pub fn seeded_rng(seed: [u8; 32]) -> ChaChaRng {
+    ChaChaRng::from_seed(seed)
+}
+
+/// [`seeded_rng`] with [`DEFAULT_SEED`].
+pub fn test_rng() -> ChaChaRng {
+    seeded_rng(DEFAULT_SEED)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_core::RngCore;
+
+    #[test]
+    fn reproducible() {
+        assert_eq!(test_rng().next_u64(), test_rng().next_u64());
+        assert_eq!(seeded_rng(DEFAULT_SEED).next_u64(), test_rng().next_u64());
+        assert_ne!(seeded_rng([1; 32]).next_u64(), test_rng().next_u64());
+    }
+}
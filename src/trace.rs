@@ -2,11 +2,12 @@
 #![allow(missing_docs)]
 // from arkworks
 // print-trace requires std, so these imports are well-defined
+use std::cell::RefCell;
 pub use std::{
     format, println,
     string::{String, ToString},
     sync::atomic::{AtomicUsize, Ordering},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 thread_local! {
@@ -22,6 +23,178 @@ pub fn on() -> bool {
     })
 }
 
+/// Override whether tracing is on for the current thread, regardless of `TRACE`.
+pub fn set_enabled(enabled: bool) {
+    IS_ON.with(|i| i.set(Some(enabled)));
+}
+
+/// Run `f` with tracing turned on or off for the current thread, then restore
+/// the previous setting.
+pub fn with_enabled<T>(enabled: bool, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<bool>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            IS_ON.with(|i| i.set(self.0));
+        }
+    }
+
+    let _restore = Restore(IS_ON.with(|i| i.get()));
+    set_enabled(enabled);
+    f()
+}
+
+thread_local! {
+    static IS_JSON: std::cell::Cell<Option<bool>> = None.into();
+}
+
+/// Whether `TRACE_FORMAT=json` is set, in which case end events are emitted as
+/// single-line JSON objects (see [`json_event`]).
+pub fn json() -> bool {
+    IS_JSON.with(|i| {
+        if i.get().is_none() {
+            i.set(Some(matches!(
+                std::env::var("TRACE_FORMAT").as_deref(),
+                Ok("json")
+            )));
+        }
+        i.get().unwrap()
+    })
+}
+
+/// Format an end event as a single-line JSON object.
+pub fn json_event(msg: &str, depth: usize, nanos: u128) -> String {
+    use std::fmt::Write;
+
+    let mut line = String::with_capacity(msg.len() + 48);
+    line.push_str("{\"msg\":\"");
+    for c in msg.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            // Control characters, including DEL and the C1 range, are all
+            // escaped so the line stays printable.
+            c if c.is_control() => write!(line, "\\u{:04x}", c as u32).unwrap(),
+            c => line.push(c),
+        }
+    }
+    write!(line, "\",\"depth\":{},\"nanos\":{}}}", depth, nanos as u64).unwrap();
+    line
+}
+
+/// A timed span, and the spans that were timed within it.
+#[derive(Clone, Debug)]
+pub struct TimingNode {
+    pub msg: String,
+    pub children: Vec<TimingNode>,
+    pub duration: Duration,
+}
+
+#[derive(Default)]
+struct Collector {
+    /// The number of live [`CollectorGuard`]s.
+    active: usize,
+    /// Spans that have started but not ended, outermost first.
+    open: Vec<TimingNode>,
+    /// Completed outermost spans.
+    spans: Vec<TimingNode>,
+}
+
+thread_local! {
+    static COLLECTOR: RefCell<Collector> = RefCell::new(Collector::default());
+}
+
+/// Stops collecting spans when dropped.
+pub struct CollectorGuard {
+    _private: (),
+}
+
+impl Drop for CollectorGuard {
+    fn drop(&mut self) {
+        COLLECTOR.with(|c| {
+            let mut c = c.borrow_mut();
+            c.active -= 1;
+            if c.active == 0 {
+                c.open.clear();
+            }
+        });
+    }
+}
+
+/// Record the spans timed on the current thread into a tree, until the returned guard is
+/// dropped. The spans are retrieved with [`take_spans`].
+///
+/// Guards nest: collection stops when the last live guard is dropped.
+pub fn start_collecting() -> CollectorGuard {
+    COLLECTOR.with(|c| {
+        let mut c = c.borrow_mut();
+        if c.active == 0 {
+            c.open.clear();
+        }
+        c.active += 1;
+    });
+    CollectorGuard { _private: () }
+}
+
+/// Take the outermost spans completed so far on the current thread.
+pub fn take_spans() -> Vec<TimingNode> {
+    COLLECTOR.with(|c| std::mem::take(&mut c.borrow_mut().spans))
+}
+
+#[doc(hidden)]
+pub fn collect_start(msg: &str) {
+    COLLECTOR.with(|c| {
+        let mut c = c.borrow_mut();
+        if c.active > 0 {
+            c.open.push(TimingNode {
+                msg: msg.to_string(),
+                children: vec![],
+                duration: Duration::default(),
+            });
+        }
+    })
+}
+
+#[doc(hidden)]
+pub fn collect_end(duration: Duration) {
+    COLLECTOR.with(|c| {
+        let mut c = c.borrow_mut();
+        if let Some(mut node) = c.open.pop() {
+            node.duration = duration;
+            match c.open.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => c.spans.push(node),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = Default::default();
+}
+
+/// Print a line of trace output.
+#[doc(hidden)]
+pub fn emit(line: String) {
+    #[cfg(test)]
+    {
+        if CAPTURED.with(|c| c.borrow().is_some()) {
+            CAPTURED.with(|c| c.borrow_mut().as_mut().unwrap().push(line));
+            return;
+        }
+    }
+    println!("{}", line);
+}
+
+/// Run `f`, returning the lines of trace output it emits on the current
+/// thread instead of printing them.
+#[cfg(test)]
+fn capture_output(f: impl FnOnce()) -> Vec<String> {
+    CAPTURED.with(|c| *c.borrow_mut() = Some(vec![]));
+    f();
+    CAPTURED.with(|c| c.borrow_mut().take().unwrap())
+}
+
 pub static NUM_INDENT: AtomicUsize = AtomicUsize::new(0);
 pub const PAD_CHAR: &str = "·";
 
@@ -33,17 +206,19 @@ pub struct TimerInfo {
 #[macro_export]
 macro_rules! start_timer {
     ($msg:expr) => {{
-        use $crate::trace::{compute_indent, Instant, Ordering, ToString, NUM_INDENT};
+        use $crate::trace::{compute_indent, format, Instant, Ordering, ToString, NUM_INDENT};
 
         let msg = $msg();
         let start_info = "Start:";
         let indent_amount = 2 * NUM_INDENT.fetch_add(0, Ordering::Relaxed);
         let indent = compute_indent(indent_amount);
 
-        if $crate::trace::on() {
-            $crate::trace::println!("{}{:8} {}", indent, start_info, msg);
+        // JSON output only has end events.
+        if $crate::trace::on() && !$crate::trace::json() {
+            $crate::trace::emit(format!("{}{:8} {}", indent, start_info, msg));
         }
         NUM_INDENT.fetch_add(1, Ordering::Relaxed);
+        $crate::trace::collect_start(&msg.to_string());
         $crate::trace::TimerInfo {
             msg: msg.to_string(),
             time: Instant::now(),
@@ -59,42 +234,55 @@ macro_rules! end_timer {
     ($time:expr, $msg:expr) => {{
         use $crate::trace::{compute_indent, format, Ordering, NUM_INDENT};
 
+        // `start_timer!` always indents, so always unindent, even if tracing
+        // has since been turned off.
+        NUM_INDENT.fetch_sub(1, Ordering::Relaxed);
+        let elapsed = $time.time.elapsed();
+        $crate::trace::collect_end(elapsed);
+
         if $crate::trace::on() {
-            let time = $time.time;
-            let final_time = time.elapsed();
-            let final_time = {
-                let secs = final_time.as_secs();
-                let millis = final_time.subsec_millis();
-                let micros = final_time.subsec_micros() % 1000;
-                let nanos = final_time.subsec_nanos() % 1000;
-                if secs != 0 {
-                    format!("{}.{:03}s", secs, millis)
-                } else if millis > 0 {
-                    format!("{}.{:03}ms", millis, micros)
-                } else if micros > 0 {
-                    format!("{}.{:03}µs", micros, nanos)
-                } else {
-                    format!("{}ns", final_time.subsec_nanos())
-                }
-            };
-
-            let end_info = "End:";
             let message = format!("{} {}", $time.msg, $msg());
 
-            NUM_INDENT.fetch_sub(1, Ordering::Relaxed);
-            let indent_amount = 2 * NUM_INDENT.fetch_add(0, Ordering::Relaxed);
-            let indent = compute_indent(indent_amount);
-
-            // Todo: Recursively ensure that *entire* string is of appropriate
-            // width (not just message).
-            $crate::trace::println!(
-                "{}{:8} {:.<pad$}{}",
-                indent,
-                end_info,
-                message,
-                final_time,
-                pad = 75 - indent_amount
-            );
+            let depth = NUM_INDENT.fetch_add(0, Ordering::Relaxed);
+
+            if $crate::trace::json() {
+                $crate::trace::emit($crate::trace::json_event(
+                    &message,
+                    depth,
+                    elapsed.as_nanos(),
+                ));
+            } else {
+                let final_time = {
+                    let secs = elapsed.as_secs();
+                    let millis = elapsed.subsec_millis();
+                    let micros = elapsed.subsec_micros() % 1000;
+                    let nanos = elapsed.subsec_nanos() % 1000;
+                    if secs != 0 {
+                        format!("{}.{:03}s", secs, millis)
+                    } else if millis > 0 {
+                        format!("{}.{:03}ms", millis, micros)
+                    } else if micros > 0 {
+                        format!("{}.{:03}µs", micros, nanos)
+                    } else {
+                        format!("{}ns", elapsed.subsec_nanos())
+                    }
+                };
+
+                let end_info = "End:";
+                let indent_amount = 2 * depth;
+                let indent = compute_indent(indent_amount);
+
+                // Todo: Recursively ensure that *entire* string is of appropriate
+                // width (not just message).
+                $crate::trace::emit(format!(
+                    "{}{:8} {:.<pad$}{}",
+                    indent,
+                    end_info,
+                    message,
+                    final_time,
+                    pad = 75 - indent_amount
+                ));
+            }
         }
     }};
 }
@@ -106,3 +294,115 @@ pub fn compute_indent(indent_amount: usize) -> String {
     }
     indent
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_event_parses() {
+        let msg = "synthesis \"quoted\" \\ \n \u{7f} \u{85} \u{e9}";
+        let line = json_event(msg, 2, 1_234_567);
+        assert!(!line.chars().any(char::is_control));
+
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["msg"], msg);
+        assert_eq!(event["depth"], 2);
+        assert_eq!(event["nanos"], 1_234_567);
+    }
+
+    #[test]
+    fn json_output() {
+        IS_JSON.with(|i| i.set(Some(true)));
+        let lines = with_enabled(true, || {
+            capture_output(|| {
+                let outer = start_timer!(|| "outer");
+                let inner = start_timer!(|| "inner");
+                end_timer!(inner);
+                end_timer!(outer, || "done");
+            })
+        });
+        IS_JSON.with(|i| i.set(None));
+
+        // Only the end events are emitted, each a JSON object on its own line.
+        let events = lines
+            .iter()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["msg"], "inner ");
+        assert_eq!(events[1]["msg"], "outer done");
+        // `depth` is shared with timers on other threads, so only its type is
+        // checked here.
+        assert!(events.iter().all(|e| e["depth"].is_u64()));
+        assert!(events[0]["nanos"].as_u64().unwrap() <= events[1]["nanos"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn nested_collectors() {
+        let outer = start_collecting();
+        let a = start_timer!(|| "a");
+        let inner = start_collecting();
+        let b = start_timer!(|| "b");
+        end_timer!(b);
+        drop(inner);
+        // The outer guard is still collecting.
+        end_timer!(a);
+        drop(outer);
+
+        let spans = take_spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].msg, "a");
+        assert_eq!(spans[0].children.len(), 1);
+        assert_eq!(spans[0].children[0].msg, "b");
+    }
+
+    #[test]
+    fn collect_spans() {
+        let outside = start_timer!(|| "outside");
+        end_timer!(outside);
+
+        let guard = start_collecting();
+        let outer = start_timer!(|| "outer");
+        for _ in 0..2 {
+            let inner = start_timer!(|| "inner");
+            end_timer!(inner);
+        }
+        end_timer!(outer);
+        let last = start_timer!(|| "last");
+        end_timer!(last);
+        drop(guard);
+
+        let ignored = start_timer!(|| "ignored");
+        end_timer!(ignored);
+
+        let spans = take_spans();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].msg, "outer");
+        assert_eq!(spans[0].children.len(), 2);
+        assert!(spans[0].children.iter().all(|c| c.msg == "inner"));
+        assert!(spans[0].children[0].duration <= spans[0].duration);
+        assert_eq!(spans[1].msg, "last");
+        assert!(spans[1].children.is_empty());
+        assert!(take_spans().is_empty());
+    }
+
+    #[test]
+    fn enable_override() {
+        set_enabled(false);
+        assert!(!on());
+
+        let inner = with_enabled(true, || {
+            let timer = start_timer!(|| "traced");
+            end_timer!(timer);
+            on()
+        });
+        assert!(inner);
+        assert!(!on());
+
+        set_enabled(true);
+        assert!(with_enabled(true, on));
+        assert!(!with_enabled(false, on));
+        assert!(on());
+    }
+}